@@ -20,4 +20,10 @@ pub mod service {
 }
 pub use service::util::{service_fn, wrap_fn};
 
-pub type BoxError = Box<dyn std::error::Error>;
+/// 装箱的错误类型。
+///
+/// 启用`send`特性时，这与[`SendBoxError`]相同。
+pub type BoxError = body::BoxError;
+
+/// 可以跨线程移动的装箱错误类型。
+pub type SendBoxError = Box<dyn std::error::Error + Send + Sync>;
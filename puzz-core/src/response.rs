@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 
 use puzz_http::body::{Body, BodyExt, BoxBody, Bytes, MapErr, StreamBody};
+use puzz_http::marker::MaybeSend;
 use puzz_http::{header, HeaderMap, HeaderValue, StatusCode};
 
 use crate::BoxError;
@@ -82,7 +83,7 @@ impl IntoResponse for BoxBody {
 
 impl<S> IntoResponse for StreamBody<S>
 where
-    Self: Body + 'static,
+    Self: Body + MaybeSend + 'static,
     <Self as Body>::Error: Into<BoxError>,
 {
     fn into_response(self) -> Response {
@@ -92,8 +93,8 @@ where
 
 impl<B, F, E> IntoResponse for MapErr<B, F>
 where
-    B: Body + 'static,
-    F: FnMut(B::Error) -> E + 'static,
+    B: Body + MaybeSend + 'static,
+    F: FnMut(B::Error) -> E + MaybeSend + 'static,
     E: Into<BoxError>,
 {
     fn into_response(self) -> Response {
@@ -103,7 +104,7 @@ where
 
 impl<B> IntoResponse for Response<B>
 where
-    B: Body + 'static,
+    B: Body + MaybeSend + 'static,
     B::Error: Into<BoxError>,
 {
     fn into_response(self) -> Response {
@@ -4,27 +4,39 @@ use std::task::{Context, Poll};
 
 use bytes::Bytes;
 
-use super::{Body, BodyExt, SizeHint};
+use super::{Body, BodyExt, BoxError, SizeHint};
+use crate::marker::MaybeSend;
 
+#[cfg(not(feature = "send"))]
+type DynBody = Pin<Box<dyn Body<Error = BoxError>>>;
+
+// 正文只需要实现`Send`，`Mutex`使`BoxBody`同时实现`Sync`，轮询时通过`get_mut`访问不需要加锁。
+#[cfg(feature = "send")]
+type DynBody = std::sync::Mutex<Pin<Box<dyn Body<Error = BoxError> + Send>>>;
+
+/// 装箱的[`Body`]特征对象。
+///
+/// 启用`send`特性时，[`BoxBody`]实现了[`Send`]和[`Sync`]，装箱的正文必须实现[`Send`]。
 pub struct BoxBody {
-    inner: Pin<Box<dyn Body<Error = Box<dyn std::error::Error>>>>,
+    inner: DynBody,
 }
 
 impl BoxBody {
     pub fn new<B>(body: B) -> Self
     where
-        B: Body + 'static,
-        B::Error: Into<Box<dyn std::error::Error>>,
+        B: Body + MaybeSend + 'static,
+        B::Error: Into<BoxError>,
     {
         Self {
-            inner: Box::pin(body.map_err(Into::into)),
+            inner: DynBody::from(Box::pin(body.map_err(Into::into)) as Pin<Box<_>>),
         }
     }
 }
 
 impl Body for BoxBody {
-    type Error = Box<dyn std::error::Error>;
+    type Error = BoxError;
 
+    #[cfg(not(feature = "send"))]
     fn poll_next(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
@@ -32,9 +44,29 @@ impl Body for BoxBody {
         self.inner.as_mut().poll_next(cx)
     }
 
+    #[cfg(feature = "send")]
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        match self.inner.get_mut() {
+            Ok(inner) => inner.as_mut().poll_next(cx),
+            Err(poisoned) => poisoned.into_inner().as_mut().poll_next(cx),
+        }
+    }
+
+    #[cfg(not(feature = "send"))]
     fn size_hint(&self) -> SizeHint {
         self.inner.size_hint()
     }
+
+    #[cfg(feature = "send")]
+    fn size_hint(&self) -> SizeHint {
+        match self.inner.lock() {
+            Ok(inner) => inner.size_hint(),
+            Err(poisoned) => poisoned.into_inner().size_hint(),
+        }
+    }
 }
 
 impl fmt::Debug for BoxBody {
@@ -1,4 +1,5 @@
-use super::{Body, BodyStream, BoxBody, MapErr, Next};
+use super::{Body, BodyStream, BoxBody, BoxError, MapErr, Next};
+use crate::marker::MaybeSend;
 
 pub trait BodyExt: Body {
     fn next(&mut self) -> Next<'_, Self>
@@ -25,8 +26,8 @@ pub trait BodyExt: Body {
 
     fn boxed(self) -> BoxBody
     where
-        Self: Sized + 'static,
-        Self::Error: Into<Box<dyn std::error::Error>>,
+        Self: Sized + MaybeSend + 'static,
+        Self::Error: Into<BoxError>,
     {
         BoxBody::new(self)
     }
@@ -26,6 +26,18 @@ pub use stream::{BodyStream, StreamBody};
 mod size_hint;
 pub use size_hint::SizeHint;
 
+/// [`BoxBody`]的错误类型。
+///
+/// 启用`send`特性时，错误也必须实现[`Send`]和[`Sync`]。
+#[cfg(not(feature = "send"))]
+pub type BoxError = Box<dyn std::error::Error>;
+
+/// [`BoxBody`]的错误类型。
+///
+/// 启用`send`特性时，错误也必须实现[`Send`]和[`Sync`]。
+#[cfg(feature = "send")]
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
 /// 请求或响应的正文特征。
 pub trait Body {
     /// 正文产生的错误。
@@ -0,0 +1,223 @@
+//! 与`http`和`http-body`类型互相转换。
+//!
+//! [`Request`]和[`Response`]可以与`http::Request`和`http::Response`互相转换，
+//! 正文使用[`IntoHttpBody`]和[`FromHttpBody`]转换。
+//!
+//! 两者的扩展类型不同：从`http`类型转换时，原来的扩展作为`http::Extensions`插入新的扩展，
+//! 转换回`http`类型时再取出。启用`send`特性时，本crate的扩展也会在转换为`http`类型时保存，
+//! 并在转换回来时恢复；否则转换为`http`类型时这些扩展将被丢弃。
+//!
+//! # 例子
+//!
+//! ```
+//! use puzz_http::compat::{FromHttpBody, IntoHttpBody};
+//! use puzz_http::Request;
+//!
+//! let request = http::Request::builder()
+//!     .uri("/users")
+//!     .extension(5i32)
+//!     .body(())
+//!     .unwrap();
+//!
+//! let request = Request::from(request).map(IntoHttpBody::new);
+//! assert_eq!(request.uri(), "/users");
+//!
+//! let request = http::Request::from(request);
+//! assert_eq!(request.extensions().get::<i32>(), Some(&5));
+//! ```
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Buf, Bytes};
+use pin_project_lite::pin_project;
+
+use crate::body::{Body, SizeHint};
+use crate::{request, response, Extensions, Request, Response};
+
+// 启用`send`特性时，保存在`http::Extensions`中的本crate的扩展。
+#[cfg(feature = "send")]
+struct Stashed(Extensions);
+
+fn from_http_extensions(extensions: http::Extensions) -> Extensions {
+    #[cfg(feature = "send")]
+    let (mut converted, extensions) = {
+        let mut extensions = extensions;
+        let converted = extensions
+            .remove::<Stashed>()
+            .map(|stashed| stashed.0)
+            .unwrap_or_default();
+        (converted, extensions)
+    };
+    #[cfg(not(feature = "send"))]
+    let mut converted = Extensions::new();
+
+    if !extensions.is_empty() {
+        converted.insert(extensions);
+    }
+    converted
+}
+
+fn into_http_extensions(mut extensions: Extensions) -> http::Extensions {
+    let converted = extensions.remove::<http::Extensions>().unwrap_or_default();
+
+    #[cfg(feature = "send")]
+    let converted = {
+        let mut converted = converted;
+        if !extensions.is_empty() {
+            converted.insert(Stashed(extensions));
+        }
+        converted
+    };
+
+    converted
+}
+
+impl<B> From<http::Request<B>> for Request<B> {
+    fn from(request: http::Request<B>) -> Self {
+        let (parts, body) = request.into_parts();
+        let mut head = request::Head::default();
+        head.method = parts.method;
+        head.uri = parts.uri;
+        head.version = parts.version;
+        head.headers = parts.headers;
+        head.extensions = from_http_extensions(parts.extensions);
+        Request::from_head(head, body)
+    }
+}
+
+impl<B> From<Request<B>> for http::Request<B> {
+    fn from(request: Request<B>) -> Self {
+        let (head, body) = request.into_head();
+        let mut request = http::Request::new(body);
+        *request.method_mut() = head.method;
+        *request.uri_mut() = head.uri;
+        *request.version_mut() = head.version;
+        *request.headers_mut() = head.headers;
+        *request.extensions_mut() = into_http_extensions(head.extensions);
+        request
+    }
+}
+
+impl<B> From<http::Response<B>> for Response<B> {
+    fn from(response: http::Response<B>) -> Self {
+        let (parts, body) = response.into_parts();
+        let mut head = response::Head::default();
+        head.status = parts.status;
+        head.version = parts.version;
+        head.headers = parts.headers;
+        head.extensions = from_http_extensions(parts.extensions);
+        Response::from_head(head, body)
+    }
+}
+
+impl<B> From<Response<B>> for http::Response<B> {
+    fn from(response: Response<B>) -> Self {
+        let (head, body) = response.into_head();
+        let mut response = http::Response::new(body);
+        *response.status_mut() = head.status;
+        *response.version_mut() = head.version;
+        *response.headers_mut() = head.headers;
+        *response.extensions_mut() = into_http_extensions(head.extensions);
+        response
+    }
+}
+
+pin_project! {
+    /// 将[`Body`]转换为`http_body::Body`。
+    #[derive(Debug, Default)]
+    pub struct IntoHttpBody<B> {
+        #[pin]
+        inner: B,
+    }
+}
+
+impl<B> IntoHttpBody<B> {
+    pub fn new(inner: B) -> Self {
+        Self { inner }
+    }
+
+    /// 获取内部的正文。
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B> http_body::Body for IntoHttpBody<B>
+where
+    B: Body,
+{
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_data(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        self.project().inner.poll_next(cx)
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn size_hint(&self) -> http_body::SizeHint {
+        let size_hint = self.inner.size_hint();
+        let mut converted = http_body::SizeHint::new();
+        converted.set_lower(size_hint.lower());
+        if let Some(upper) = size_hint.upper() {
+            converted.set_upper(upper);
+        }
+        converted
+    }
+}
+
+pin_project! {
+    /// 将`http_body::Body`转换为[`Body`]，尾部标头将被忽略。
+    #[derive(Debug, Default)]
+    pub struct FromHttpBody<B> {
+        #[pin]
+        inner: B,
+    }
+}
+
+impl<B> FromHttpBody<B> {
+    pub fn new(inner: B) -> Self {
+        Self { inner }
+    }
+
+    /// 获取内部的正文。
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B> Body for FromHttpBody<B>
+where
+    B: http_body::Body,
+{
+    type Error = B::Error;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        self.project()
+            .inner
+            .poll_data(cx)
+            .map_ok(|mut data| data.copy_to_bytes(data.remaining()))
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let size_hint = self.inner.size_hint();
+        let mut converted = SizeHint::new();
+        converted.set_lower(size_hint.lower());
+        if let Some(upper) = size_hint.upper() {
+            converted.set_upper(upper);
+        }
+        converted
+    }
+}
@@ -3,7 +3,14 @@ use std::collections::HashMap;
 use std::fmt;
 use std::hash::{BuildHasherDefault, Hasher};
 
-type AnyMap = HashMap<TypeId, Box<dyn Any>, BuildHasherDefault<IdHasher>>;
+use crate::marker::MaybeSendSync;
+
+#[cfg(not(feature = "send"))]
+type AnyBox = Box<dyn Any>;
+#[cfg(feature = "send")]
+type AnyBox = Box<dyn Any + Send + Sync>;
+
+type AnyMap = HashMap<TypeId, AnyBox, BuildHasherDefault<IdHasher>>;
 
 // 使用`TypeId`作为键时，不需要进行散列。
 #[derive(Default)]
@@ -27,7 +34,7 @@ impl Hasher for IdHasher {
 
 /// 请求和响应的扩展组件。
 ///
-/// 请求和响应可以使用扩展来存储额外的数据。
+/// 请求和响应可以使用扩展来存储额外的数据。启用`send`特性时，插入的类型必须实现[`Send`]和[`Sync`]。
 #[derive(Default)]
 pub struct Extensions {
     // 如果从不使用扩展，则无需携带空的`HashMap`，并且这只占用一个字长。
@@ -56,7 +63,7 @@ impl Extensions {
     /// assert!(ext.insert(4u8).is_none());
     /// assert_eq!(ext.insert(9i32), Some(5i32));
     /// ```
-    pub fn insert<T: 'static>(&mut self, val: T) -> Option<T> {
+    pub fn insert<T: MaybeSendSync + 'static>(&mut self, val: T) -> Option<T> {
         self.map
             .get_or_insert_with(|| Box::new(HashMap::default()))
             .insert(TypeId::of::<T>(), Box::new(val))
@@ -1,6 +1,9 @@
 mod extensions;
 
 pub mod body;
+#[cfg(feature = "http-body")]
+pub mod compat;
+pub mod marker;
 pub mod request;
 pub mod response;
 
@@ -0,0 +1,32 @@
+//! 根据`send`特性决定是否要求线程安全的标记特征。
+//!
+//! 启用`send`特性时，[`MaybeSend`]等同于[`Send`]，[`MaybeSendSync`]等同于[`Send`] + [`Sync`]；
+//! 否则所有类型都实现了这两个特征。
+
+/// 启用`send`特性时要求类型实现[`Send`]。
+#[cfg(feature = "send")]
+pub trait MaybeSend: Send {}
+
+#[cfg(feature = "send")]
+impl<T> MaybeSend for T where T: Send + ?Sized {}
+
+/// 启用`send`特性时要求类型实现[`Send`]。
+#[cfg(not(feature = "send"))]
+pub trait MaybeSend {}
+
+#[cfg(not(feature = "send"))]
+impl<T> MaybeSend for T where T: ?Sized {}
+
+/// 启用`send`特性时要求类型实现[`Send`]和[`Sync`]。
+#[cfg(feature = "send")]
+pub trait MaybeSendSync: Send + Sync {}
+
+#[cfg(feature = "send")]
+impl<T> MaybeSendSync for T where T: Send + Sync + ?Sized {}
+
+/// 启用`send`特性时要求类型实现[`Send`]和[`Sync`]。
+#[cfg(not(feature = "send"))]
+pub trait MaybeSendSync {}
+
+#[cfg(not(feature = "send"))]
+impl<T> MaybeSendSync for T where T: ?Sized {}
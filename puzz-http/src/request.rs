@@ -1,5 +1,6 @@
 use std::fmt;
 
+use crate::marker::MaybeSendSync;
 use crate::{Extensions, HeaderMap, HeaderName, HeaderValue, Method, Result, Uri, Version};
 
 /// 一个HTTP请求。
@@ -636,7 +637,7 @@ impl Builder {
     /// assert_eq!(req.extensions().get::<&'static str>(),
     ///            Some(&"My Extension"));
     /// ```
-    pub fn extension<T: 'static>(self, extension: T) -> Builder {
+    pub fn extension<T: MaybeSendSync + 'static>(self, extension: T) -> Builder {
         self.and_then(move |mut head| {
             head.extensions.insert(extension);
             Ok(head)
@@ -1,5 +1,6 @@
 use std::fmt;
 
+use crate::marker::MaybeSendSync;
 use crate::{Extensions, HeaderMap, HeaderName, HeaderValue, Result, StatusCode, Version};
 
 /// 一个HTTP响应。
@@ -553,7 +554,7 @@ impl Builder {
     /// ```
     pub fn extension<T>(self, extension: T) -> Builder
     where
-        T: 'static,
+        T: MaybeSendSync + 'static,
     {
         self.and_then(move |mut head| {
             head.extensions.insert(extension);
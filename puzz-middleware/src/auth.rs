@@ -0,0 +1,534 @@
+//! 身份认证。
+//!
+//! 提供[`basic`]、[`bearer`]和[`api_key`]三种认证方式。认证方式从请求中提取凭据后交给异步的验证函数，
+//! 验证成功时将验证函数返回的主体以[`Authenticated`]的形式插入请求扩展，
+//! 否则返回携带`WWW-Authenticate`质询的`401 Unauthorized`响应。
+
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures_core::ready;
+use pin_project_lite::pin_project;
+use puzz_core::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use puzz_core::http::marker::MaybeSendSync;
+use puzz_core::http::{HeaderName, HeaderValue, StatusCode};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::{Request, Response};
+
+const DEFAULT_REALM: &str = "Restricted";
+
+/// 创建一个使用HTTP Basic认证（RFC 7617）的[`Wrap`]。
+///
+/// 验证函数接收[`BasicCredentials`]，返回[`Some`]表示认证成功。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::http::StatusCode;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::auth::{self, Authenticated, BasicCredentials};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|request: Request| async move {
+///     let user = request.extensions().get::<Authenticated<String>>().unwrap();
+///     Ok::<_, Infallible>(format!("hello, {}!", **user))
+/// })
+/// .with(auth::basic(|credentials: BasicCredentials| async move {
+///     (credentials.username == "alice" && credentials.password == "secret")
+///         .then(|| credentials.username)
+/// }));
+///
+/// let request = Request::builder()
+///     .header("authorization", "Basic YWxpY2U6c2VjcmV0")
+///     .body(Default::default())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::OK);
+///
+/// let response = service.call(Request::default()).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+/// assert_eq!(
+///     response.headers()["www-authenticate"],
+///     r#"Basic realm="Restricted", charset="UTF-8""#
+/// );
+/// # }
+/// ```
+pub fn basic<V>(validator: V) -> AuthWrap<Basic, V> {
+    AuthWrap::new(Basic, validator)
+}
+
+/// 创建一个使用Bearer令牌认证（RFC 6750）的[`Wrap`]。
+///
+/// 验证函数接收`Authorization`标头中的令牌，返回[`Some`]表示认证成功。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::http::StatusCode;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::auth;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+///     .with(auth::bearer(|token: String| async move { (token == "abc").then(|| ()) }).realm("api"));
+///
+/// let request = Request::builder()
+///     .header("authorization", "Bearer xyz")
+///     .body(Default::default())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+/// assert_eq!(
+///     response.headers()["www-authenticate"],
+///     r#"Bearer realm="api", error="invalid_token""#
+/// );
+/// # }
+/// ```
+pub fn bearer<V>(validator: V) -> AuthWrap<Bearer, V> {
+    AuthWrap::new(Bearer, validator)
+}
+
+/// 创建一个使用API密钥认证的[`Wrap`]，密钥从`location`指定的请求标头或查询参数中提取。
+///
+/// 验证函数接收API密钥，返回[`Some`]表示认证成功。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::http::StatusCode;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::auth::{self, ApiKey};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+///     .with(auth::api_key(ApiKey::query("api_key"), |key: String| async move {
+///         (key == "k1").then(|| "team-a")
+///     }));
+///
+/// let request = Request::builder()
+///     .uri("/?api_key=k1")
+///     .body(Default::default())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+pub fn api_key<V>(location: ApiKey, validator: V) -> AuthWrap<ApiKey, V> {
+    AuthWrap::new(location, validator)
+}
+
+/// 认证成功的主体。
+///
+/// 由认证中间件插入请求扩展，其中的值是验证函数的返回值。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Authenticated<T>(pub T);
+
+impl<T> Authenticated<T> {
+    /// 获取其中的主体。
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Authenticated<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Authenticated<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// 认证方式，负责从请求中提取凭据并生成质询。
+pub trait Scheme {
+    /// 凭据的类型。
+    type Credentials;
+
+    /// 从请求中提取凭据，如果请求未携带凭据或凭据格式无效则返回[`None`]。
+    fn credentials<B>(&self, request: &Request<B>) -> Option<Self::Credentials>;
+
+    /// 生成`WWW-Authenticate`质询。
+    ///
+    /// `invalid`表示请求携带了凭据，但验证失败。
+    fn challenge(&self, realm: &str, invalid: bool) -> String;
+}
+
+/// HTTP Basic认证方式。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Basic;
+
+/// HTTP Basic认证的凭据。
+#[derive(Clone, PartialEq, Eq)]
+pub struct BasicCredentials {
+    /// 用户名。
+    pub username: String,
+    /// 密码。
+    pub password: String,
+}
+
+impl fmt::Debug for BasicCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BasicCredentials")
+            .field("username", &self.username)
+            .field("password", &"..")
+            .finish()
+    }
+}
+
+impl Scheme for Basic {
+    type Credentials = BasicCredentials;
+
+    fn credentials<B>(&self, request: &Request<B>) -> Option<Self::Credentials> {
+        let encoded = authorization(request, "Basic")?;
+        let decoded = String::from_utf8(STANDARD.decode(encoded).ok()?).ok()?;
+        let (username, password) = decoded.split_once(':')?;
+
+        Some(BasicCredentials {
+            username: username.to_owned(),
+            password: password.to_owned(),
+        })
+    }
+
+    fn challenge(&self, realm: &str, _: bool) -> String {
+        format!(r#"Basic realm="{}", charset="UTF-8""#, quote(realm))
+    }
+}
+
+/// Bearer令牌认证方式。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bearer;
+
+impl Scheme for Bearer {
+    type Credentials = String;
+
+    fn credentials<B>(&self, request: &Request<B>) -> Option<Self::Credentials> {
+        authorization(request, "Bearer").map(ToOwned::to_owned)
+    }
+
+    fn challenge(&self, realm: &str, invalid: bool) -> String {
+        if invalid {
+            format!(r#"Bearer realm="{}", error="invalid_token""#, quote(realm))
+        } else {
+            format!(r#"Bearer realm="{}""#, quote(realm))
+        }
+    }
+}
+
+/// API密钥认证方式，指定API密钥所在的位置。
+#[derive(Debug, Clone)]
+pub enum ApiKey {
+    /// 从请求标头中提取API密钥。
+    Header(HeaderName),
+    /// 从查询参数中提取API密钥。
+    Query(String),
+}
+
+impl ApiKey {
+    /// 从给定名称的请求标头中提取API密钥。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果`name`不是有效的标头名称，将会发生恐慌。
+    pub fn header<N>(name: N) -> Self
+    where
+        N: TryInto<HeaderName>,
+        N::Error: fmt::Debug,
+    {
+        Self::Header(name.try_into().expect("invalid header name"))
+    }
+
+    /// 从给定名称的查询参数中提取API密钥。
+    pub fn query<N>(name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        Self::Query(name.into())
+    }
+}
+
+impl Scheme for ApiKey {
+    type Credentials = String;
+
+    fn credentials<B>(&self, request: &Request<B>) -> Option<Self::Credentials> {
+        let key = match self {
+            ApiKey::Header(name) => request.headers().get(name)?.to_str().ok()?.to_owned(),
+            ApiKey::Query(name) => form_urlencoded::parse(request.uri().query()?.as_bytes())
+                .find(|(key, _)| key == name)?
+                .1
+                .into_owned(),
+        };
+
+        if key.is_empty() {
+            None
+        } else {
+            Some(key)
+        }
+    }
+
+    fn challenge(&self, realm: &str, _: bool) -> String {
+        let (location, name) = match self {
+            ApiKey::Header(name) => ("header", name.as_str()),
+            ApiKey::Query(name) => ("query", name.as_str()),
+        };
+        format!(
+            r#"ApiKey realm="{}", in="{}", name="{}""#,
+            quote(realm),
+            location,
+            quote(name)
+        )
+    }
+}
+
+/// 提取`Authorization`标头中指定认证方式的参数，认证方式不区分大小写。
+fn authorization<'a, B>(request: &'a Request<B>, scheme: &str) -> Option<&'a str> {
+    let value = request.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let (name, param) = value.split_once(' ')?;
+
+    if !name.eq_ignore_ascii_case(scheme) {
+        return None;
+    }
+
+    let param = param.trim();
+    if param.is_empty() {
+        None
+    } else {
+        Some(param)
+    }
+}
+
+fn quote(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(Clone)]
+pub struct AuthWrap<C, V> {
+    scheme: C,
+    validator: V,
+    realm: String,
+}
+
+impl<C, V> AuthWrap<C, V> {
+    pub fn new(scheme: C, validator: V) -> Self {
+        Self {
+            scheme,
+            validator,
+            realm: DEFAULT_REALM.to_owned(),
+        }
+    }
+
+    /// 设置质询中的保护域，默认为`Restricted`。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果`realm`包含控制字符，将会发生恐慌。
+    pub fn realm<R>(mut self, realm: R) -> Self
+    where
+        R: Into<String>,
+    {
+        let realm = realm.into();
+        if realm.chars().any(char::is_control) {
+            panic!("realm must not contain control characters");
+        }
+        self.realm = realm;
+        self
+    }
+}
+
+impl<S, C, V> Wrap<S> for AuthWrap<C, V>
+where
+    C: Scheme,
+{
+    type Service = Auth<S, C, V>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        let challenge = |invalid| {
+            HeaderValue::try_from(self.scheme.challenge(&self.realm, invalid))
+                .expect("invalid challenge")
+        };
+
+        Auth {
+            inner: Arc::new(service),
+            challenges: Arc::new([challenge(false), challenge(true)]),
+            scheme: self.scheme,
+            validator: self.validator,
+        }
+    }
+}
+
+impl<C, V> fmt::Debug for AuthWrap<C, V>
+where
+    C: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthWrap")
+            .field("scheme", &self.scheme)
+            .field("validator", &std::any::type_name::<V>())
+            .field("realm", &self.realm)
+            .finish()
+    }
+}
+
+pub struct Auth<S, C, V> {
+    // 验证完成后才会调用服务。
+    inner: Arc<S>,
+    // 未携带凭据和凭据无效时的质询。
+    challenges: Arc<[HeaderValue; 2]>,
+    scheme: C,
+    validator: V,
+}
+
+impl<S, C, V> Clone for Auth<S, C, V>
+where
+    C: Clone,
+    V: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            challenges: self.challenges.clone(),
+            scheme: self.scheme.clone(),
+            validator: self.validator.clone(),
+        }
+    }
+}
+
+impl<S, C, V, B, Fut, T> Service<Request<B>> for Auth<S, C, V>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+    C: Scheme,
+    V: Fn(C::Credentials) -> Fut,
+    Fut: Future<Output = Option<T>>,
+    T: MaybeSendSync + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = AuthFuture<S, Request<B>, Fut>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Request<B>) -> Self::Future {
+        match self.scheme.credentials(&request) {
+            Some(credentials) => AuthFuture::Validating {
+                fut: (self.validator)(credentials),
+                state: Some((self.inner.clone(), request, self.challenges.clone())),
+            },
+            None => AuthFuture::Rejected {
+                challenge: Some(self.challenges[0].clone()),
+            },
+        }
+    }
+}
+
+impl<S, C, V> fmt::Debug for Auth<S, C, V>
+where
+    S: fmt::Debug,
+    C: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Auth")
+            .field("inner", &self.inner)
+            .field("scheme", &self.scheme)
+            .field("validator", &std::any::type_name::<V>())
+            .finish()
+    }
+}
+
+pin_project! {
+    #[project = AuthFutureProj]
+    pub enum AuthFuture<S, Req, Fut>
+    where
+        S: Service<Req>,
+    {
+        Validating {
+            #[pin]
+            fut: Fut,
+            state: Option<(Arc<S>, Req, Arc<[HeaderValue; 2]>)>,
+        },
+        Calling {
+            #[pin]
+            fut: S::Future,
+        },
+        Rejected {
+            challenge: Option<HeaderValue>,
+        },
+    }
+}
+
+impl<S, B, Fut, T> Future for AuthFuture<S, Request<B>, Fut>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+    Fut: Future<Output = Option<T>>,
+    T: MaybeSendSync + 'static,
+{
+    type Output = Result<Response, S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                AuthFutureProj::Validating { fut, state } => {
+                    let principal = ready!(fut.poll(cx));
+                    let (inner, mut request, challenges) =
+                        state.take().expect("polled after completion");
+
+                    match principal {
+                        Some(principal) => {
+                            request.extensions_mut().insert(Authenticated(principal));
+                            self.set(AuthFuture::Calling {
+                                fut: inner.call(request),
+                            });
+                        }
+                        None => self.set(AuthFuture::Rejected {
+                            challenge: Some(challenges[1].clone()),
+                        }),
+                    }
+                }
+                AuthFutureProj::Calling { fut } => {
+                    return fut.poll(cx).map_ok(IntoResponse::into_response);
+                }
+                AuthFutureProj::Rejected { challenge } => {
+                    let challenge = challenge.take().expect("polled after completion");
+                    let mut response = StatusCode::UNAUTHORIZED.into_response();
+                    response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+                    return Poll::Ready(Ok(response));
+                }
+            }
+        }
+    }
+}
+
+impl<S, Req, Fut> fmt::Debug for AuthFuture<S, Req, Fut>
+where
+    S: Service<Req>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthFuture").finish()
+    }
+}
@@ -0,0 +1,21 @@
+#[cfg(any(feature = "metrics", feature = "trace"))]
+mod on_end;
+#[cfg(feature = "trace")]
+pub(crate) use on_end::BodyEnd;
+#[cfg(any(feature = "metrics", feature = "trace"))]
+pub(crate) use on_end::OnEndBody;
+
+#[cfg(any(
+    feature = "cache",
+    feature = "conditional",
+    feature = "jwt",
+    feature = "retry"
+))]
+mod to_bytes;
+#[cfg(any(
+    feature = "cache",
+    feature = "conditional",
+    feature = "jwt",
+    feature = "retry"
+))]
+pub(crate) use to_bytes::to_bytes;
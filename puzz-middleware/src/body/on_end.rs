@@ -0,0 +1,106 @@
+use std::fmt;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use puzz_core::body::{Body, BoxBody, Bytes, SizeHint};
+use puzz_core::BoxError;
+
+/// 响应正文结束的方式。
+// 只有`trace`中间件会区分结束的方式并读取错误。
+#[cfg_attr(not(feature = "trace"), allow(dead_code))]
+pub(crate) enum BodyEnd<'a> {
+    /// 正文已完整发送。
+    Complete,
+    /// 正文产生了错误。
+    Error(&'a BoxError),
+    /// 正文在发送完成前被丢弃，通常是因为客户端断开了连接。
+    Dropped,
+}
+
+/// 统计正文字节数，并在正文结束时调用回调函数的正文。
+pub(crate) struct OnEndBody<F>
+where
+    F: FnOnce(u64, BodyEnd<'_>),
+{
+    inner: BoxBody,
+    bytes: u64,
+    f: Option<F>,
+}
+
+impl<F> OnEndBody<F>
+where
+    F: FnOnce(u64, BodyEnd<'_>),
+{
+    pub(crate) fn new(inner: BoxBody, f: F) -> Self {
+        Self {
+            inner,
+            bytes: 0,
+            f: Some(f),
+        }
+    }
+
+    fn end(&mut self, end: BodyEnd<'_>) {
+        if let Some(f) = self.f.take() {
+            f(self.bytes, end);
+        }
+    }
+}
+
+impl<F> Body for OnEndBody<F>
+where
+    F: FnOnce(u64, BodyEnd<'_>) + Unpin,
+{
+    type Error = BoxError;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_next(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Some(Ok(data))) => {
+                this.bytes += data.len() as u64;
+                Poll::Ready(Some(Ok(data)))
+            }
+            Poll::Ready(Some(Err(err))) => {
+                this.end(BodyEnd::Error(&err));
+                Poll::Ready(Some(Err(err)))
+            }
+            Poll::Ready(None) => {
+                this.end(BodyEnd::Complete);
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl<F> Drop for OnEndBody<F>
+where
+    F: FnOnce(u64, BodyEnd<'_>),
+{
+    fn drop(&mut self) {
+        // 服务器可能不会轮询已知为空的正文。
+        if self.inner.size_hint().exact() == Some(0) {
+            self.end(BodyEnd::Complete);
+        } else {
+            self.end(BodyEnd::Dropped);
+        }
+    }
+}
+
+impl<F> fmt::Debug for OnEndBody<F>
+where
+    F: FnOnce(u64, BodyEnd<'_>),
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OnEndBody")
+            .field("inner", &self.inner)
+            .field("bytes", &self.bytes)
+            .finish()
+    }
+}
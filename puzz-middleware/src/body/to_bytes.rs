@@ -0,0 +1,30 @@
+use puzz_core::body::{Body, BodyExt, Bytes};
+use puzz_core::BoxError;
+
+/// 读取完整的正文。
+pub(crate) async fn to_bytes<B>(mut body: B) -> Result<Bytes, BoxError>
+where
+    B: Body + Unpin,
+    B::Error: Into<BoxError>,
+{
+    let first = match body.next().await {
+        Some(data) => data.map_err(Into::into)?,
+        None => return Ok(Bytes::new()),
+    };
+
+    let mut buf = match body.next().await {
+        Some(data) => {
+            let mut buf = Vec::with_capacity(first.len() + body.size_hint().lower() as usize);
+            buf.extend_from_slice(&first);
+            buf.extend_from_slice(&data.map_err(Into::into)?);
+            buf
+        }
+        None => return Ok(first),
+    };
+
+    while let Some(data) = body.next().await {
+        buf.extend_from_slice(&data.map_err(Into::into)?);
+    }
+
+    Ok(buf.into())
+}
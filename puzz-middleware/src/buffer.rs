@@ -0,0 +1,456 @@
+//! 通过工作任务共享服务。
+//!
+//! [`buffer`]将服务交给一个后台工作任务，请求通过有界通道发送给工作任务，由它调用服务。
+//! 返回的[`Buffer`]可以廉价地克隆，因此可以在多个任务之间共享未实现[`Clone`]的服务（例如[`SendBoxService`](puzz_core::service::util::SendBoxService)）。
+//!
+//! 调用方在调用服务之前使用`poll_ready`时，服务会在通道中预留一个位置，通道已满时返回`Poll::Pending`，
+//! 并在工作任务取走请求后唤醒任务，从而向调用方施加背压。预留的位置由随后的`call`使用。
+//! 路由和服务器不会调用`poll_ready`，此时`call`返回的[`Future`](std::future::Future)会等待通道出现空位。
+//!
+//! 启用`send`特性时，工作任务通过[`tokio::spawn`]运行，服务、请求、服务返回的[`Future`](std::future::Future)都需要实现[`Send`]；
+//! 否则通过[`tokio::task::spawn_local`]运行，因此需要在[`LocalSet`](tokio::task::LocalSet)中使用（服务器的工作线程已经满足这一点）。
+//! 不在Tokio运行时中创建时，工作任务无法启动，所有调用都会返回[`BufferError::Closed`]。
+
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures_core::ready;
+use puzz_core::http::marker::{MaybeSend, MaybeSendSync};
+use puzz_core::service::{Service, Wrap};
+use puzz_core::Request;
+use tokio::sync::mpsc::error::SendError;
+use tokio::sync::mpsc::OwnedPermit;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{task, BoxFuture};
+
+/// 创建一个通过工作任务共享服务的[`Wrap`]。
+///
+/// `bound`是通道中最多可以排队的请求数，通道已满时调用会等待。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::buffer::buffer;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// tokio::task::LocalSet::new()
+///     .run_until(async {
+///         let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+///             .send_boxed()
+///             .with(buffer(32));
+///
+///         let cloned = service.clone();
+///         let task = tokio::task::spawn_local(async move {
+///             cloned.call(Request::default()).await.unwrap()
+///         });
+///
+///         assert_eq!(service.call(Request::default()).await.unwrap(), "hi!");
+///         assert_eq!(task.await.unwrap(), "hi!");
+///     })
+///     .await;
+/// # }
+/// ```
+pub fn buffer(bound: usize) -> BufferWrap {
+    BufferWrap::new(bound)
+}
+
+pub struct BufferWrap<Req = Request> {
+    bound: usize,
+    _req: PhantomData<fn(Req)>,
+}
+
+impl<Req> BufferWrap<Req> {
+    /// 创建一个通道容量为`bound`的[`BufferWrap`]。
+    pub fn new(bound: usize) -> Self {
+        Self {
+            bound,
+            _req: PhantomData,
+        }
+    }
+}
+
+impl<Req> Clone for BufferWrap<Req> {
+    fn clone(&self) -> Self {
+        Self::new(self.bound)
+    }
+}
+
+impl<S, Req> Wrap<S> for BufferWrap<Req>
+where
+    S: Service<Req> + MaybeSend + 'static,
+    S::Future: MaybeSend,
+    S::Error: MaybeSendSync,
+    Req: MaybeSend + 'static,
+{
+    type Service = Buffer<S, Req>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        Buffer::new(service, self.bound)
+    }
+}
+
+impl<Req> fmt::Debug for BufferWrap<Req> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BufferWrap")
+            .field("bound", &self.bound)
+            .finish()
+    }
+}
+
+type Failure<E> = Arc<Mutex<Option<Arc<E>>>>;
+
+struct Message<Req, F> {
+    request: Req,
+    tx: oneshot::Sender<F>,
+}
+
+type Reserve<T> = BoxFuture<Result<OwnedPermit<T>, SendError<()>>>;
+
+/// 工作任务所拥有的服务的句柄。
+///
+/// # 例子
+///
+/// 通道已满时，`poll_ready`返回`Poll::Pending`：
+///
+/// ```
+/// use std::future::{poll_fn, Pending};
+/// use std::task::{Context, Poll};
+///
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::Request;
+/// use puzz_middleware::buffer::buffer;
+///
+/// // 永远不会就绪的服务，工作任务取走的请求会一直等待。
+/// struct Stuck;
+///
+/// impl Service<Request> for Stuck {
+///     type Response = ();
+///     type Error = ();
+///     type Future = Pending<Result<(), ()>>;
+///
+///     fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), ()>> {
+///         Poll::Pending
+///     }
+///
+///     fn call(&self, _: Request) -> Self::Future {
+///         std::future::pending()
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// tokio::task::LocalSet::new()
+///     .run_until(async {
+///         let service = Stuck.with(buffer(1));
+///
+///         // 第一个请求被工作任务取走。
+///         drop(service.ready().await.unwrap().call(Request::default()));
+///         tokio::task::yield_now().await;
+///
+///         // 第二个请求占用通道中唯一的位置。
+///         let cloned = service.clone();
+///         drop(cloned.ready().await.unwrap().call(Request::default()));
+///
+///         let other = service.clone();
+///         assert!(poll_fn(|cx| Poll::Ready(other.poll_ready(cx))).await.is_pending());
+///     })
+///     .await;
+/// # }
+/// ```
+pub struct Buffer<S, Req>
+where
+    S: Service<Req>,
+{
+    tx: mpsc::Sender<Message<Req, S::Future>>,
+    failure: Failure<S::Error>,
+    // `poll_ready`预留的位置，每个克隆各自持有。
+    reserved: Mutex<Reserved<Message<Req, S::Future>>>,
+}
+
+struct Reserved<T> {
+    permit: Option<OwnedPermit<T>>,
+    reserve: Option<Reserve<T>>,
+}
+
+impl<T> Default for Reserved<T> {
+    fn default() -> Self {
+        Self {
+            permit: None,
+            reserve: None,
+        }
+    }
+}
+
+impl<S, Req> Buffer<S, Req>
+where
+    S: Service<Req> + MaybeSend + 'static,
+    S::Future: MaybeSend,
+    S::Error: MaybeSendSync,
+    Req: MaybeSend + 'static,
+{
+    /// 启动一个拥有给定服务的工作任务，并返回它的句柄。
+    ///
+    /// 不在Tokio运行时中调用时，工作任务无法启动，服务会被丢弃，所有调用都会返回[`BufferError::Closed`]。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果`bound`为`0`，将会发生恐慌。未启用`send`特性时，如果在Tokio运行时中但不在[`LocalSet`](tokio::task::LocalSet)中调用，也会发生恐慌。
+    pub fn new(service: S, bound: usize) -> Self {
+        let (tx, rx) = mpsc::channel(bound);
+        let failure = Failure::default();
+
+        // 无法启动时丢弃工作任务，接收端随之关闭。
+        let _ = task::spawn(Worker {
+            service,
+            rx,
+            failure: failure.clone(),
+            message: None,
+            failed: false,
+        });
+
+        Self {
+            tx,
+            failure,
+            reserved: Mutex::default(),
+        }
+    }
+}
+
+impl<S, Req> Buffer<S, Req>
+where
+    S: Service<Req>,
+{
+    fn error(failure: &Failure<S::Error>) -> BufferError<S::Error> {
+        match &*failure.lock().unwrap() {
+            Some(err) => BufferError::Failed(err.clone()),
+            None => BufferError::Closed,
+        }
+    }
+}
+
+impl<S, Req> Clone for Buffer<S, Req>
+where
+    S: Service<Req>,
+{
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            failure: self.failure.clone(),
+            reserved: Mutex::default(),
+        }
+    }
+}
+
+impl<S, Req> Service<Req> for Buffer<S, Req>
+where
+    S: Service<Req> + 'static,
+    S::Future: MaybeSend,
+    S::Error: MaybeSendSync,
+    Req: MaybeSend + 'static,
+{
+    type Response = S::Response;
+    type Error = BufferError<S::Error>;
+    type Future = BoxFuture<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut reserved = self.reserved.lock().unwrap();
+        if reserved.permit.is_none() {
+            let tx = &self.tx;
+            let reserve = reserved
+                .reserve
+                .get_or_insert_with(|| Box::pin(tx.clone().reserve_owned()));
+
+            let result = ready!(reserve.as_mut().poll(cx));
+            reserved.reserve = None;
+
+            match result {
+                Ok(permit) => reserved.permit = Some(permit),
+                Err(_) => return Poll::Ready(Err(Self::error(&self.failure))),
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&self, request: Req) -> Self::Future {
+        let (otx, orx) = oneshot::channel();
+        let message = Message { request, tx: otx };
+
+        // 使用`poll_ready`预留的位置时立即发送，否则在返回的`Future`中等待通道出现空位。
+        let pending = match self.reserved.lock().unwrap().permit.take() {
+            Some(permit) => {
+                permit.send(message);
+                None
+            }
+            None => Some((self.tx.clone(), message)),
+        };
+        let failure = self.failure.clone();
+
+        Box::pin(async move {
+            if let Some((tx, message)) = pending {
+                if tx.send(message).await.is_err() {
+                    return Err(Self::error(&failure));
+                }
+            }
+
+            match orx.await {
+                Ok(fut) => fut.await.map_err(BufferError::Service),
+                Err(_) => Err(Self::error(&failure)),
+            }
+        })
+    }
+}
+
+impl<S, Req> fmt::Debug for Buffer<S, Req>
+where
+    S: Service<Req>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Buffer")
+            .field("service", &std::any::type_name::<S>())
+            .finish()
+    }
+}
+
+// 手动实现`Future`，避免在等待时持有服务的引用，这样启用`send`特性时服务只需要实现`Send`。
+struct Worker<S, Req>
+where
+    S: Service<Req>,
+{
+    service: S,
+    rx: mpsc::Receiver<Message<Req, S::Future>>,
+    failure: Failure<S::Error>,
+    message: Option<Message<Req, S::Future>>,
+    failed: bool,
+}
+
+impl<S, Req> Unpin for Worker<S, Req> where S: Service<Req> {}
+
+impl<S, Req> Future for Worker<S, Req>
+where
+    S: Service<Req>,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        loop {
+            if this.message.is_none() {
+                match ready!(this.rx.poll_recv(cx)) {
+                    // 服务已经失败时丢弃剩余的请求，等待者会取得记录的错误。
+                    Some(_) if this.failed => continue,
+                    Some(message) => this.message = Some(message),
+                    None => return Poll::Ready(()),
+                }
+            }
+
+            match ready!(this.service.poll_ready(cx)) {
+                Ok(()) => {
+                    let message = this.message.take().unwrap();
+                    // 等待者已经放弃时丢弃返回的`Future`。
+                    let _ = message.tx.send(this.service.call(message.request));
+                }
+                Err(err) => {
+                    // 先记录错误再关闭通道，等待者发现请求被丢弃时可以取得错误。
+                    *this.failure.lock().unwrap() = Some(Arc::new(err));
+                    this.failed = true;
+                    this.rx.close();
+                    this.message = None;
+                }
+            }
+        }
+    }
+}
+
+/// [`Buffer`]返回的错误。
+///
+/// # 例子
+///
+/// ```
+/// use std::task::Poll;
+///
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::buffer::{buffer, BufferError};
+///
+/// struct Broken;
+///
+/// impl Service<Request> for Broken {
+///     type Response = ();
+///     type Error = &'static str;
+///     type Future = std::future::Ready<Result<(), &'static str>>;
+///
+///     fn poll_ready(&self, _: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+///         Poll::Ready(Err("broken"))
+///     }
+///
+///     fn call(&self, _: Request) -> Self::Future {
+///         std::future::ready(Ok(()))
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// tokio::task::LocalSet::new()
+///     .run_until(async {
+///         let service = Broken.with(buffer(8));
+///
+///         let first = service.call(Request::default());
+///         let second = service.call(Request::default());
+///
+///         for result in [first.await, second.await] {
+///             match result {
+///                 Err(BufferError::Failed(err)) => assert_eq!(*err, "broken"),
+///                 _ => unreachable!(),
+///             }
+///         }
+///     })
+///     .await;
+/// # }
+/// ```
+#[derive(Debug)]
+pub enum BufferError<E> {
+    /// 服务处理请求时返回的错误。
+    Service(E),
+    /// 服务在就绪检查时返回了错误，工作任务已经停止。所有等待中和随后的请求都会得到这个错误。
+    Failed(Arc<E>),
+    /// 工作任务已经停止，例如所在的[`LocalSet`](tokio::task::LocalSet)已经被丢弃。
+    Closed,
+}
+
+impl<E> fmt::Display for BufferError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BufferError::Service(e) => e.fmt(f),
+            BufferError::Failed(e) => write!(f, "buffered service failed: {}", e),
+            BufferError::Closed => f.write_str("buffer's worker closed unexpectedly"),
+        }
+    }
+}
+
+impl<E> std::error::Error for BufferError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BufferError::Service(e) => Some(e),
+            BufferError::Failed(e) => Some(&**e),
+            BufferError::Closed => None,
+        }
+    }
+}
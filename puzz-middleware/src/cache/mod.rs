@@ -0,0 +1,499 @@
+//! 响应缓存。
+//!
+//! [`cache`]将可缓存的`GET`和`HEAD`响应保存在[`MemoryCache`]中，按请求的方法、主机、路径和`Vary`标头区分。
+//!
+//! 响应的新鲜度由`Cache-Control`标头的`s-maxage`或`max-age`指令决定，包含`no-store`、`no-cache`或`private`指令、
+//! 设置了Cookie或者`Vary: *`的响应不会被缓存。
+//!
+//! 过期的响应在`stale-while-revalidate`指令给定的时间内仍然可以使用，同时在后台重新请求服务以更新缓存。
+//! 启用`send`特性时，后台请求通过[`tokio::spawn`]运行；否则通过[`tokio::task::spawn_local`]运行，
+//! 因此需要在[`LocalSet`](tokio::task::LocalSet)中使用（服务器的工作线程已经满足这一点）。
+//! 不在Tokio运行时中时，请求会等待服务重新验证，而不是使用过期的响应。
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::ready;
+use pin_project_lite::pin_project;
+use puzz_core::body::{Body, BodyExt};
+use puzz_core::http::header::{AGE, AUTHORIZATION, CACHE_CONTROL, SET_COOKIE, VARY};
+use puzz_core::http::marker::MaybeSend;
+use puzz_core::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::{Request, Response};
+
+use crate::{task, BoxFuture};
+
+mod store;
+
+pub use store::MemoryCache;
+
+use store::{Lookup, Resource, Stored};
+
+/// `X-Cache`标头，表明响应是否来自缓存。
+pub const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+
+const X_CACHE_HIT: HeaderValue = HeaderValue::from_static("HIT");
+
+const X_CACHE_MISS: HeaderValue = HeaderValue::from_static("MISS");
+
+const X_CACHE_STALE: HeaderValue = HeaderValue::from_static("STALE");
+
+// 默认缓存单个响应正文的最大长度。
+const DEFAULT_MAX_ENTRY_SIZE: u64 = 1024 * 1024;
+
+/// 创建一个缓存响应的[`Wrap`]。
+///
+/// 来自缓存的响应会携带`Age`标头，所有经过缓存的响应都会携带[`X-Cache`](X_CACHE)标头，
+/// 其值为`HIT`、`STALE`或`MISS`。
+///
+/// # 例子
+///
+/// ```
+/// use std::cell::Cell;
+/// use std::convert::Infallible;
+/// use std::rc::Rc;
+///
+/// use puzz_core::body::BodyExt;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request, Response};
+/// use puzz_middleware::cache::cache;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let calls = Rc::new(Cell::new(0));
+///
+/// let service = service_fn({
+///     let calls = calls.clone();
+///     move |_: Request| {
+///         calls.set(calls.get() + 1);
+///         async {
+///             Ok::<_, Infallible>(
+///                 Response::builder()
+///                     .header("cache-control", "max-age=60")
+///                     .body("hi!".boxed())
+///                     .unwrap(),
+///             )
+///         }
+///     }
+/// })
+/// .with(cache());
+///
+/// let response = service.call(Request::default()).await.unwrap();
+/// assert_eq!(response.headers()["x-cache"], "MISS");
+///
+/// let response = service.call(Request::default()).await.unwrap();
+/// assert_eq!(response.headers()["x-cache"], "HIT");
+/// assert_eq!(response.headers()["age"], "0");
+/// assert_eq!(calls.get(), 1);
+/// # }
+/// ```
+pub fn cache() -> CacheWrap {
+    CacheWrap::new()
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheWrap {
+    store: MemoryCache,
+    max_entry_size: u64,
+    stale_while_revalidate: Duration,
+}
+
+impl CacheWrap {
+    pub fn new() -> Self {
+        Self {
+            store: MemoryCache::default(),
+            max_entry_size: DEFAULT_MAX_ENTRY_SIZE,
+            stale_while_revalidate: Duration::ZERO,
+        }
+    }
+
+    /// 设置保存响应的缓存，默认为总大小不超过16MiB的[`MemoryCache`]。
+    pub fn store(mut self, store: MemoryCache) -> Self {
+        self.store = store;
+        self
+    }
+
+    /// 设置缓存单个响应正文的最大长度，默认为1MiB。
+    ///
+    /// 正文长度的上界未知或超过此值的响应（例如流式响应）不会被缓存。
+    pub fn max_entry_size(mut self, max_entry_size: u64) -> Self {
+        self.max_entry_size = max_entry_size;
+        self
+    }
+
+    /// 设置响应没有`stale-while-revalidate`指令时，过期响应仍然可以使用的时间，默认为`0`。
+    pub fn stale_while_revalidate(mut self, duration: Duration) -> Self {
+        self.stale_while_revalidate = duration;
+        self
+    }
+}
+
+impl Default for CacheWrap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Wrap<S> for CacheWrap {
+    type Service = Cache<S>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        Cache {
+            inner: service,
+            wrap: self,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cache<S> {
+    inner: S,
+    wrap: CacheWrap,
+}
+
+impl<S> Service<Request> for Cache<S>
+where
+    S: Service<Request>,
+    S::Response: IntoResponse + MaybeSend,
+    S::Future: MaybeSend + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = CacheFuture<S::Future>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Request) -> Self::Future {
+        let directives = Directives::new(request.headers());
+        let cacheable = request.method() == Method::GET || request.method() == Method::HEAD;
+
+        // 请求要求绕过缓存时直接调用服务。
+        if !cacheable || directives.no_store || directives.no_cache {
+            return CacheFuture::Calling {
+                fut: self.inner.call(request),
+                admission: None,
+            };
+        }
+
+        let resource = Resource::new(&request);
+
+        match self.wrap.store.lookup(&resource, request.headers()) {
+            Lookup::Fresh(response) => CacheFuture::Cached {
+                response: Some(response),
+            },
+            Lookup::Stale(response, revalidate) => {
+                if revalidate {
+                    let admission = self.admission(resource, &request);
+                    let fut = self.inner.call(request);
+
+                    // 无法在后台运行时，由这个请求重新验证。
+                    if !task::can_spawn() {
+                        return CacheFuture::Calling {
+                            fut,
+                            admission: Some(admission),
+                        };
+                    }
+
+                    let _ = task::spawn(async move {
+                        let response = match fut.await {
+                            Ok(response) => response.into_response(),
+                            Err(_) => return admission.release(),
+                        };
+                        admission.revalidated(response).await;
+                    });
+                }
+                CacheFuture::Cached {
+                    response: Some(response),
+                }
+            }
+            Lookup::Miss => CacheFuture::Calling {
+                admission: Some(self.admission(resource, &request)),
+                fut: self.inner.call(request),
+            },
+        }
+    }
+}
+
+impl<S> Cache<S> {
+    fn admission(&self, resource: Resource, request: &Request) -> Admission {
+        Admission {
+            store: self.wrap.store.clone(),
+            resource,
+            headers: request.headers().clone(),
+            max_entry_size: self.wrap.max_entry_size,
+            stale_while_revalidate: self.wrap.stale_while_revalidate,
+        }
+    }
+}
+
+/// 决定是否以及如何缓存响应。
+struct Admission {
+    store: MemoryCache,
+    resource: Resource,
+    // 请求的标头，用于计算`Vary`。
+    headers: HeaderMap,
+    max_entry_size: u64,
+    stale_while_revalidate: Duration,
+}
+
+impl Admission {
+    /// 检查响应是否可以缓存，返回响应的新鲜时间和过期后仍然可用的时间。
+    fn check(&self, response: &Response) -> Option<(Duration, Duration)> {
+        if !is_cacheable_status(response.status()) {
+            return None;
+        }
+
+        let headers = response.headers();
+        let directives = Directives::new(headers);
+        if directives.no_store || directives.no_cache || directives.private {
+            return None;
+        }
+
+        // 携带凭据的请求只有在响应明确允许时才可以被共享缓存保存。
+        if self.headers.contains_key(AUTHORIZATION)
+            && !directives.public
+            && directives.s_maxage.is_none()
+        {
+            return None;
+        }
+
+        if headers.contains_key(SET_COOKIE) || vary(headers).is_none() {
+            return None;
+        }
+
+        match response.body().size_hint().upper() {
+            Some(upper) if upper <= self.max_entry_size => {}
+            _ => return None,
+        }
+
+        let ttl = Duration::from_secs(directives.s_maxage.or(directives.max_age)?);
+        let stale_while_revalidate = directives
+            .stale_while_revalidate
+            .map_or(self.stale_while_revalidate, Duration::from_secs);
+
+        if ttl.is_zero() && stale_while_revalidate.is_zero() {
+            return None;
+        }
+
+        Some((ttl, stale_while_revalidate))
+    }
+
+    /// 缓冲正文并保存响应。
+    async fn store(
+        self,
+        response: Response,
+        ttl: Duration,
+        stale_while_revalidate: Duration,
+    ) -> Response {
+        let (head, body) = response.into_head();
+        let body = match crate::body::to_bytes(body).await {
+            Ok(body) => body,
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        };
+
+        let initial_age = head
+            .headers
+            .get(AGE)
+            .and_then(|age| age.to_str().ok())
+            .and_then(|age| age.parse().ok())
+            .map_or(Duration::ZERO, Duration::from_secs);
+
+        self.store.insert(
+            self.resource.clone(),
+            &self.headers,
+            Stored {
+                status: head.status,
+                version: head.version,
+                headers: head.headers.clone(),
+                body: body.clone(),
+                vary: vary(&head.headers).unwrap_or_default(),
+                ttl,
+                stale_while_revalidate,
+                initial_age,
+            },
+        );
+
+        let mut response = Response::from_head(head, body.boxed());
+        response.headers_mut().insert(X_CACHE, X_CACHE_MISS);
+        response
+    }
+
+    /// 处理后台重新验证的响应。
+    async fn revalidated(self, response: Response) {
+        match self.check(&response) {
+            Some((ttl, stale_while_revalidate)) => {
+                self.store(response, ttl, stale_while_revalidate).await;
+            }
+            None => self.discard(),
+        }
+    }
+
+    fn release(&self) {
+        self.store.release(&self.resource, &self.headers);
+    }
+
+    fn discard(&self) {
+        self.store.discard(&self.resource, &self.headers);
+    }
+}
+
+/// 具有明确新鲜时间时默认可以缓存的状态码（RFC 9110 第15.1节）。
+fn is_cacheable_status(status: StatusCode) -> bool {
+    matches!(
+        status.as_u16(),
+        200 | 203 | 204 | 206 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+    )
+}
+
+/// 获取`Vary`标头中的名称，如果包含`*`则返回[`None`]。
+fn vary(headers: &HeaderMap) -> Option<Vec<HeaderName>> {
+    let mut names = Vec::new();
+    for name in headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        if name == "*" {
+            return None;
+        }
+        if let Ok(name) = HeaderName::try_from(name) {
+            names.push(name);
+        }
+    }
+    names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    names.dedup();
+    Some(names)
+}
+
+/// `Cache-Control`标头中的指令。
+#[derive(Debug, Default)]
+struct Directives {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    public: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+    stale_while_revalidate: Option<u64>,
+}
+
+impl Directives {
+    fn new(headers: &HeaderMap) -> Self {
+        let mut directives = Self::default();
+
+        for directive in headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+        {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            let seconds = value.and_then(|value| value.parse().ok());
+
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => directives.no_store = true,
+                "no-cache" => directives.no_cache = true,
+                "private" => directives.private = true,
+                "public" => directives.public = true,
+                "max-age" => directives.max_age = seconds,
+                "s-maxage" => directives.s_maxage = seconds,
+                "stale-while-revalidate" => directives.stale_while_revalidate = seconds,
+                _ => {}
+            }
+        }
+
+        directives
+    }
+}
+
+pin_project! {
+    #[project = CacheFutureProj]
+    pub enum CacheFuture<Fut> {
+        Cached {
+            response: Option<Response>,
+        },
+        Calling {
+            #[pin]
+            fut: Fut,
+            admission: Option<Admission>,
+        },
+        Storing {
+            fut: BoxFuture<Response>,
+        },
+    }
+}
+
+impl<Fut, T, E> Future for CacheFuture<Fut>
+where
+    Fut: Future<Output = Result<T, E>>,
+    T: IntoResponse,
+{
+    type Output = Result<Response, E>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                CacheFutureProj::Cached { response } => {
+                    let response = response.take().expect("polled after completion");
+                    return Poll::Ready(Ok(response));
+                }
+                CacheFutureProj::Calling { fut, admission } => {
+                    let result = ready!(fut.poll(cx));
+
+                    let admission = match admission.take() {
+                        Some(admission) => admission,
+                        None => return Poll::Ready(result.map(IntoResponse::into_response)),
+                    };
+
+                    // 可能是在重新验证过期的响应，需要允许之后的请求再次重新验证。
+                    let mut response = match result {
+                        Ok(response) => response.into_response(),
+                        Err(err) => {
+                            admission.release();
+                            return Poll::Ready(Err(err));
+                        }
+                    };
+
+                    match admission.check(&response) {
+                        Some((ttl, stale_while_revalidate)) => {
+                            self.set(CacheFuture::Storing {
+                                fut: Box::pin(admission.store(
+                                    response,
+                                    ttl,
+                                    stale_while_revalidate,
+                                )),
+                            });
+                        }
+                        None => {
+                            admission.discard();
+                            response.headers_mut().insert(X_CACHE, X_CACHE_MISS);
+                            return Poll::Ready(Ok(response));
+                        }
+                    }
+                }
+                CacheFutureProj::Storing { fut } => {
+                    return fut.as_mut().poll(cx).map(Ok);
+                }
+            }
+        }
+    }
+}
+
+impl<Fut> fmt::Debug for CacheFuture<Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheFuture").finish()
+    }
+}
@@ -0,0 +1,435 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use puzz_core::body::{BodyExt, Bytes};
+use puzz_core::http::header::AGE;
+use puzz_core::http::header::HOST;
+use puzz_core::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Version};
+use puzz_core::{Request, Response};
+use tokio::time::Instant;
+
+use super::{X_CACHE, X_CACHE_HIT, X_CACHE_STALE};
+
+/// 保存在内存中的响应缓存。
+///
+/// 缓存的总大小（正文、标头和`Vary`记录的长度之和）超过上限时，最近最少使用的响应将被淘汰。
+/// 缓存可以廉价地克隆，克隆后的缓存共享相同的响应，因此可以在初始化时保留一个克隆用于清除缓存。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::body::BodyExt;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request, Response};
+/// use puzz_middleware::cache::{cache, MemoryCache};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let store = MemoryCache::new(16 * 1024 * 1024);
+///
+/// let service = service_fn(|_: Request| async {
+///     Ok::<_, Infallible>(
+///         Response::builder()
+///             .header("cache-control", "max-age=60")
+///             .body("hi!".boxed())
+///             .unwrap(),
+///     )
+/// })
+/// .with(cache().store(store.clone()));
+///
+/// let request = || Request::builder().uri("/users?page=1").body(Default::default()).unwrap();
+///
+/// service.call(request()).await.unwrap();
+/// assert_eq!(store.len(), 1);
+///
+/// assert_eq!(store.purge("/users?page=1"), 1);
+/// assert!(store.is_empty());
+/// assert_eq!(store.size(), 0);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct MemoryCache {
+    inner: Arc<Mutex<CacheState>>,
+}
+
+struct CacheState {
+    entries: HashMap<Key, Entry>,
+    // 每个资源最近一次响应的`Vary`标头。
+    vary: HashMap<Resource, Vary>,
+    // 按最近使用的顺序排列的键。
+    lru: BTreeMap<u64, Key>,
+    tick: u64,
+    size: usize,
+    max_size: usize,
+}
+
+/// 资源的`Vary`标头，在资源的最后一个响应被删除时一起删除。
+struct Vary {
+    names: Vec<HeaderName>,
+    // 使用这些标头的响应数量。
+    entries: usize,
+    size: usize,
+}
+
+impl Vary {
+    fn size(resource: &Resource, names: &[HeaderName]) -> usize {
+        resource.method.as_str().len()
+            + resource.authority.len()
+            + resource.path.len()
+            + names.iter().map(|name| name.as_str().len()).sum::<usize>()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Resource {
+    method: Method,
+    authority: String,
+    path: String,
+}
+
+impl Resource {
+    pub(crate) fn new<B>(request: &Request<B>) -> Self {
+        let authority = request
+            .headers()
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .or_else(|| {
+                request
+                    .uri()
+                    .authority()
+                    .map(|authority| authority.as_str())
+            })
+            .unwrap_or_default();
+
+        Self {
+            method: request.method().clone(),
+            authority: authority.to_ascii_lowercase(),
+            path: request
+                .uri()
+                .path_and_query()
+                .map_or("/", |path| path.as_str())
+                .to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    resource: Resource,
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+}
+
+impl Key {
+    fn new(resource: Resource, names: &[HeaderName], headers: &HeaderMap) -> Self {
+        Self {
+            resource,
+            vary: names
+                .iter()
+                .map(|name| (name.clone(), headers.get(name).cloned()))
+                .collect(),
+        }
+    }
+}
+
+/// 准备写入缓存的响应。
+pub(crate) struct Stored {
+    pub(crate) status: StatusCode,
+    pub(crate) version: Version,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Bytes,
+    pub(crate) vary: Vec<HeaderName>,
+    pub(crate) ttl: Duration,
+    pub(crate) stale_while_revalidate: Duration,
+    pub(crate) initial_age: Duration,
+}
+
+struct Entry {
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    body: Bytes,
+    ttl: Duration,
+    stale_while_revalidate: Duration,
+    initial_age: Duration,
+    stored_at: Instant,
+    tick: u64,
+    size: usize,
+    revalidating: bool,
+}
+
+impl Entry {
+    fn age(&self, now: Instant) -> Duration {
+        self.initial_age + now.saturating_duration_since(self.stored_at)
+    }
+
+    fn to_response(&self, age: Duration, stale: bool) -> Response {
+        let mut response = Response::new(self.body.clone().boxed());
+        *response.status_mut() = self.status;
+        *response.version_mut() = self.version;
+        *response.headers_mut() = self.headers.clone();
+
+        let headers = response.headers_mut();
+        headers.insert(AGE, HeaderValue::from(age.as_secs()));
+        headers.insert(X_CACHE, if stale { X_CACHE_STALE } else { X_CACHE_HIT });
+        response
+    }
+}
+
+/// 查找缓存的结果。
+pub(crate) enum Lookup {
+    /// 新鲜的响应。
+    Fresh(Response),
+    /// 已经过期但仍然可用的响应，如果需要在后台重新验证则为`true`。
+    Stale(Response, bool),
+    /// 没有可用的响应。
+    Miss,
+}
+
+impl MemoryCache {
+    /// 创建一个总大小不超过`max_size`字节的缓存。
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(CacheState {
+                entries: HashMap::new(),
+                vary: HashMap::new(),
+                lru: BTreeMap::new(),
+                tick: 0,
+                size: 0,
+                max_size,
+            })),
+        }
+    }
+
+    /// 清除给定路径（包括查询字符串）的所有响应，返回清除的响应数量。
+    pub fn purge(&self, path: &str) -> usize {
+        let mut state = self.inner.lock().unwrap();
+        let keys = state
+            .entries
+            .keys()
+            .filter(|key| key.resource.path == path)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for key in &keys {
+            state.remove(key);
+        }
+
+        keys.len()
+    }
+
+    /// 清除所有响应。
+    pub fn clear(&self) {
+        let mut state = self.inner.lock().unwrap();
+        state.entries.clear();
+        state.vary.clear();
+        state.lru.clear();
+        state.size = 0;
+    }
+
+    /// 获取缓存中响应的数量。
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// 缓存是否为空。
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 获取缓存的总大小（字节）。
+    pub fn size(&self) -> usize {
+        self.inner.lock().unwrap().size
+    }
+
+    pub(crate) fn lookup(&self, resource: &Resource, headers: &HeaderMap) -> Lookup {
+        let mut state = self.inner.lock().unwrap();
+        let now = Instant::now();
+
+        let key = match state.vary.get(resource) {
+            Some(vary) => Key::new(resource.clone(), &vary.names, headers),
+            None => return Lookup::Miss,
+        };
+
+        let (age, ttl, stale_while_revalidate) = match state.entries.get(&key) {
+            Some(entry) => (entry.age(now), entry.ttl, entry.stale_while_revalidate),
+            None => return Lookup::Miss,
+        };
+
+        if age >= ttl + stale_while_revalidate {
+            state.remove(&key);
+            return Lookup::Miss;
+        }
+
+        state.touch(&key);
+        let entry = state.entries.get_mut(&key).expect("entry exists");
+
+        if age < ttl {
+            return Lookup::Fresh(entry.to_response(age, false));
+        }
+
+        let revalidate = !entry.revalidating;
+        entry.revalidating = true;
+        Lookup::Stale(entry.to_response(age, true), revalidate)
+    }
+
+    pub(crate) fn insert(&self, resource: Resource, headers: &HeaderMap, stored: Stored) {
+        let mut state = self.inner.lock().unwrap();
+
+        let key = Key::new(resource.clone(), &stored.vary, headers);
+        let size = stored.body.len()
+            + stored
+                .headers
+                .iter()
+                .map(|(name, value)| name.as_str().len() + value.len())
+                .sum::<usize>();
+
+        let vary_size = Vary::size(&resource, &stored.vary);
+
+        state.remove(&key);
+
+        // 资源的`Vary`标头改变后，按旧标头保存的响应将无法再被找到。
+        if state
+            .vary
+            .get(&resource)
+            .is_some_and(|vary| vary.names != stored.vary)
+        {
+            let stale = state
+                .entries
+                .keys()
+                .filter(|key| key.resource == resource)
+                .cloned()
+                .collect::<Vec<_>>();
+            for key in &stale {
+                state.remove(key);
+            }
+        }
+
+        if size + vary_size > state.max_size {
+            return;
+        }
+
+        loop {
+            // 淘汰响应时可能会一起删除该资源的`Vary`记录。
+            let needed = if state.vary.contains_key(&resource) {
+                size
+            } else {
+                size + vary_size
+            };
+            if state.size + needed <= state.max_size {
+                break;
+            }
+            match state.lru.first_key_value() {
+                Some((_, key)) => {
+                    let key = key.clone();
+                    state.remove(&key);
+                }
+                None => break,
+            }
+        }
+
+        if !state.vary.contains_key(&resource) {
+            state.size += vary_size;
+            state.vary.insert(
+                resource,
+                Vary {
+                    names: stored.vary,
+                    entries: 0,
+                    size: vary_size,
+                },
+            );
+        }
+        if let Some(vary) = state.vary.get_mut(&key.resource) {
+            vary.entries += 1;
+        }
+
+        state.tick += 1;
+        let tick = state.tick;
+        state.lru.insert(tick, key.clone());
+        state.size += size;
+        state.entries.insert(
+            key,
+            Entry {
+                status: stored.status,
+                version: stored.version,
+                headers: stored.headers,
+                body: stored.body,
+                ttl: stored.ttl,
+                stale_while_revalidate: stored.stale_while_revalidate,
+                initial_age: stored.initial_age,
+                stored_at: Instant::now(),
+                tick,
+                size,
+                revalidating: false,
+            },
+        );
+    }
+
+    /// 重新验证失败后，允许下一个请求再次尝试。
+    pub(crate) fn release(&self, resource: &Resource, headers: &HeaderMap) {
+        let mut state = self.inner.lock().unwrap();
+        if let Some(vary) = state.vary.get(resource) {
+            let key = Key::new(resource.clone(), &vary.names, headers);
+            if let Some(entry) = state.entries.get_mut(&key) {
+                entry.revalidating = false;
+            }
+        }
+    }
+
+    /// 重新验证得到不可缓存的响应，删除旧的响应。
+    pub(crate) fn discard(&self, resource: &Resource, headers: &HeaderMap) {
+        let mut state = self.inner.lock().unwrap();
+        if let Some(vary) = state.vary.get(resource) {
+            let key = Key::new(resource.clone(), &vary.names, headers);
+            state.remove(&key);
+        }
+    }
+}
+
+impl CacheState {
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.tick);
+            self.size -= entry.size;
+
+            if let Some(vary) = self.vary.get_mut(&key.resource) {
+                vary.entries -= 1;
+                if vary.entries == 0 {
+                    self.size -= vary.size;
+                    self.vary.remove(&key.resource);
+                }
+            }
+        }
+    }
+
+    fn touch(&mut self, key: &Key) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.lru.remove(&entry.tick);
+            entry.tick = tick;
+            self.lru.insert(tick, key.clone());
+        }
+    }
+}
+
+impl Default for MemoryCache {
+    /// 创建一个总大小不超过16MiB的缓存。
+    fn default() -> Self {
+        Self::new(16 * 1024 * 1024)
+    }
+}
+
+impl fmt::Debug for MemoryCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.lock().unwrap();
+        f.debug_struct("MemoryCache")
+            .field("len", &state.entries.len())
+            .field("size", &state.size)
+            .field("max_size", &state.max_size)
+            .finish()
+    }
+}
@@ -0,0 +1,384 @@
+//! 捕获处理请求时发生的恐慌。
+//!
+//! [`catch_panic`]捕获服务在调用和轮询期间发生的恐慌（也可以[捕获正文中的恐慌](CatchPanicWrap::catch_body)），
+//! 记录恐慌消息，并将其转换为响应或错误，使工作线程可以继续处理其它请求。
+
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use pin_project_lite::pin_project;
+use puzz_core::body::{Body, BodyExt, BoxBody, Bytes, SizeHint};
+use puzz_core::http::StatusCode;
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::{BoxError, Request, Response};
+
+/// 创建一个捕获恐慌的[`Wrap`]。
+///
+/// 默认将恐慌转换为`500 Internal Server Error`响应，响应扩展中包含[`Panicked`]。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::http::StatusCode;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::catch_panic::{catch_panic, Panicked};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|request: Request| async move {
+///     if request.uri().path() == "/panic" {
+///         panic!("oops");
+///     }
+///     Ok::<_, Infallible>("hi!")
+/// })
+/// .with(catch_panic());
+///
+/// let request = Request::builder().uri("/panic").body(Default::default()).unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+/// assert_eq!(response.extensions().get::<Panicked>().unwrap().message(), Some("oops"));
+///
+/// // 服务仍然可以继续处理请求。
+/// let response = service.call(Request::default()).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+pub fn catch_panic() -> CatchPanicWrap<PanicResponse> {
+    CatchPanicWrap::new()
+}
+
+/// 捕获到的恐慌。
+#[derive(Debug, Clone)]
+pub struct Panicked {
+    message: Option<String>,
+}
+
+impl Panicked {
+    fn new(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => Some(*message),
+            Err(payload) => payload
+                .downcast_ref::<&'static str>()
+                .map(|message| (*message).to_owned()),
+        };
+
+        let panicked = Self { message };
+        tracing::error!(message = %panicked, "panic while handling request");
+        panicked
+    }
+
+    /// 获取恐慌消息，如果恐慌的负载不是字符串则返回[`None`]。
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+}
+
+impl fmt::Display for Panicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "panicked: {}", message),
+            None => f.write_str("panicked"),
+        }
+    }
+}
+
+impl std::error::Error for Panicked {}
+
+impl IntoResponse for Panicked {
+    fn into_response(self) -> Response {
+        let mut response = StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// 处理捕获到的恐慌。
+pub trait PanicHandler<E> {
+    /// 将恐慌转换为响应或错误。
+    fn handle(&self, panicked: Panicked) -> Result<Response, E>;
+}
+
+impl<F, R, E> PanicHandler<E> for F
+where
+    F: Fn(Panicked) -> R,
+    R: IntoResponse,
+{
+    fn handle(&self, panicked: Panicked) -> Result<Response, E> {
+        Ok(self(panicked).into_response())
+    }
+}
+
+/// 将恐慌转换为`500 Internal Server Error`响应。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PanicResponse;
+
+impl<E> PanicHandler<E> for PanicResponse {
+    fn handle(&self, panicked: Panicked) -> Result<Response, E> {
+        Ok(panicked.into_response())
+    }
+}
+
+/// 将恐慌转换为服务的错误。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PanicError;
+
+impl<E> PanicHandler<E> for PanicError
+where
+    E: From<Panicked>,
+{
+    fn handle(&self, panicked: Panicked) -> Result<Response, E> {
+        Err(panicked.into())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CatchPanicWrap<H> {
+    handler: H,
+    catch_body: bool,
+}
+
+impl CatchPanicWrap<PanicResponse> {
+    pub fn new() -> Self {
+        Self {
+            handler: PanicResponse,
+            catch_body: false,
+        }
+    }
+}
+
+impl Default for CatchPanicWrap<PanicResponse> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H> CatchPanicWrap<H> {
+    /// 设置处理恐慌的方式。
+    ///
+    /// 可以使用闭包`Fn(Panicked) -> impl IntoResponse`返回自定义的响应，
+    /// 或者使用[`PanicError`]将恐慌转换为服务的错误（要求错误类型实现`From<Panicked>`）。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use puzz_core::http::StatusCode;
+    /// use puzz_core::service::{Service, ServiceExt};
+    /// use puzz_core::{service_fn, BoxError, Request};
+    /// use puzz_middleware::catch_panic::{catch_panic, PanicError, Panicked};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let service = service_fn(|_: Request| async {
+    ///     if true {
+    ///         panic!("oops");
+    ///     }
+    ///     Ok::<_, BoxError>("hi!")
+    /// });
+    ///
+    /// let custom = service.clone().with(
+    ///     catch_panic().handler(|_: Panicked| (StatusCode::SERVICE_UNAVAILABLE, "try again later")),
+    /// );
+    /// let response = custom.call(Request::default()).await.unwrap();
+    /// assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    ///
+    /// let error = service
+    ///     .with(catch_panic().handler(PanicError))
+    ///     .call(Request::default())
+    ///     .await
+    ///     .unwrap_err();
+    /// assert!(error.is::<Panicked>());
+    /// # }
+    /// ```
+    pub fn handler<T>(self, handler: T) -> CatchPanicWrap<T> {
+        CatchPanicWrap {
+            handler,
+            catch_body: self.catch_body,
+        }
+    }
+
+    /// 设置是否捕获响应正文产生数据时发生的恐慌，默认为`false`。
+    ///
+    /// 正文中的恐慌无法再转换为响应，只会被记录并作为正文的错误，使连接被中止。
+    pub fn catch_body(mut self, catch_body: bool) -> Self {
+        self.catch_body = catch_body;
+        self
+    }
+}
+
+impl<S, H> Wrap<S> for CatchPanicWrap<H> {
+    type Service = CatchPanic<S, H>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        CatchPanic {
+            inner: service,
+            handler: Arc::new(self.handler),
+            catch_body: self.catch_body,
+        }
+    }
+}
+
+pub struct CatchPanic<S, H> {
+    inner: S,
+    handler: Arc<H>,
+    catch_body: bool,
+}
+
+impl<S, H> Clone for CatchPanic<S, H>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            handler: self.handler.clone(),
+            catch_body: self.catch_body,
+        }
+    }
+}
+
+impl<S, H, B> Service<Request<B>> for CatchPanic<S, H>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+    H: PanicHandler<S::Error>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = CatchPanicFuture<S::Future, H>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Request<B>) -> Self::Future {
+        match catch_unwind(AssertUnwindSafe(|| self.inner.call(request))) {
+            Ok(fut) => CatchPanicFuture::Future {
+                fut,
+                handler: self.handler.clone(),
+                catch_body: self.catch_body,
+            },
+            Err(payload) => CatchPanicFuture::Panicked {
+                panicked: Some(Panicked::new(payload)),
+                handler: self.handler.clone(),
+            },
+        }
+    }
+}
+
+impl<S, H> fmt::Debug for CatchPanic<S, H>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CatchPanic")
+            .field("inner", &self.inner)
+            .field("handler", &std::any::type_name::<H>())
+            .field("catch_body", &self.catch_body)
+            .finish()
+    }
+}
+
+pin_project! {
+    #[project = CatchPanicFutureProj]
+    pub enum CatchPanicFuture<Fut, H> {
+        Future {
+            #[pin]
+            fut: Fut,
+            handler: Arc<H>,
+            catch_body: bool,
+        },
+        Panicked {
+            panicked: Option<Panicked>,
+            handler: Arc<H>,
+        },
+    }
+}
+
+impl<Fut, H, Res, Err> Future for CatchPanicFuture<Fut, H>
+where
+    Fut: Future<Output = Result<Res, Err>>,
+    Res: IntoResponse,
+    H: PanicHandler<Err>,
+{
+    type Output = Result<Response, Err>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.as_mut().project() {
+            CatchPanicFutureProj::Future {
+                fut,
+                handler,
+                catch_body,
+            } => {
+                let catch_body = *catch_body;
+                // 转换响应时也可能发生恐慌。
+                let result = catch_unwind(AssertUnwindSafe(|| {
+                    fut.poll(cx)
+                        .map(|result| result.map(IntoResponse::into_response))
+                }));
+
+                match result {
+                    Ok(Poll::Pending) => Poll::Pending,
+                    Ok(Poll::Ready(Ok(response))) if catch_body => Poll::Ready(Ok(
+                        response.map(|body| CatchPanicBody { inner: body }.boxed())
+                    )),
+                    Ok(Poll::Ready(result)) => Poll::Ready(result),
+                    Err(payload) => {
+                        // 发生恐慌的Future不能再被轮询。
+                        let handler = handler.clone();
+                        self.set(CatchPanicFuture::Panicked {
+                            panicked: None,
+                            handler: handler.clone(),
+                        });
+                        Poll::Ready(handler.handle(Panicked::new(payload)))
+                    }
+                }
+            }
+            CatchPanicFutureProj::Panicked { panicked, handler } => {
+                let panicked = panicked.take().expect("polled after completion");
+                Poll::Ready(handler.handle(panicked))
+            }
+        }
+    }
+}
+
+impl<Fut, H> fmt::Debug for CatchPanicFuture<Fut, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CatchPanicFuture").finish()
+    }
+}
+
+/// 捕获恐慌的正文。
+struct CatchPanicBody {
+    inner: BoxBody,
+}
+
+impl Body for CatchPanicBody {
+    type Error = BoxError;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        match catch_unwind(AssertUnwindSafe(|| Pin::new(&mut self.inner).poll_next(cx))) {
+            Ok(poll) => poll,
+            Err(payload) => {
+                // 发生恐慌的正文不能再被轮询。
+                self.inner = BoxBody::default();
+                Poll::Ready(Some(Err(Panicked::new(payload).into())))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
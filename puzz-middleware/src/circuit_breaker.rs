@@ -0,0 +1,759 @@
+//! 熔断器。
+//!
+//! [`circuit_breaker`]统计滑动窗口内请求的结果。失败率或连续失败次数达到阈值时，熔断器*断开*，
+//! 随后的请求不再调用服务，而是立即返回[`CircuitOpen`]错误。经过冷却时间后，熔断器*半开*，
+//! 允许少量探测请求通过：探测请求全部成功时熔断器*闭合*，恢复正常；任何一个探测请求失败时重新断开。
+//!
+//! 请求是否失败由[`Classify`]决定，默认将错误和`5xx`响应视为失败。
+//!
+//! 时间使用[`tokio::time::Instant`]计算，因此可以在测试中使用[`tokio::time::pause`]控制时间。
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use pin_project_lite::pin_project;
+use puzz_core::http::{header, HeaderValue, StatusCode};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::Response;
+use tokio::time::Instant;
+
+// 滑动窗口被划分的桶数。
+const BUCKETS: u32 = 10;
+
+/// 创建一个熔断器[`Wrap`]。
+///
+/// 同一个[`CircuitBreakerWrap`]（及其克隆）包裹的所有服务共享同一个熔断器。
+///
+/// # 例子
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use puzz_core::http::StatusCode;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request, Response};
+/// use puzz_middleware::circuit_breaker::{circuit_breaker, CircuitBreakerError, CircuitState};
+///
+/// # #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// # async fn main() {
+/// let service = service_fn(|_: Request| async {
+///     Ok::<_, ()>(
+///         Response::builder()
+///             .status(StatusCode::SERVICE_UNAVAILABLE)
+///             .body(())
+///             .unwrap(),
+///     )
+/// })
+/// .with(
+///     circuit_breaker()
+///         .consecutive_failures(3)
+///         .cooldown(Duration::from_secs(10))
+///         .on_state_change(|from, to| println!("{:?} -> {:?}", from, to)),
+/// );
+///
+/// for _ in 0..3 {
+///     assert!(service.call(Request::default()).await.is_ok());
+/// }
+/// assert_eq!(service.state(), CircuitState::Open);
+///
+/// // 断开时立即返回错误。
+/// match service.call(Request::default()).await {
+///     Err(CircuitBreakerError::Open(open)) => {
+///         assert_eq!(open.retry_after(), Duration::from_secs(10))
+///     }
+///     _ => unreachable!(),
+/// }
+///
+/// // 冷却后允许探测请求通过，探测失败时重新断开。
+/// tokio::time::advance(Duration::from_secs(10)).await;
+/// assert!(service.call(Request::default()).await.is_ok());
+/// assert_eq!(service.state(), CircuitState::Open);
+/// # }
+/// ```
+pub fn circuit_breaker() -> CircuitBreakerWrap {
+    CircuitBreakerWrap::new()
+}
+
+/// 熔断器的状态。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CircuitState {
+    /// 闭合，请求正常通过。
+    Closed,
+    /// 断开，请求立即失败。
+    Open,
+    /// 半开，只允许探测请求通过。
+    HalfOpen,
+}
+
+/// 判断请求的结果是否失败。
+pub trait Classify<Res, E> {
+    /// 返回`true`表示请求失败。
+    fn is_failure(&self, result: Result<&Res, &E>) -> bool;
+}
+
+impl<F, Res, E> Classify<Res, E> for F
+where
+    F: Fn(Result<&Res, &E>) -> bool,
+{
+    fn is_failure(&self, result: Result<&Res, &E>) -> bool {
+        self(result)
+    }
+}
+
+/// 将错误和`5xx`响应视为失败。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServerErrors;
+
+impl<B, E> Classify<Response<B>, E> for ServerErrors {
+    fn is_failure(&self, result: Result<&Response<B>, &E>) -> bool {
+        match result {
+            Ok(response) => response.status().is_server_error(),
+            Err(_) => true,
+        }
+    }
+}
+
+type OnStateChange = Arc<dyn Fn(CircuitState, CircuitState) + Send + Sync>;
+
+#[derive(Clone)]
+struct Config {
+    failure_rate: f64,
+    minimum_requests: u32,
+    consecutive_failures: u32,
+    window: Duration,
+    cooldown: Duration,
+    probes: u32,
+    on_state_change: Option<OnStateChange>,
+}
+
+pub struct CircuitBreakerWrap<C = ServerErrors> {
+    config: Config,
+    breaker: Arc<Mutex<Breaker>>,
+    classify: C,
+}
+
+impl CircuitBreakerWrap {
+    /// 创建一个新的[`CircuitBreakerWrap`]。
+    pub fn new() -> Self {
+        Self {
+            config: Config {
+                failure_rate: 0.5,
+                minimum_requests: 20,
+                consecutive_failures: 5,
+                window: Duration::from_secs(10),
+                cooldown: Duration::from_secs(10),
+                probes: 1,
+                on_state_change: None,
+            },
+            breaker: Arc::new(Mutex::new(Breaker::new())),
+            classify: ServerErrors,
+        }
+    }
+}
+
+impl Default for CircuitBreakerWrap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> CircuitBreakerWrap<C> {
+    /// 设置断开熔断器的失败率，默认为`0.5`。
+    ///
+    /// 只有窗口内的请求数达到[`minimum_requests`](Self::minimum_requests)时才会检查失败率。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果失败率不在`(0, 1]`范围内，将会发生恐慌。
+    pub fn failure_rate(mut self, failure_rate: f64) -> Self {
+        if !(failure_rate > 0.0 && failure_rate <= 1.0) {
+            panic!("Failure rate must be in the range (0, 1]");
+        }
+        self.config.failure_rate = failure_rate;
+        self
+    }
+
+    /// 设置检查失败率所需的最少请求数，默认为`20`。
+    pub fn minimum_requests(mut self, minimum_requests: u32) -> Self {
+        self.config.minimum_requests = minimum_requests;
+        self
+    }
+
+    /// 设置断开熔断器的连续失败次数，默认为`5`，`0`表示不检查连续失败次数。
+    pub fn consecutive_failures(mut self, consecutive_failures: u32) -> Self {
+        self.config.consecutive_failures = consecutive_failures;
+        self
+    }
+
+    /// 设置统计失败率的滑动窗口，默认为`10`秒。
+    pub fn window(mut self, window: Duration) -> Self {
+        self.config.window = window;
+        self
+    }
+
+    /// 设置断开后进入半开状态前的冷却时间，默认为`10`秒。
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.config.cooldown = cooldown;
+        self
+    }
+
+    /// 设置半开状态下允许的探测请求数，默认为`1`。
+    ///
+    /// 状态改变后，之前开始的请求的结果会被忽略，不会计入新的状态。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use puzz_core::http::StatusCode;
+    /// use puzz_core::service::{Service, ServiceExt};
+    /// use puzz_core::{service_fn, Request, Response};
+    /// use puzz_middleware::circuit_breaker::{circuit_breaker, CircuitBreakerError, CircuitState};
+    ///
+    /// # #[tokio::main(flavor = "current_thread", start_paused = true)]
+    /// # async fn main() {
+    /// let service = service_fn(|request: Request| async move {
+    ///     if request.uri().path() == "/fail" {
+    ///         return Ok::<_, ()>(StatusCode::SERVICE_UNAVAILABLE);
+    ///     }
+    ///     tokio::time::sleep(Duration::from_secs(60)).await;
+    ///     Ok(StatusCode::OK)
+    /// })
+    /// .map_response(|status| Response::builder().status(status).body(()).unwrap())
+    /// .with(circuit_breaker().consecutive_failures(1).probes(3));
+    ///
+    /// let fail = || Request::builder().uri("/fail").body(Default::default()).unwrap();
+    ///
+    /// assert!(service.call(fail()).await.is_ok());
+    /// tokio::time::advance(Duration::from_secs(10)).await;
+    ///
+    /// // 第三个探测请求失败，熔断器重新断开，前两个探测请求被放弃。
+    /// let first = service.call(Request::default());
+    /// let second = service.call(Request::default());
+    /// assert!(service.call(fail()).await.is_ok());
+    /// assert_eq!(service.state(), CircuitState::Open);
+    ///
+    /// tokio::time::advance(Duration::from_secs(10)).await;
+    /// let probes = [
+    ///     service.call(Request::default()),
+    ///     service.call(Request::default()),
+    ///     service.call(Request::default()),
+    /// ];
+    /// drop((first, second));
+    ///
+    /// // 之前的探测请求不会释放新的探测名额。
+    /// assert_eq!(service.state(), CircuitState::HalfOpen);
+    /// assert!(matches!(
+    ///     service.call(Request::default()).await,
+    ///     Err(CircuitBreakerError::Open(_))
+    /// ));
+    /// # drop(probes);
+    /// # }
+    /// ```
+    ///
+    /// # 恐慌
+    ///
+    /// 如果探测请求数为`0`，将会发生恐慌。
+    pub fn probes(mut self, probes: u32) -> Self {
+        assert!(probes > 0, "probes must be greater than 0");
+        self.config.probes = probes;
+        self
+    }
+
+    /// 设置状态变化时调用的回调，参数是变化前和变化后的状态。
+    ///
+    /// 回调在请求的处理过程中同步调用，不应该阻塞。
+    pub fn on_state_change<F>(mut self, f: F) -> Self
+    where
+        F: Fn(CircuitState, CircuitState) + Send + Sync + 'static,
+    {
+        self.config.on_state_change = Some(Arc::new(f));
+        self
+    }
+
+    /// 设置判断请求是否失败的方式。
+    pub fn classify<T>(self, classify: T) -> CircuitBreakerWrap<T> {
+        CircuitBreakerWrap {
+            config: self.config,
+            breaker: self.breaker,
+            classify,
+        }
+    }
+
+    /// 获取熔断器当前的状态。
+    pub fn state(&self) -> CircuitState {
+        self.breaker.lock().unwrap().state()
+    }
+}
+
+impl<C> Clone for CircuitBreakerWrap<C>
+where
+    C: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            breaker: self.breaker.clone(),
+            classify: self.classify.clone(),
+        }
+    }
+}
+
+impl<S, C> Wrap<S> for CircuitBreakerWrap<C> {
+    type Service = CircuitBreaker<S, C>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        CircuitBreaker {
+            inner: service,
+            wrap: self,
+        }
+    }
+}
+
+impl<C> fmt::Debug for CircuitBreakerWrap<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreakerWrap")
+            .field("failure_rate", &self.config.failure_rate)
+            .field("minimum_requests", &self.config.minimum_requests)
+            .field("consecutive_failures", &self.config.consecutive_failures)
+            .field("window", &self.config.window)
+            .field("cooldown", &self.config.cooldown)
+            .field("probes", &self.config.probes)
+            .field("classify", &std::any::type_name::<C>())
+            .field("state", &self.state())
+            .finish()
+    }
+}
+
+pub struct CircuitBreaker<S, C = ServerErrors> {
+    inner: S,
+    wrap: CircuitBreakerWrap<C>,
+}
+
+impl<S, C> CircuitBreaker<S, C> {
+    /// 获取熔断器当前的状态。
+    pub fn state(&self) -> CircuitState {
+        self.wrap.state()
+    }
+}
+
+impl<S, C> Clone for CircuitBreaker<S, C>
+where
+    S: Clone,
+    C: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            wrap: self.wrap.clone(),
+        }
+    }
+}
+
+impl<S, C, Req> Service<Req> for CircuitBreaker<S, C>
+where
+    S: Service<Req>,
+    C: Classify<S::Response, S::Error> + Clone,
+{
+    type Response = S::Response;
+    type Error = CircuitBreakerError<S::Error>;
+    type Future = CircuitBreakerFuture<S::Future, C>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner
+            .poll_ready(cx)
+            .map_err(CircuitBreakerError::Inner)
+    }
+
+    fn call(&self, request: Req) -> Self::Future {
+        let config = &self.wrap.config;
+        let acquired = self.wrap.breaker.lock().unwrap().acquire(config);
+
+        match acquired {
+            Ok((epoch, probe, transition)) => {
+                notify(config, transition);
+                CircuitBreakerFuture::Calling {
+                    fut: self.inner.call(request),
+                    guard: Guard {
+                        config: config.clone(),
+                        breaker: self.wrap.breaker.clone(),
+                        epoch,
+                        probe,
+                        done: false,
+                    },
+                    classify: self.wrap.classify.clone(),
+                }
+            }
+            Err(open) => CircuitBreakerFuture::Open { open: Some(open) },
+        }
+    }
+}
+
+impl<S, C> fmt::Debug for CircuitBreaker<S, C>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("inner", &self.inner)
+            .field("wrap", &self.wrap)
+            .finish()
+    }
+}
+
+pin_project! {
+    #[project = CircuitBreakerFutureProj]
+    pub enum CircuitBreakerFuture<F, C> {
+        Calling {
+            #[pin]
+            fut: F,
+            guard: Guard,
+            classify: C,
+        },
+        Open {
+            open: Option<CircuitOpen>,
+        },
+    }
+}
+
+impl<F, C, Res, E> Future for CircuitBreakerFuture<F, C>
+where
+    F: Future<Output = Result<Res, E>>,
+    C: Classify<Res, E>,
+{
+    type Output = Result<Res, CircuitBreakerError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            CircuitBreakerFutureProj::Calling {
+                fut,
+                guard,
+                classify,
+            } => {
+                let result = futures_core::ready!(fut.poll(cx));
+                guard.record(classify.is_failure(result.as_ref()));
+                Poll::Ready(result.map_err(CircuitBreakerError::Inner))
+            }
+            CircuitBreakerFutureProj::Open { open } => Poll::Ready(Err(CircuitBreakerError::Open(
+                open.take().expect("polled after completion"),
+            ))),
+        }
+    }
+}
+
+impl<F, C> fmt::Debug for CircuitBreakerFuture<F, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Calling { guard, .. } => f
+                .debug_struct("Calling")
+                .field("probe", &guard.probe)
+                .finish(),
+            Self::Open { open } => f.debug_struct("Open").field("open", open).finish(),
+        }
+    }
+}
+
+// 记录请求的结果，请求在完成前被丢弃时释放占用的探测名额。
+struct Guard {
+    config: Config,
+    breaker: Arc<Mutex<Breaker>>,
+    epoch: u64,
+    probe: bool,
+    done: bool,
+}
+
+impl Guard {
+    fn record(&mut self, failure: bool) {
+        self.done = true;
+        let transition = self.breaker.lock().unwrap().record(
+            &self.config,
+            self.epoch,
+            self.probe,
+            failure,
+            Instant::now(),
+        );
+        notify(&self.config, transition);
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if !self.done && self.probe {
+            self.breaker.lock().unwrap().release(self.epoch);
+        }
+    }
+}
+
+impl fmt::Debug for Guard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Guard").field("probe", &self.probe).finish()
+    }
+}
+
+type Transition = Option<(CircuitState, CircuitState)>;
+
+fn notify(config: &Config, transition: Transition) {
+    if let (Some(f), Some((from, to))) = (&config.on_state_change, transition) {
+        f(from, to);
+    }
+}
+
+enum State {
+    Closed,
+    Open { until: Instant },
+    HalfOpen { in_flight: u32, successes: u32 },
+}
+
+struct Bucket {
+    start: Instant,
+    successes: u32,
+    failures: u32,
+}
+
+struct Breaker {
+    state: State,
+    // 每次状态变化时递增，用于识别在之前的状态中开始的请求。
+    epoch: u64,
+    buckets: VecDeque<Bucket>,
+    consecutive_failures: u32,
+}
+
+impl Breaker {
+    fn new() -> Self {
+        Self {
+            state: State::Closed,
+            epoch: 0,
+            buckets: VecDeque::new(),
+            consecutive_failures: 0,
+        }
+    }
+
+    fn state(&self) -> CircuitState {
+        match self.state {
+            State::Closed => CircuitState::Closed,
+            State::Open { .. } => CircuitState::Open,
+            State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    fn transition(&mut self, state: State) -> Transition {
+        let from = self.state();
+        self.state = state;
+        self.epoch += 1;
+        self.buckets.clear();
+        self.consecutive_failures = 0;
+        Some((from, self.state()))
+    }
+
+    // 返回请求开始时的状态编号，以及请求是否是探测请求。
+    fn acquire(&mut self, config: &Config) -> Result<(u64, bool, Transition), CircuitOpen> {
+        let now = Instant::now();
+        let mut transition = None;
+
+        if let State::Open { until } = self.state {
+            if now < until {
+                return Err(CircuitOpen {
+                    retry_after: until - now,
+                });
+            }
+            transition = self.transition(State::HalfOpen {
+                in_flight: 0,
+                successes: 0,
+            });
+        }
+
+        match &mut self.state {
+            State::HalfOpen { in_flight, .. } => {
+                if *in_flight >= config.probes {
+                    return Err(CircuitOpen {
+                        retry_after: Duration::ZERO,
+                    });
+                }
+                *in_flight += 1;
+                Ok((self.epoch, true, transition))
+            }
+            _ => Ok((self.epoch, false, transition)),
+        }
+    }
+
+    fn record(
+        &mut self,
+        config: &Config,
+        epoch: u64,
+        probe: bool,
+        failure: bool,
+        now: Instant,
+    ) -> Transition {
+        // 状态已经改变，忽略之前的请求的结果。
+        if epoch != self.epoch {
+            return None;
+        }
+
+        match &mut self.state {
+            State::HalfOpen {
+                in_flight,
+                successes,
+            } if probe => {
+                *in_flight -= 1;
+                if failure {
+                    self.transition(State::Open {
+                        until: now + config.cooldown,
+                    })
+                } else {
+                    *successes += 1;
+                    if *successes >= config.probes {
+                        self.transition(State::Closed)
+                    } else {
+                        None
+                    }
+                }
+            }
+            State::Closed if !probe => {
+                self.push(config, failure, now);
+                if self.should_trip(config) {
+                    self.transition(State::Open {
+                        until: now + config.cooldown,
+                    })
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn release(&mut self, epoch: u64) {
+        if epoch != self.epoch {
+            return;
+        }
+        if let State::HalfOpen { in_flight, .. } = &mut self.state {
+            *in_flight -= 1;
+        }
+    }
+
+    fn push(&mut self, config: &Config, failure: bool, now: Instant) {
+        if failure {
+            self.consecutive_failures += 1;
+        } else {
+            self.consecutive_failures = 0;
+        }
+
+        while self
+            .buckets
+            .front()
+            .is_some_and(|bucket| bucket.start + config.window <= now)
+        {
+            self.buckets.pop_front();
+        }
+
+        let width = config.window / BUCKETS;
+        let bucket = match self.buckets.back_mut() {
+            Some(bucket) if now < bucket.start + width => bucket,
+            _ => {
+                self.buckets.push_back(Bucket {
+                    start: now,
+                    successes: 0,
+                    failures: 0,
+                });
+                self.buckets.back_mut().unwrap()
+            }
+        };
+
+        if failure {
+            bucket.failures += 1;
+        } else {
+            bucket.successes += 1;
+        }
+    }
+
+    fn should_trip(&self, config: &Config) -> bool {
+        if config.consecutive_failures > 0
+            && self.consecutive_failures >= config.consecutive_failures
+        {
+            return true;
+        }
+
+        let (successes, failures) = self.buckets.iter().fold((0, 0), |(s, f), bucket| {
+            (s + bucket.successes, f + bucket.failures)
+        });
+        let total = successes + failures;
+
+        total > 0
+            && total >= config.minimum_requests
+            && failures as f64 / total as f64 >= config.failure_rate
+    }
+}
+
+/// 熔断器断开时返回的错误。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitOpen {
+    retry_after: Duration,
+}
+
+impl CircuitOpen {
+    /// 获取熔断器进入半开状态前剩余的冷却时间。
+    ///
+    /// 熔断器已经半开但探测名额已满时返回`0`。
+    pub fn retry_after(&self) -> Duration {
+        self.retry_after
+    }
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("circuit breaker is open")
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+impl IntoResponse for CircuitOpen {
+    fn into_response(self) -> Response {
+        let mut response = StatusCode::SERVICE_UNAVAILABLE.into_response();
+        response.headers_mut().insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(self.retry_after.as_secs().max(1)),
+        );
+        response
+    }
+}
+
+/// [`CircuitBreaker`]返回的错误。
+#[derive(Debug)]
+pub enum CircuitBreakerError<E> {
+    /// 熔断器断开，请求没有被处理。
+    Open(CircuitOpen),
+    /// 服务返回的错误。
+    Inner(E),
+}
+
+impl<E> fmt::Display for CircuitBreakerError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitBreakerError::Open(e) => e.fmt(f),
+            CircuitBreakerError::Inner(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E> std::error::Error for CircuitBreakerError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CircuitBreakerError::Open(e) => Some(e),
+            CircuitBreakerError::Inner(e) => Some(e),
+        }
+    }
+}
@@ -0,0 +1,613 @@
+//! 条件请求（RFC 9110 第13节）。
+//!
+//! [`conditional`]为缓冲的响应计算强`ETag`，并根据请求的`If-Match`、`If-None-Match`、`If-Modified-Since`和
+//! `If-Unmodified-Since`标头返回`304 Not Modified`或`412 Precondition Failed`响应。
+//!
+//! 处理函数也可以自行设置`ETag`或`Last-Modified`标头，此时将直接使用这些验证器。
+//!
+//! 对于不安全的方法（例如`PUT`和`DELETE`），条件必须在修改资源之前评估，因此需要通过
+//! [`ConditionalWrap::validators`]提供查询资源当前验证器的函数；没有提供时，这些请求的条件不会被评估。
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures_core::ready;
+use pin_project_lite::pin_project;
+use puzz_core::body::{Body, BodyExt, Bytes};
+use puzz_core::http::header::{
+    CACHE_CONTROL, CONTENT_LOCATION, DATE, ETAG, EXPIRES, IF_MATCH, IF_MODIFIED_SINCE,
+    IF_NONE_MATCH, IF_UNMODIFIED_SINCE, LAST_MODIFIED, VARY,
+};
+use puzz_core::http::marker::{MaybeSend, MaybeSendSync};
+use puzz_core::http::response::Head;
+use puzz_core::http::{HeaderMap, HeaderValue, Method, StatusCode};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::{BoxError, Request, Response};
+use sha2::{Digest, Sha256};
+
+use crate::{BoxFuture, MaybeArc};
+
+// 默认计算ETag的最大正文长度。
+const DEFAULT_MAX_SIZE: u64 = 1024 * 1024;
+
+/// 创建一个处理条件请求的[`Wrap`]。
+///
+/// 对于`GET`和`HEAD`请求的成功响应，如果响应没有`ETag`标头，并且正文长度的上界不超过[最大长度](ConditionalWrap::max_size)，
+/// 将缓冲正文并根据其SHA-256摘要计算强`ETag`。流式正文（例如服务器发送事件）不会被缓冲。
+///
+/// 对于不安全的方法，条件会在调用服务之前根据[`ConditionalWrap::validators`]返回的验证器进行评估，
+/// 条件不成立时直接返回`412 Precondition Failed`响应。没有设置该函数时不会评估这些请求的条件，
+/// 处理函数应该在修改资源之前使用[`Preconditions`]自行评估。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::http::StatusCode;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::conditional::conditional;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|_: Request| async {
+///     Ok::<_, Infallible>(r#"{"name":"alice"}"#)
+/// })
+/// .with(conditional());
+///
+/// let response = service.call(Request::default()).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::OK);
+/// let etag = response.headers()["etag"].clone();
+///
+/// let request = Request::builder()
+///     .header("if-none-match", etag.clone())
+///     .body(Default::default())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+/// assert_eq!(response.headers()["etag"], etag);
+///
+/// let request = Request::builder()
+///     .header("if-match", r#""outdated""#)
+///     .body(Default::default())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+/// # }
+/// ```
+pub fn conditional() -> ConditionalWrap {
+    ConditionalWrap::new()
+}
+
+/// 请求中的条件标头。
+///
+/// # 例子
+///
+/// ```
+/// use puzz_core::http::{HeaderValue, StatusCode};
+/// use puzz_core::Request;
+/// use puzz_middleware::conditional::Preconditions;
+///
+/// let request: Request = Request::builder()
+///     .method("PUT")
+///     .header("if-match", r#""v1""#)
+///     .body(Default::default())
+///     .unwrap();
+/// let preconditions = Preconditions::new(&request);
+///
+/// let current = HeaderValue::from_static(r#""v2""#);
+/// assert_eq!(
+///     preconditions.evaluate(Some(&current), None),
+///     Some(StatusCode::PRECONDITION_FAILED)
+/// );
+///
+/// let current = HeaderValue::from_static(r#""v1""#);
+/// assert_eq!(preconditions.evaluate(Some(&current), None), None);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Preconditions {
+    method: Method,
+    if_match: Option<HeaderValue>,
+    if_none_match: Option<HeaderValue>,
+    if_modified_since: Option<SystemTime>,
+    if_unmodified_since: Option<SystemTime>,
+}
+
+impl Preconditions {
+    /// 从请求中提取条件标头。
+    pub fn new<B>(request: &Request<B>) -> Self {
+        let headers = request.headers();
+        Self {
+            method: request.method().clone(),
+            if_match: headers.get(IF_MATCH).cloned(),
+            if_none_match: headers.get(IF_NONE_MATCH).cloned(),
+            if_modified_since: headers.get(IF_MODIFIED_SINCE).and_then(parse_date),
+            if_unmodified_since: headers.get(IF_UNMODIFIED_SINCE).and_then(parse_date),
+        }
+    }
+
+    /// 请求是否包含条件标头。
+    pub fn is_empty(&self) -> bool {
+        self.if_match.is_none()
+            && self.if_none_match.is_none()
+            && self.if_modified_since.is_none()
+            && self.if_unmodified_since.is_none()
+    }
+
+    fn is_safe(&self) -> bool {
+        self.method == Method::GET || self.method == Method::HEAD
+    }
+
+    /// 根据资源当前的`ETag`和`Last-Modified`评估条件。
+    ///
+    /// 两者都为[`None`]表示资源不存在，此时`If-Match: *`不成立，`If-None-Match: *`成立。
+    ///
+    /// 条件成立时返回[`None`]，否则返回`304 Not Modified`或`412 Precondition Failed`。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use puzz_core::http::{HeaderValue, StatusCode};
+    /// use puzz_core::Request;
+    /// use puzz_middleware::conditional::Preconditions;
+    ///
+    /// let current = HeaderValue::from_static(r#""v1""#);
+    ///
+    /// // 只在资源不存在时创建。
+    /// let request: Request = Request::builder()
+    ///     .method("PUT")
+    ///     .header("if-none-match", "*")
+    ///     .body(Default::default())
+    ///     .unwrap();
+    /// let preconditions = Preconditions::new(&request);
+    /// assert_eq!(preconditions.evaluate(None, None), None);
+    /// assert_eq!(
+    ///     preconditions.evaluate(Some(&current), None),
+    ///     Some(StatusCode::PRECONDITION_FAILED)
+    /// );
+    ///
+    /// // 只在资源存在时更新。
+    /// let request: Request = Request::builder()
+    ///     .method("PUT")
+    ///     .header("if-match", "*")
+    ///     .body(Default::default())
+    ///     .unwrap();
+    /// let preconditions = Preconditions::new(&request);
+    /// assert_eq!(
+    ///     preconditions.evaluate(None, None),
+    ///     Some(StatusCode::PRECONDITION_FAILED)
+    /// );
+    /// assert_eq!(preconditions.evaluate(Some(&current), None), None);
+    /// ```
+    pub fn evaluate(
+        &self,
+        etag: Option<&HeaderValue>,
+        last_modified: Option<&HeaderValue>,
+    ) -> Option<StatusCode> {
+        let exists = etag.is_some() || last_modified.is_some();
+        let etag = etag.and_then(|etag| etag.to_str().ok());
+        let last_modified = last_modified.and_then(parse_date);
+        let safe = self.is_safe();
+
+        // 步骤1和2：If-Match优先于If-Unmodified-Since。
+        if let Some(if_match) = &self.if_match {
+            if !matches_any(if_match, exists, etag, true) {
+                return Some(StatusCode::PRECONDITION_FAILED);
+            }
+        } else if let (Some(since), Some(modified)) = (self.if_unmodified_since, last_modified) {
+            if modified > since {
+                return Some(StatusCode::PRECONDITION_FAILED);
+            }
+        }
+
+        // 步骤3和4：If-None-Match优先于If-Modified-Since。
+        if let Some(if_none_match) = &self.if_none_match {
+            if matches_any(if_none_match, exists, etag, false) {
+                return Some(if safe {
+                    StatusCode::NOT_MODIFIED
+                } else {
+                    StatusCode::PRECONDITION_FAILED
+                });
+            }
+        } else if let (true, Some(since), Some(modified)) =
+            (safe, self.if_modified_since, last_modified)
+        {
+            if modified <= since {
+                return Some(StatusCode::NOT_MODIFIED);
+            }
+        }
+
+        None
+    }
+}
+
+fn parse_date(value: &HeaderValue) -> Option<SystemTime> {
+    httpdate::parse_http_date(value.to_str().ok()?).ok()
+}
+
+/// 判断实体标签列表是否与当前的`ETag`匹配，`*`匹配任何存在的资源。
+fn matches_any(list: &HeaderValue, exists: bool, etag: Option<&str>, strong: bool) -> bool {
+    let list = match list.to_str() {
+        Ok(list) => list.trim(),
+        Err(_) => return false,
+    };
+
+    if list == "*" {
+        return exists;
+    }
+
+    let etag = match etag {
+        Some(etag) => etag,
+        None => return false,
+    };
+
+    list.split(',').map(str::trim).any(|tag| {
+        if strong {
+            // 强比较：两者都不能是弱标签。
+            !tag.starts_with("W/") && !etag.starts_with("W/") && tag == etag
+        } else {
+            tag.trim_start_matches("W/") == etag.trim_start_matches("W/")
+        }
+    })
+}
+
+/// 资源当前的验证器，由[`ConditionalWrap::validators`]设置的函数返回。
+///
+/// 两者都为[`None`]表示资源不存在。
+#[derive(Debug, Clone, Default)]
+pub struct Validators {
+    /// 资源当前的`ETag`。
+    pub etag: Option<HeaderValue>,
+    /// 资源当前的`Last-Modified`。
+    pub last_modified: Option<HeaderValue>,
+}
+
+#[cfg(not(feature = "send"))]
+type LookupValidators = MaybeArc<dyn Fn(&Request) -> BoxFuture<Validators>>;
+#[cfg(feature = "send")]
+type LookupValidators = MaybeArc<dyn Fn(&Request) -> BoxFuture<Validators> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct ConditionalWrap {
+    etag: bool,
+    max_size: u64,
+    validators: Option<LookupValidators>,
+}
+
+impl ConditionalWrap {
+    pub fn new() -> Self {
+        Self {
+            etag: true,
+            max_size: DEFAULT_MAX_SIZE,
+            validators: None,
+        }
+    }
+
+    /// 设置是否自动计算`ETag`，默认为`true`。
+    pub fn etag(mut self, enable: bool) -> Self {
+        self.etag = enable;
+        self
+    }
+
+    /// 设置计算`ETag`时缓冲正文的最大长度，默认为1MiB。
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// 设置查询资源当前验证器的函数，用于在调用服务之前评估不安全方法的条件。
+    ///
+    /// 只有包含条件标头的不安全请求才会调用该函数。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use std::convert::Infallible;
+    ///
+    /// use puzz_core::http::{HeaderValue, StatusCode};
+    /// use puzz_core::service::{Service, ServiceExt};
+    /// use puzz_core::{service_fn, Request};
+    /// use puzz_middleware::conditional::{conditional, Validators};
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("updated") }).with(
+    ///     conditional().validators(|_: &Request| async {
+    ///         Validators {
+    ///             etag: Some(HeaderValue::from_static(r#""v2""#)),
+    ///             last_modified: None,
+    ///         }
+    ///     }),
+    /// );
+    ///
+    /// // 资源已经被其他客户端修改，服务不会被调用。
+    /// let request = Request::builder()
+    ///     .method("PUT")
+    ///     .header("if-match", r#""v1""#)
+    ///     .body(Default::default())
+    ///     .unwrap();
+    /// let response = service.call(request).await.unwrap();
+    /// assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    ///
+    /// let request = Request::builder()
+    ///     .method("PUT")
+    ///     .header("if-match", r#""v2""#)
+    ///     .body(Default::default())
+    ///     .unwrap();
+    /// let response = service.call(request).await.unwrap();
+    /// assert_eq!(response.status(), StatusCode::OK);
+    /// # }
+    /// ```
+    pub fn validators<F, Fut>(mut self, f: F) -> Self
+    where
+        F: Fn(&Request) -> Fut + MaybeSendSync + 'static,
+        Fut: Future<Output = Validators> + MaybeSend + 'static,
+    {
+        self.validators = Some(MaybeArc::new(move |request| Box::pin(f(request))));
+        self
+    }
+}
+
+impl Default for ConditionalWrap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Wrap<S> for ConditionalWrap {
+    type Service = Conditional<S>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        Conditional {
+            inner: Arc::new(service),
+            wrap: self,
+        }
+    }
+}
+
+impl fmt::Debug for ConditionalWrap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConditionalWrap")
+            .field("etag", &self.etag)
+            .field("max_size", &self.max_size)
+            .field("validators", &self.validators.is_some())
+            .finish()
+    }
+}
+
+pub struct Conditional<S> {
+    // 评估不安全方法的条件后才会调用服务。
+    inner: Arc<S>,
+    wrap: ConditionalWrap,
+}
+
+impl<S> Clone for Conditional<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            wrap: self.wrap.clone(),
+        }
+    }
+}
+
+impl<S> fmt::Debug for Conditional<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Conditional")
+            .field("inner", &self.inner)
+            .field("wrap", &self.wrap)
+            .finish()
+    }
+}
+
+impl<S> Service<Request> for Conditional<S>
+where
+    S: Service<Request>,
+    S::Response: IntoResponse,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = ConditionalFuture<S>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Request) -> Self::Future {
+        let preconditions = Preconditions::new(&request);
+        let safe = preconditions.is_safe();
+        let state = State {
+            etag: self.wrap.etag && safe,
+            safe,
+            max_size: self.wrap.max_size,
+            preconditions,
+        };
+
+        match &self.wrap.validators {
+            Some(validators) if !safe && !state.preconditions.is_empty() => {
+                ConditionalFuture::Checking {
+                    fut: validators(&request),
+                    state: Some((self.inner.clone(), request, state)),
+                }
+            }
+            _ => ConditionalFuture::Calling {
+                fut: self.inner.call(request),
+                state: Some(state),
+            },
+        }
+    }
+}
+
+struct State {
+    preconditions: Preconditions,
+    safe: bool,
+    etag: bool,
+    max_size: u64,
+}
+
+impl State {
+    fn should_buffer(&self, response: &Response) -> bool {
+        self.etag
+            && response.status().is_success()
+            && !response.headers().contains_key(ETAG)
+            && response
+                .body()
+                .size_hint()
+                .upper()
+                .is_some_and(|upper| upper <= self.max_size)
+    }
+
+    fn evaluate(&self, response: Response) -> Response {
+        // 只有安全方法的成功响应才需要评估条件，不安全方法的条件已经在调用服务之前评估过了。
+        if !self.safe || !response.status().is_success() || self.preconditions.is_empty() {
+            return response;
+        }
+
+        let headers = response.headers();
+        match self
+            .preconditions
+            .evaluate(headers.get(ETAG), headers.get(LAST_MODIFIED))
+        {
+            Some(StatusCode::NOT_MODIFIED) => not_modified(response),
+            Some(status) => status.into_response(),
+            None => response,
+        }
+    }
+}
+
+/// 丢弃正文，只保留304响应需要的标头。
+fn not_modified(response: Response) -> Response {
+    let (head, _) = response.into_head();
+
+    let mut headers = HeaderMap::new();
+    for name in [
+        CACHE_CONTROL,
+        CONTENT_LOCATION,
+        DATE,
+        ETAG,
+        EXPIRES,
+        LAST_MODIFIED,
+        VARY,
+    ] {
+        for value in head.headers.get_all(&name) {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+
+    let mut response = (StatusCode::NOT_MODIFIED, headers).into_response();
+    *response.extensions_mut() = head.extensions;
+    response
+}
+
+fn etag(bytes: &[u8]) -> HeaderValue {
+    let digest = Sha256::digest(bytes);
+    let etag = format!("\"{}\"", URL_SAFE_NO_PAD.encode(digest));
+    HeaderValue::try_from(etag).expect("base64 is a valid header value")
+}
+
+async fn buffer(response: Response) -> (Head, Result<Bytes, BoxError>) {
+    let (head, body) = response.into_head();
+    (head, crate::body::to_bytes(body).await)
+}
+
+pin_project! {
+    #[project = ConditionalFutureProj]
+    pub enum ConditionalFuture<S>
+    where
+        S: Service<Request>,
+    {
+        Checking {
+            fut: BoxFuture<Validators>,
+            state: Option<(Arc<S>, Request, State)>,
+        },
+        Calling {
+            #[pin]
+            fut: S::Future,
+            state: Option<State>,
+        },
+        Buffering {
+            fut: BoxFuture<(Head, Result<Bytes, BoxError>)>,
+            state: Option<State>,
+        },
+    }
+}
+
+impl<S> Future for ConditionalFuture<S>
+where
+    S: Service<Request>,
+    S::Response: IntoResponse,
+{
+    type Output = Result<Response, S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                ConditionalFutureProj::Checking { fut, state } => {
+                    let validators = ready!(fut.as_mut().poll(cx));
+                    let (inner, request, state) = state.take().expect("polled after completion");
+
+                    if let Some(status) = state
+                        .preconditions
+                        .evaluate(validators.etag.as_ref(), validators.last_modified.as_ref())
+                    {
+                        return Poll::Ready(Ok(status.into_response()));
+                    }
+
+                    self.set(ConditionalFuture::Calling {
+                        fut: inner.call(request),
+                        state: Some(state),
+                    });
+                }
+                ConditionalFutureProj::Calling { fut, state } => {
+                    let response = ready!(fut.poll(cx))?.into_response();
+                    let state = state.take().expect("polled after completion");
+
+                    if !state.should_buffer(&response) {
+                        return Poll::Ready(Ok(state.evaluate(response)));
+                    }
+
+                    self.set(ConditionalFuture::Buffering {
+                        fut: Box::pin(buffer(response)),
+                        state: Some(state),
+                    });
+                }
+                ConditionalFutureProj::Buffering { fut, state } => {
+                    let (mut head, bytes) = ready!(fut.as_mut().poll(cx));
+                    let state = state.take().expect("polled after completion");
+
+                    let bytes = match bytes {
+                        Ok(bytes) => bytes,
+                        Err(_) => {
+                            return Poll::Ready(Ok(
+                                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                            ))
+                        }
+                    };
+
+                    head.headers.insert(ETAG, etag(&bytes));
+                    let response = Response::from_head(head, bytes.boxed());
+                    return Poll::Ready(Ok(state.evaluate(response)));
+                }
+            }
+        }
+    }
+}
+
+impl<S> fmt::Debug for ConditionalFuture<S>
+where
+    S: Service<Request>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConditionalFuture").finish()
+    }
+}
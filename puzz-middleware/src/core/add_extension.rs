@@ -1,5 +1,7 @@
 use std::fmt;
+use std::task::{Context, Poll};
 
+use puzz_core::http::marker::MaybeSendSync;
 use puzz_core::service::{Service, Wrap};
 use puzz_core::Request;
 
@@ -47,12 +49,16 @@ impl<S, F, B, T> Service<Request<B>> for AddExtension<S, F>
 where
     S: Service<Request<B>>,
     F: Fn() -> T,
-    T: 'static,
+    T: MaybeSendSync + 'static,
 {
     type Response = S::Response;
     type Error = S::Error;
     type Future = S::Future;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
     fn call(&self, mut request: Request<B>) -> Self::Future {
         request.extensions_mut().insert((self.f)());
         self.inner.call(request)
@@ -57,6 +57,14 @@ where
     type Error = Infallible;
     type Future = HandleErrorFuture<S::Future, F>;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // 就绪检查的错误无法转换为响应，交给随后的调用处理。
+        match self.inner.poll_ready(cx) {
+            Poll::Ready(_) => Poll::Ready(Ok(())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
     fn call(&self, request: Req) -> Self::Future {
         HandleErrorFuture::Incomplete {
             fut: self.inner.call(request),
@@ -0,0 +1,644 @@
+//! 跨站请求伪造（CSRF）防护。
+//!
+//! [`csrf`]为每个请求准备一个令牌，并以[`CsrfToken`]的形式插入请求扩展，处理函数可以将其渲染到表单中。
+//! 令牌可以通过[双重提交Cookie](CsrfPattern::DoubleSubmitCookie)或[同步器令牌](CsrfPattern::Synchronizer)模式保存。
+//!
+//! 对于不安全的方法（除`GET`、`HEAD`、`OPTIONS`和`TRACE`以外的方法），依次检查：
+//!
+//! - `Sec-Fetch-Site`标头不能是`cross-site`；
+//! - `Origin`标头（如果存在）必须与请求的`Host`或受信任的源一致；
+//! - `X-CSRF-Token`标头或表单字段`csrf_token`中的令牌必须与保存的令牌一致。
+//!
+//! 检查失败时返回`403 Forbidden`响应，响应扩展中包含具体的[`CsrfError`]。
+//!
+//! 从表单中读取令牌时，正文大小不能超过[限制](CsrfWrap::body_limit)，否则返回`413 Payload Too Large`响应。
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use cookie::{Cookie, SameSite};
+use futures_core::ready;
+use pin_project_lite::pin_project;
+use puzz_core::body::{Body, BodyExt, Bytes};
+use puzz_core::http::header::{CONTENT_TYPE, COOKIE, HOST, ORIGIN, SET_COOKIE};
+use puzz_core::http::marker::MaybeSendSync;
+use puzz_core::http::{HeaderName, HeaderValue, Method, StatusCode};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::{Request, Response};
+
+use crate::session::Session;
+use crate::{BoxFuture, MaybeArc};
+
+/// `X-CSRF-Token`标头。
+pub const X_CSRF_TOKEN: HeaderName = HeaderName::from_static("x-csrf-token");
+
+const SEC_FETCH_SITE: HeaderName = HeaderName::from_static("sec-fetch-site");
+
+const DEFAULT_COOKIE_NAME: &str = "csrf_token";
+
+const DEFAULT_FIELD_NAME: &str = "csrf_token";
+
+const DEFAULT_BODY_LIMIT: usize = 64 * 1024;
+
+const SESSION_KEY: &str = "_csrf";
+
+/// 创建一个防护跨站请求伪造的[`Wrap`]，默认使用双重提交Cookie模式。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::body::BodyExt;
+/// use puzz_core::http::StatusCode;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::csrf::{csrf, CsrfError, CsrfToken};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|request: Request| async move {
+///     let token = request.extensions().get::<CsrfToken>().unwrap();
+///     Ok::<_, Infallible>(format!(r#"<input name="csrf_token" value="{}">"#, token.as_str()))
+/// })
+/// .with(csrf().secure(false).exempt(|request| request.uri().path().starts_with("/api/")));
+///
+/// // 安全的方法会签发令牌。
+/// let response = service.call(Request::default()).await.unwrap();
+/// let cookie = response.headers()["set-cookie"].to_str().unwrap();
+/// let cookie = cookie.split(';').next().unwrap().to_owned();
+/// let token = cookie.trim_start_matches("csrf_token=").to_owned();
+///
+/// // 提交表单时需要携带令牌。
+/// let request = Request::builder()
+///     .method("POST")
+///     .header("cookie", &cookie)
+///     .header("content-type", "application/x-www-form-urlencoded")
+///     .body(format!("name=alice&csrf_token={}", token).boxed())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::OK);
+///
+/// // 跨站请求将被拒绝。
+/// let request = Request::builder()
+///     .method("POST")
+///     .header("cookie", &cookie)
+///     .header("x-csrf-token", &token)
+///     .header("sec-fetch-site", "cross-site")
+///     .body(Default::default())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::FORBIDDEN);
+/// assert!(matches!(
+///     response.extensions().get::<CsrfError>(),
+///     Some(CsrfError::CrossSite)
+/// ));
+///
+/// // 表单正文默认不能超过64KiB。
+/// let request = Request::builder()
+///     .method("POST")
+///     .header("cookie", &cookie)
+///     .header("content-type", "application/x-www-form-urlencoded")
+///     .body(format!("csrf_token={}&data={}", token, "a".repeat(64 * 1024)).boxed())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+///
+/// // 豁免的路由不检查令牌。
+/// let request = Request::builder()
+///     .method("POST")
+///     .uri("/api/users")
+///     .body(Default::default())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+pub fn csrf() -> CsrfWrap {
+    CsrfWrap::new()
+}
+
+/// 保存令牌的模式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsrfPattern {
+    /// 将令牌保存在Cookie中，提交时与请求中的令牌比较。
+    #[default]
+    DoubleSubmitCookie,
+    /// 将令牌保存在[`Session`]中，需要在外层使用[`session`](crate::session::session)中间件。
+    Synchronizer,
+}
+
+/// 当前请求的CSRF令牌。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    /// 获取令牌的字符串形式。
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CsrfToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// CSRF检查失败的原因。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsrfError {
+    /// `Sec-Fetch-Site`标头表明这是一个跨站请求。
+    CrossSite,
+    /// `Origin`标头与请求的`Host`或受信任的源不一致。
+    OriginMismatch,
+    /// 请求没有携带令牌。
+    MissingToken,
+    /// 请求携带的令牌无效。
+    InvalidToken,
+    /// 使用同步器令牌模式，但请求扩展中没有[`Session`]。
+    MissingSession,
+    /// 表单正文超过了[限制](CsrfWrap::body_limit)。
+    PayloadTooLarge,
+}
+
+impl fmt::Display for CsrfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsrfError::CrossSite => f.write_str("cross-site request"),
+            CsrfError::OriginMismatch => f.write_str("origin mismatch"),
+            CsrfError::MissingToken => f.write_str("missing csrf token"),
+            CsrfError::InvalidToken => f.write_str("invalid csrf token"),
+            CsrfError::MissingSession => f.write_str("missing session"),
+            CsrfError::PayloadTooLarge => f.write_str("payload too large"),
+        }
+    }
+}
+
+impl std::error::Error for CsrfError {}
+
+impl IntoResponse for CsrfError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            CsrfError::MissingSession => StatusCode::INTERNAL_SERVER_ERROR,
+            CsrfError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            _ => StatusCode::FORBIDDEN,
+        };
+        let mut response = (status, self.to_string()).into_response();
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+#[cfg(not(feature = "send"))]
+type Exempt = MaybeArc<dyn Fn(&Request) -> bool>;
+#[cfg(feature = "send")]
+type Exempt = MaybeArc<dyn Fn(&Request) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+pub struct CsrfWrap {
+    pattern: CsrfPattern,
+    cookie_name: String,
+    field_name: String,
+    secure: bool,
+    trusted_origins: Vec<String>,
+    exempt: Option<Exempt>,
+    body_limit: usize,
+}
+
+impl CsrfWrap {
+    pub fn new() -> Self {
+        Self {
+            pattern: CsrfPattern::default(),
+            cookie_name: DEFAULT_COOKIE_NAME.to_owned(),
+            field_name: DEFAULT_FIELD_NAME.to_owned(),
+            secure: true,
+            trusted_origins: Vec::new(),
+            exempt: None,
+            body_limit: DEFAULT_BODY_LIMIT,
+        }
+    }
+
+    /// 设置保存令牌的模式，默认为[`CsrfPattern::DoubleSubmitCookie`]。
+    pub fn pattern(mut self, pattern: CsrfPattern) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    /// 设置双重提交Cookie的名称，默认为`csrf_token`。
+    pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.cookie_name = name.into();
+        self
+    }
+
+    /// 设置表单字段的名称，默认为`csrf_token`。
+    pub fn field_name(mut self, name: impl Into<String>) -> Self {
+        self.field_name = name.into();
+        self
+    }
+
+    /// 设置双重提交Cookie的`Secure`属性，默认为`true`。
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// 设置从表单中读取令牌时允许的最大正文字节数，默认为`64KiB`。
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    /// 添加一个受信任的源（例如`https://admin.example.com`），来自该源的请求可以通过`Origin`检查。
+    pub fn trusted_origin(mut self, origin: impl Into<String>) -> Self {
+        self.trusted_origins.push(origin.into());
+        self
+    }
+
+    /// 设置豁免检查的请求，例如使用令牌认证的JSON API路由。
+    ///
+    /// 豁免的请求仍然会获得[`CsrfToken`]。
+    pub fn exempt<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Request) -> bool + MaybeSendSync + 'static,
+    {
+        self.exempt = Some(MaybeArc::new(f));
+        self
+    }
+
+    fn cookie_token(&self, request: &Request) -> Option<String> {
+        request
+            .headers()
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(Cookie::split_parse)
+            .filter_map(Result::ok)
+            .find(|cookie| cookie.name() == self.cookie_name)
+            .map(|cookie| cookie.value().to_owned())
+            .filter(|token| !token.is_empty())
+    }
+
+    fn check_origin(&self, request: &Request) -> Result<(), CsrfError> {
+        let headers = request.headers();
+
+        if headers
+            .get(SEC_FETCH_SITE)
+            .is_some_and(|site| site == "cross-site")
+        {
+            return Err(CsrfError::CrossSite);
+        }
+
+        let origin = match headers.get(ORIGIN).and_then(|origin| origin.to_str().ok()) {
+            Some(origin) => origin,
+            None => return Ok(()),
+        };
+
+        if self.trusted_origins.iter().any(|trusted| trusted == origin) {
+            return Ok(());
+        }
+
+        let host = headers
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .or_else(|| {
+                request
+                    .uri()
+                    .authority()
+                    .map(|authority| authority.as_str())
+            });
+
+        match (origin.split_once("://"), host) {
+            (Some((_, origin_host)), Some(host)) if origin_host.eq_ignore_ascii_case(host) => {
+                Ok(())
+            }
+            _ => Err(CsrfError::OriginMismatch),
+        }
+    }
+}
+
+impl Default for CsrfWrap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Wrap<S> for CsrfWrap {
+    type Service = Csrf<S>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        Csrf {
+            inner: Arc::new(service),
+            wrap: MaybeArc::new(self),
+        }
+    }
+}
+
+impl fmt::Debug for CsrfWrap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CsrfWrap")
+            .field("pattern", &self.pattern)
+            .field("cookie_name", &self.cookie_name)
+            .field("field_name", &self.field_name)
+            .field("secure", &self.secure)
+            .field("trusted_origins", &self.trusted_origins)
+            .field("body_limit", &self.body_limit)
+            .finish()
+    }
+}
+
+pub struct Csrf<S> {
+    // 读取表单后才会调用服务。
+    inner: Arc<S>,
+    wrap: MaybeArc<CsrfWrap>,
+}
+
+impl<S> Clone for Csrf<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            wrap: self.wrap.clone(),
+        }
+    }
+}
+
+impl<S> Csrf<S> {
+    /// 获取或生成令牌，返回令牌和需要设置的Cookie。
+    fn token(&self, request: &Request) -> Result<(String, Option<HeaderValue>), CsrfError> {
+        match self.wrap.pattern {
+            CsrfPattern::DoubleSubmitCookie => match self.wrap.cookie_token(request) {
+                Some(token) => Ok((token, None)),
+                None => {
+                    let token = generate_token();
+                    // 双重提交Cookie需要允许脚本读取，以便通过标头提交令牌。
+                    let cookie = Cookie::build((self.wrap.cookie_name.clone(), token.clone()))
+                        .path("/")
+                        .secure(self.wrap.secure)
+                        .same_site(SameSite::Lax)
+                        .build();
+                    Ok((token, HeaderValue::try_from(cookie.to_string()).ok()))
+                }
+            },
+            CsrfPattern::Synchronizer => {
+                let session = request
+                    .extensions()
+                    .get::<Session>()
+                    .ok_or(CsrfError::MissingSession)?;
+                match session.get::<String>(SESSION_KEY) {
+                    Some(token) => Ok((token, None)),
+                    None => {
+                        let token = generate_token();
+                        session
+                            .insert(SESSION_KEY, &token)
+                            .map_err(|_| CsrfError::MissingSession)?;
+                        Ok((token, None))
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<S> Service<Request> for Csrf<S>
+where
+    S: Service<Request>,
+    S::Response: IntoResponse,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = CsrfFuture<S>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, mut request: Request) -> Self::Future {
+        let (token, set_cookie) = match self.token(&request) {
+            Ok(token) => token,
+            Err(e) => return CsrfFuture::rejected(e),
+        };
+        request.extensions_mut().insert(CsrfToken(token.clone()));
+
+        let safe = matches!(
+            *request.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        );
+        let exempt = self.wrap.exempt.as_ref().is_some_and(|f| f(&request));
+
+        if safe || exempt {
+            return CsrfFuture::call(&*self.inner, request, set_cookie);
+        }
+
+        if let Err(e) = self.wrap.check_origin(&request) {
+            return CsrfFuture::rejected(e);
+        }
+
+        if let Some(submitted) = request.headers().get(X_CSRF_TOKEN) {
+            return match verify(submitted.as_bytes(), &token) {
+                Ok(()) => CsrfFuture::call(&*self.inner, request, set_cookie),
+                Err(e) => CsrfFuture::rejected(e),
+            };
+        }
+
+        if !is_form(&request) {
+            return CsrfFuture::rejected(CsrfError::MissingToken);
+        }
+
+        let field_name = self.wrap.field_name.clone();
+        CsrfFuture::Reading {
+            fut: Box::pin(read_field(request, field_name, self.wrap.body_limit)),
+            state: Some((self.inner.clone(), token, set_cookie)),
+        }
+    }
+}
+
+impl<S> fmt::Debug for Csrf<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Csrf")
+            .field("inner", &self.inner)
+            .field("wrap", &self.wrap)
+            .finish()
+    }
+}
+
+fn generate_token() -> String {
+    let mut buf = uuid::Uuid::encode_buffer();
+    uuid::Uuid::new_v4()
+        .simple()
+        .encode_lower(&mut buf)
+        .to_owned()
+}
+
+fn is_form(request: &Request) -> bool {
+    request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| {
+            mime.trim()
+                .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+        })
+}
+
+/// 以固定时间比较令牌。
+fn verify(submitted: &[u8], token: &str) -> Result<(), CsrfError> {
+    let token = token.as_bytes();
+    if submitted.is_empty() {
+        return Err(CsrfError::MissingToken);
+    }
+    if submitted.len() != token.len() {
+        return Err(CsrfError::InvalidToken);
+    }
+    if submitted
+        .iter()
+        .zip(token)
+        .fold(0, |acc, (a, b)| acc | (a ^ b))
+        == 0
+    {
+        Ok(())
+    } else {
+        Err(CsrfError::InvalidToken)
+    }
+}
+
+/// 读取表单字段，并将正文放回请求中。
+async fn read_field(
+    mut request: Request,
+    field_name: String,
+    limit: usize,
+) -> Result<(Request, Option<String>), CsrfError> {
+    let body = std::mem::take(request.body_mut());
+    let bytes = match read_limited(body, limit).await {
+        Ok(Some(bytes)) => bytes,
+        Ok(None) => return Err(CsrfError::PayloadTooLarge),
+        Err(_) => return Ok((request, None)),
+    };
+
+    let field = form_urlencoded::parse(&bytes)
+        .find(|(key, _)| *key == field_name)
+        .map(|(_, value)| value.into_owned());
+
+    *request.body_mut() = bytes.boxed();
+    Ok((request, field))
+}
+
+/// 读取完整的正文，超过`limit`字节时返回[`None`]。
+async fn read_limited<B>(mut body: B, limit: usize) -> Result<Option<Bytes>, B::Error>
+where
+    B: Body + Unpin,
+{
+    if body.size_hint().lower() > limit as u64 {
+        return Ok(None);
+    }
+
+    let mut buf = Vec::new();
+    while let Some(data) = body.next().await {
+        let data = data?;
+        if buf.len() + data.len() > limit {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&data);
+    }
+
+    Ok(Some(buf.into()))
+}
+
+pin_project! {
+    #[project = CsrfFutureProj]
+    pub enum CsrfFuture<S>
+    where
+        S: Service<Request>,
+    {
+        Reading {
+            fut: BoxFuture<Result<(Request, Option<String>), CsrfError>>,
+            state: Option<(Arc<S>, String, Option<HeaderValue>)>,
+        },
+        Calling {
+            #[pin]
+            fut: S::Future,
+            set_cookie: Option<HeaderValue>,
+        },
+        Rejected {
+            error: Option<CsrfError>,
+        },
+    }
+}
+
+impl<S> CsrfFuture<S>
+where
+    S: Service<Request>,
+{
+    fn call(inner: &S, request: Request, set_cookie: Option<HeaderValue>) -> Self {
+        Self::Calling {
+            fut: inner.call(request),
+            set_cookie,
+        }
+    }
+
+    fn rejected(error: CsrfError) -> Self {
+        Self::Rejected { error: Some(error) }
+    }
+}
+
+impl<S> Future for CsrfFuture<S>
+where
+    S: Service<Request>,
+    S::Response: IntoResponse,
+{
+    type Output = Result<Response, S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                CsrfFutureProj::Reading { fut, state } => {
+                    let result = ready!(fut.as_mut().poll(cx));
+                    let (inner, token, set_cookie) = state.take().expect("polled after completion");
+
+                    let (request, field) = match result {
+                        Ok(result) => result,
+                        Err(e) => {
+                            self.set(CsrfFuture::rejected(e));
+                            continue;
+                        }
+                    };
+
+                    let next = match field.map_or(Err(CsrfError::MissingToken), |field| {
+                        verify(field.as_bytes(), &token)
+                    }) {
+                        Ok(()) => CsrfFuture::call(&*inner, request, set_cookie),
+                        Err(e) => CsrfFuture::rejected(e),
+                    };
+                    self.set(next);
+                }
+                CsrfFutureProj::Calling { fut, set_cookie } => {
+                    let mut response = ready!(fut.poll(cx))?.into_response();
+                    if let Some(set_cookie) = set_cookie.take() {
+                        response.headers_mut().append(SET_COOKIE, set_cookie);
+                    }
+                    return Poll::Ready(Ok(response));
+                }
+                CsrfFutureProj::Rejected { error } => {
+                    let error = error.take().expect("polled after completion");
+                    return Poll::Ready(Ok(error.into_response()));
+                }
+            }
+        }
+    }
+}
+
+impl<S> fmt::Debug for CsrfFuture<S>
+where
+    S: Service<Request>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CsrfFuture").finish()
+    }
+}
@@ -0,0 +1,481 @@
+//! 代理转发的客户端信息。
+//!
+//! 位于反向代理或负载均衡器之后时，[`PeerAddr`]是代理的地址。[`forwarded`]从受信任的代理转发的
+//! `Forwarded`（RFC 7239）或`X-Forwarded-For`、`X-Forwarded-Proto`和`X-Forwarded-Host`标头中解析客户端的信息，
+//! 并以[`ClientIp`]和[`EffectiveOrigin`]的形式插入请求扩展。
+//!
+//! 只有当直接连接的对端属于受信任的网段时才会读取这些标头。沿着转发链从右向左查找，
+//! 第一个不受信任的地址即为客户端地址，以防止客户端伪造标头。
+//!
+//! 读取哪一组标头由[`ForwardedWrap::header`]决定，必须与代理实际设置的标头一致。
+//! 另一组标头总是被忽略，因为代理通常不会删除它不认识的标头，客户端可以借此伪造信息。
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+
+use puzz_core::http::header::{FORWARDED, HOST};
+use puzz_core::http::uri::{Authority, Scheme};
+use puzz_core::http::{HeaderMap, HeaderName, Uri};
+use puzz_core::service::{Service, Wrap};
+use puzz_core::Request;
+use puzz_server::PeerAddr;
+
+/// `X-Forwarded-For`标头。
+pub const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// `X-Forwarded-Proto`标头。
+pub const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+/// `X-Forwarded-Host`标头。
+pub const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+
+/// 创建一个解析代理转发信息的[`Wrap`]。
+///
+/// 默认不信任任何代理，此时[`ClientIp`]为对端地址。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::forwarded::{forwarded, ClientIp, EffectiveOrigin};
+/// use puzz_server::PeerAddr;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|request: Request| async move {
+///     let ip = request.extensions().get::<ClientIp>().unwrap();
+///     let origin = request.extensions().get::<EffectiveOrigin>().unwrap();
+///     assert_eq!(ip.to_string(), "203.0.113.7");
+///     assert_eq!(origin.to_string(), "https://example.com");
+///     assert_eq!(request.uri(), "https://example.com/users");
+///     Ok::<_, Infallible>("hi!")
+/// })
+/// .with(forwarded().trust("10.0.0.0/8").rewrite_uri(true));
+///
+/// let mut request = Request::builder()
+///     .uri("/users")
+///     .header("host", "backend:8080")
+///     // 最左侧的地址由客户端伪造，将被忽略。
+///     .header("x-forwarded-for", "1.2.3.4, 203.0.113.7, 10.0.0.2")
+///     .header("x-forwarded-proto", "https")
+///     .header("x-forwarded-host", "example.com")
+///     .body(Default::default())
+///     .unwrap();
+/// request
+///     .extensions_mut()
+///     .insert(PeerAddr("10.0.0.1:4321".parse().unwrap()));
+///
+/// service.call(request).await.unwrap();
+/// # }
+/// ```
+pub fn forwarded() -> ForwardedWrap {
+    ForwardedWrap::new()
+}
+
+/// 客户端的IP地址。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClientIp(pub IpAddr);
+
+impl fmt::Display for ClientIp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// 客户端看到的方案和主机，可以用于生成URL和重定向。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectiveOrigin {
+    scheme: Scheme,
+    host: Option<Authority>,
+}
+
+impl EffectiveOrigin {
+    /// 获取方案。
+    pub fn scheme(&self) -> &Scheme {
+        &self.scheme
+    }
+
+    /// 获取主机（可能包含端口），如果无法确定则返回[`None`]。
+    pub fn host(&self) -> Option<&Authority> {
+        self.host.as_ref()
+    }
+}
+
+impl fmt::Display for EffectiveOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.host {
+            Some(host) => write!(f, "{}://{}", self.scheme, host),
+            None => write!(f, "{}://", self.scheme),
+        }
+    }
+}
+
+/// IP网段。
+///
+/// 可以从`10.0.0.0/8`、`fd00::/8`或单个IP地址的形式解析。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// 创建网段。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果前缀长度超过地址的位数，将会发生恐慌。
+    pub fn new(addr: IpAddr, prefix: u8) -> Self {
+        let bits = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        assert!(prefix <= bits, "invalid prefix length");
+        Self { addr, prefix }
+    }
+
+    /// 网段是否包含给定的地址。
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = InvalidCidr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+
+        let addr = addr.parse::<IpAddr>().map_err(|_| InvalidCidr(()))?;
+        let bits = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse::<u8>().map_err(|_| InvalidCidr(()))?,
+            None => bits,
+        };
+
+        if prefix > bits {
+            return Err(InvalidCidr(()));
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// 无效的网段。
+#[derive(Debug)]
+pub struct InvalidCidr(());
+
+impl fmt::Display for InvalidCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid CIDR")
+    }
+}
+
+impl std::error::Error for InvalidCidr {}
+
+/// 读取转发信息的标头。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ForwardedHeader {
+    /// `X-Forwarded-For`、`X-Forwarded-Proto`和`X-Forwarded-Host`标头。
+    #[default]
+    XForwardedFor,
+    /// `Forwarded`标头（RFC 7239）。
+    Forwarded,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ForwardedWrap {
+    trusted: Vec<Cidr>,
+    header: ForwardedHeader,
+    rewrite_uri: bool,
+}
+
+impl ForwardedWrap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 信任给定网段中的代理，例如`10.0.0.0/8`或`127.0.0.1`。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果网段无效，将会发生恐慌。
+    pub fn trust(mut self, cidr: &str) -> Self {
+        self.trusted.push(cidr.parse().expect("invalid CIDR"));
+        self
+    }
+
+    /// 信任给定的网段。
+    pub fn trust_cidr(mut self, cidr: Cidr) -> Self {
+        self.trusted.push(cidr);
+        self
+    }
+
+    /// 设置读取转发信息的标头，默认为[`ForwardedHeader::XForwardedFor`]。
+    ///
+    /// 只会读取给定的标头，不会在它缺失时改为读取另一组标头。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use std::convert::Infallible;
+    ///
+    /// use puzz_core::service::{Service, ServiceExt};
+    /// use puzz_core::{service_fn, Request};
+    /// use puzz_middleware::forwarded::{forwarded, ClientIp, ForwardedHeader};
+    /// use puzz_server::PeerAddr;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let service = service_fn(|request: Request| async move {
+    ///     Ok::<_, Infallible>(request.extensions().get::<ClientIp>().unwrap().to_string())
+    /// });
+    /// let request = || {
+    ///     let mut request = Request::builder()
+    ///         .header("forwarded", "for=203.0.113.7")
+    ///         // 代理只设置了`Forwarded`标头，这个标头由客户端伪造。
+    ///         .header("x-forwarded-for", "1.2.3.4")
+    ///         .body(Default::default())
+    ///         .unwrap();
+    ///     request
+    ///         .extensions_mut()
+    ///         .insert(PeerAddr("10.0.0.1:4321".parse().unwrap()));
+    ///     request
+    /// };
+    ///
+    /// let service = service.with(
+    ///     forwarded()
+    ///         .trust("10.0.0.0/8")
+    ///         .header(ForwardedHeader::Forwarded),
+    /// );
+    /// assert_eq!(service.call(request()).await.unwrap(), "203.0.113.7");
+    /// # }
+    /// ```
+    pub fn header(mut self, header: ForwardedHeader) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// 设置是否将请求`Uri`的方案和主机改写为客户端看到的值，默认为`false`。
+    pub fn rewrite_uri(mut self, rewrite: bool) -> Self {
+        self.rewrite_uri = rewrite;
+        self
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|cidr| cidr.contains(ip))
+    }
+}
+
+impl<S> Wrap<S> for ForwardedWrap {
+    type Service = Forwarded<S>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        Forwarded {
+            inner: service,
+            wrap: self,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Forwarded<S> {
+    inner: S,
+    wrap: ForwardedWrap,
+}
+
+impl<S, B> Service<Request<B>> for Forwarded<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, mut request: Request<B>) -> Self::Future {
+        let peer = request
+            .extensions()
+            .get::<PeerAddr>()
+            .map(|peer| peer.0.ip().to_canonical());
+
+        let mut client = peer;
+        let mut scheme = request.uri().scheme().cloned();
+        let mut host = request
+            .headers()
+            .get(HOST)
+            .and_then(|host| Authority::try_from(host.as_bytes()).ok())
+            .or_else(|| request.uri().authority().cloned());
+
+        if let Some(peer) = peer.filter(|peer| self.wrap.is_trusted(*peer)) {
+            let hops = match self.wrap.header {
+                ForwardedHeader::Forwarded => forwarded_hops(request.headers()),
+                ForwardedHeader::XForwardedFor => x_forwarded_hops(request.headers()),
+            };
+
+            // 从右向左查找第一个不受信任的地址。
+            let index = hops
+                .iter()
+                .rposition(|hop| match hop.ip {
+                    Some(ip) => !self.wrap.is_trusted(ip),
+                    None => true,
+                })
+                .unwrap_or(0);
+
+            if let Some(hop) = hops.into_iter().nth(index) {
+                client = hop.ip.or(Some(peer));
+                scheme = hop.proto.or(scheme);
+                host = hop.host.or(host);
+            }
+        }
+
+        let scheme = scheme.unwrap_or(Scheme::HTTP);
+
+        if self.wrap.rewrite_uri {
+            if let Some(host) = &host {
+                let mut parts = std::mem::take(request.uri_mut()).into_parts();
+                parts.scheme = Some(scheme.clone());
+                parts.authority = Some(host.clone());
+                if parts.path_and_query.is_none() {
+                    parts.path_and_query = Some("/".parse().unwrap());
+                }
+                if let Ok(uri) = Uri::from_parts(parts) {
+                    *request.uri_mut() = uri;
+                }
+            }
+        }
+
+        let extensions = request.extensions_mut();
+        if let Some(client) = client {
+            extensions.insert(ClientIp(client));
+        }
+        extensions.insert(EffectiveOrigin { scheme, host });
+
+        self.inner.call(request)
+    }
+}
+
+/// 转发链中的一跳。
+#[derive(Debug, Default)]
+struct Hop {
+    ip: Option<IpAddr>,
+    proto: Option<Scheme>,
+    host: Option<Authority>,
+}
+
+/// 从`Forwarded`标头解析转发链。
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Hop> {
+    headers
+        .get_all(FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(parse_forwarded_element)
+        .collect()
+}
+
+/// 从`X-Forwarded-*`标头解析转发链。
+fn x_forwarded_hops(headers: &HeaderMap) -> Vec<Hop> {
+    let list = |name: &HeaderName| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .collect::<Vec<_>>()
+    };
+
+    let mut hops = list(&X_FORWARDED_FOR)
+        .into_iter()
+        .map(|value| Hop {
+            ip: parse_node(value),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    // `X-Forwarded-Proto`和`X-Forwarded-Host`与`X-Forwarded-For`一一对应时按位置匹配，
+    // 否则使用最后一个值（由最近的代理设置）。
+    let protos = list(&X_FORWARDED_PROTO);
+    let hosts = list(&X_FORWARDED_HOST);
+    let len = hops.len();
+
+    for (index, hop) in hops.iter_mut().enumerate() {
+        hop.proto = select(&protos, index, len).and_then(|proto| proto.parse().ok());
+        hop.host = select(&hosts, index, len).and_then(|host| host.parse().ok());
+    }
+
+    hops
+}
+
+fn select<'a>(values: &[&'a str], index: usize, len: usize) -> Option<&'a str> {
+    if values.len() == len {
+        values.get(index).copied()
+    } else {
+        values.last().copied()
+    }
+}
+
+fn parse_forwarded_element(element: &str) -> Hop {
+    let mut hop = Hop::default();
+
+    for pair in element.split(';') {
+        let (key, value) = match pair.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim().trim_matches('"')),
+            None => continue,
+        };
+
+        match key.to_ascii_lowercase().as_str() {
+            "for" => hop.ip = parse_node(value),
+            "proto" => hop.proto = value.parse().ok(),
+            "host" => hop.host = value.parse().ok(),
+            _ => {}
+        }
+    }
+
+    hop
+}
+
+/// 解析节点标识，例如`192.0.2.43`、`192.0.2.43:4711`或`[2001:db8::1]:4711`。
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip.to_canonical());
+    }
+
+    let host = match node.strip_prefix('[') {
+        Some(rest) => rest.split(']').next()?,
+        None => node.rsplit_once(':').map_or(node, |(host, _)| host),
+    };
+
+    host.parse::<IpAddr>().ok().map(|ip| ip.to_canonical())
+}
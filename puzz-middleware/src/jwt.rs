@@ -0,0 +1,872 @@
+//! JWT认证。
+//!
+//! [`jwt`]从`Authorization`标头（`Bearer`认证方式）或Cookie中提取令牌，验证签名和`exp`、`nbf`、`aud`、`iss`声明后，
+//! 将反序列化得到的声明以[`Authenticated`]的形式插入请求扩展。
+//!
+//! 支持`HS256`、`RS256`和`ES256`算法，密钥可以来自静态密钥、PEM或定期刷新的[`Jwks`]文档。
+
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_core::ready;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Header, Validation};
+use pin_project_lite::pin_project;
+use puzz_core::http::header::{AUTHORIZATION, CACHE_CONTROL, COOKIE, WWW_AUTHENTICATE};
+use puzz_core::http::marker::{MaybeSend, MaybeSendSync};
+use puzz_core::http::{HeaderValue, Method, StatusCode, Uri};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, ServiceExt, Wrap};
+use puzz_core::{BoxError, Request, Response};
+use serde::de::DeserializeOwned;
+use tokio::sync::Mutex;
+
+use crate::auth::Authenticated;
+use crate::{BoxFuture, MaybeArc};
+
+const SUPPORTED_ALGORITHMS: [Algorithm; 3] = [Algorithm::HS256, Algorithm::RS256, Algorithm::ES256];
+
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+// 令牌使用未知的密钥ID时，两次刷新之间的最小间隔。
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 创建一个验证JWT并将声明反序列化为`T`的[`Wrap`]。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use jsonwebtoken::{encode, EncodingKey, Header};
+/// use puzz_core::http::StatusCode;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::auth::Authenticated;
+/// use puzz_middleware::jwt::{jwt, Keys};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Claims {
+///     sub: String,
+///     exp: u64,
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|request: Request| async move {
+///     let claims = request.extensions().get::<Authenticated<Claims>>().unwrap();
+///     Ok::<_, Infallible>(format!("hello, {}!", claims.sub))
+/// })
+/// .with(jwt::<Claims>(Keys::hs256("secret")));
+///
+/// let claims = Claims {
+///     sub: "alice".to_owned(),
+///     exp: u64::MAX / 2,
+/// };
+/// let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(b"secret")).unwrap();
+///
+/// let request = Request::builder()
+///     .header("authorization", format!("Bearer {}", token))
+///     .body(Default::default())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::OK);
+///
+/// let response = service.call(Request::default()).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+/// # }
+/// ```
+pub fn jwt<T>(keys: impl Into<Keys>) -> JwtWrap<T> {
+    JwtWrap::new(keys.into())
+}
+
+/// 验证JWT签名的密钥。
+#[derive(Clone)]
+pub struct Keys {
+    kind: KeysKind,
+}
+
+#[derive(Clone)]
+enum KeysKind {
+    Static {
+        algorithm: Algorithm,
+        key: DecodingKey,
+    },
+    Jwks(MaybeArc<JwksInner>),
+}
+
+impl Keys {
+    /// 使用`HS256`算法和共享密钥。
+    pub fn hs256(secret: impl AsRef<[u8]>) -> Self {
+        Self::new(Algorithm::HS256, DecodingKey::from_secret(secret.as_ref()))
+    }
+
+    /// 使用`RS256`算法和PEM格式的RSA公钥。
+    pub fn rs256_pem(pem: impl AsRef<[u8]>) -> Result<Self, JwtError> {
+        DecodingKey::from_rsa_pem(pem.as_ref())
+            .map(|key| Self::new(Algorithm::RS256, key))
+            .map_err(JwtError::InvalidKey)
+    }
+
+    /// 使用`ES256`算法和PEM格式的ECDSA公钥。
+    pub fn es256_pem(pem: impl AsRef<[u8]>) -> Result<Self, JwtError> {
+        DecodingKey::from_ec_pem(pem.as_ref())
+            .map(|key| Self::new(Algorithm::ES256, key))
+            .map_err(JwtError::InvalidKey)
+    }
+
+    fn new(algorithm: Algorithm, key: DecodingKey) -> Self {
+        Self {
+            kind: KeysKind::Static { algorithm, key },
+        }
+    }
+}
+
+impl From<Jwks> for Keys {
+    fn from(jwks: Jwks) -> Self {
+        Self {
+            kind: KeysKind::Jwks(MaybeArc::new(JwksInner {
+                uri: jwks.uri,
+                fetcher: jwks.fetcher,
+                refresh_interval: jwks.refresh_interval,
+                cache: StdMutex::new(None),
+                failed: StdMutex::new(None),
+                refresh: Mutex::new(()),
+            })),
+        }
+    }
+}
+
+impl fmt::Debug for Keys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            KeysKind::Static { algorithm, .. } => f
+                .debug_struct("Keys")
+                .field("algorithm", algorithm)
+                .finish(),
+            KeysKind::Jwks(jwks) => f.debug_struct("Keys").field("jwks", &jwks.uri).finish(),
+        }
+    }
+}
+
+/// 从JWKS文档（RFC 7517）获取密钥。
+///
+/// 文档通过给定的服务以`GET`请求获取，首次使用时加载。文档的有效期取自响应的`Cache-Control: max-age`，
+/// 没有该指令时使用[刷新间隔](Jwks::refresh_interval)，过期后在下一次验证令牌时刷新。
+/// 当令牌使用未知的密钥ID时，也会尝试刷新文档。
+///
+/// 同一时间最多只有一个刷新请求，其他请求会等待并复用它的结果。刷新失败时继续使用过期的文档。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use jsonwebtoken::{encode, EncodingKey, Header};
+/// use puzz_core::http::StatusCode;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::jwt::{jwt, Jwks};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// // "c2VjcmV0"是"secret"的Base64URL编码。
+/// let jwks = service_fn(|_: Request| async {
+///     Ok::<_, Infallible>(r#"{"keys":[{"kty":"oct","kid":"k1","alg":"HS256","k":"c2VjcmV0"}]}"#)
+/// });
+///
+/// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+///     .with(jwt::<serde_json::Value>(Jwks::new("http://auth/jwks.json", jwks)));
+///
+/// let mut header = Header::default();
+/// header.kid = Some("k1".to_owned());
+/// let claims = serde_json::json!({ "sub": "alice", "exp": u64::MAX / 2 });
+/// let token = encode(&header, &claims, &EncodingKey::from_secret(b"secret")).unwrap();
+///
+/// let request = Request::builder()
+///     .header("authorization", format!("Bearer {}", token))
+///     .body(Default::default())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+pub struct Jwks {
+    uri: Uri,
+    fetcher: Fetcher,
+    refresh_interval: Duration,
+}
+
+#[cfg(not(feature = "send"))]
+type Fetcher = puzz_core::service::util::BoxService<Request, Response, BoxError>;
+#[cfg(feature = "send")]
+type Fetcher = puzz_core::service::util::SendBoxService<Request, Response, BoxError>;
+
+impl Jwks {
+    /// 使用`fetcher`服务从`uri`获取JWKS文档。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果`uri`不是有效的URI，将会发生恐慌。
+    pub fn new<U, S>(uri: U, fetcher: S) -> Self
+    where
+        U: TryInto<Uri>,
+        U::Error: fmt::Debug,
+        S: Service<Request> + MaybeSendSync + 'static,
+        S::Future: MaybeSend,
+        S::Response: IntoResponse,
+        S::Error: Into<BoxError>,
+    {
+        Self {
+            uri: uri.try_into().expect("invalid uri"),
+            fetcher: Fetcher::new(
+                fetcher
+                    .map_response(IntoResponse::into_response)
+                    .map_err(Into::into),
+            ),
+            refresh_interval: DEFAULT_REFRESH_INTERVAL,
+        }
+    }
+
+    /// 设置响应没有`Cache-Control: max-age`时JWKS文档的有效期，默认为`5`分钟。
+    pub fn refresh_interval(mut self, refresh_interval: Duration) -> Self {
+        self.refresh_interval = refresh_interval;
+        self
+    }
+}
+
+impl fmt::Debug for Jwks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jwks")
+            .field("uri", &self.uri)
+            .field("refresh_interval", &self.refresh_interval)
+            .finish()
+    }
+}
+
+struct JwksInner {
+    uri: Uri,
+    fetcher: Fetcher,
+    refresh_interval: Duration,
+    cache: StdMutex<Option<CachedJwks>>,
+    // 最近一次刷新失败的时间。
+    failed: StdMutex<Option<Instant>>,
+    // 保证同一时间最多只有一个刷新请求。
+    refresh: Mutex<()>,
+}
+
+#[derive(Clone)]
+struct CachedJwks {
+    fetched: Instant,
+    expires: Instant,
+    jwks: Arc<JwkSet>,
+}
+
+impl JwksInner {
+    async fn key(
+        self: MaybeArc<Self>,
+        algorithm: Algorithm,
+        kid: Option<String>,
+    ) -> Result<DecodingKey, JwtError> {
+        let cached = self.cache.lock().unwrap().clone();
+
+        let jwks = match cached {
+            Some(cached) if Instant::now() < cached.expires => {
+                let known = match &kid {
+                    Some(kid) => cached.jwks.find(kid).is_some(),
+                    None => true,
+                };
+                if known || cached.fetched.elapsed() < MIN_REFRESH_INTERVAL {
+                    cached.jwks
+                } else {
+                    self.refresh().await?
+                }
+            }
+            _ => self.refresh().await?,
+        };
+
+        let jwk = match &kid {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first(),
+        }
+        .ok_or(JwtError::UnknownKey)?;
+
+        // 防止使用与密钥不符的算法签名的令牌。
+        if let Some(key_algorithm) = jwk.common.key_algorithm {
+            if key_algorithm.to_string().parse::<Algorithm>().ok() != Some(algorithm) {
+                return Err(JwtError::UnknownKey);
+            }
+        }
+
+        DecodingKey::from_jwk(jwk).map_err(JwtError::InvalidKey)
+    }
+
+    async fn refresh(&self) -> Result<Arc<JwkSet>, JwtError> {
+        let waiting = Instant::now();
+        let _guard = self.refresh.lock().await;
+
+        let cached = self.cache.lock().unwrap().clone();
+
+        // 等待期间其他请求已经完成了刷新，直接复用它的结果。
+        if let Some(cached) = &cached {
+            if cached.fetched >= waiting {
+                return Ok(cached.jwks.clone());
+            }
+        }
+        if let Some(failed) = *self.failed.lock().unwrap() {
+            if failed >= waiting {
+                return match cached {
+                    Some(cached) => Ok(cached.jwks),
+                    None => Err(JwtError::Jwks("failed to refresh jwks".into())),
+                };
+            }
+        }
+
+        match self.fetch().await {
+            Ok(jwks) => Ok(jwks),
+            Err(e) => {
+                *self.failed.lock().unwrap() = Some(Instant::now());
+                match cached {
+                    Some(cached) => Ok(cached.jwks),
+                    None => Err(e),
+                }
+            }
+        }
+    }
+
+    async fn fetch(&self) -> Result<Arc<JwkSet>, JwtError> {
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(self.uri.clone())
+            .body(Default::default())
+            .expect("invalid request");
+
+        let response = self.fetcher.call(request).await.map_err(JwtError::Jwks)?;
+
+        if !response.status().is_success() {
+            return Err(JwtError::Jwks(
+                format!("unexpected status code `{}`", response.status()).into(),
+            ));
+        }
+
+        let max_age = max_age(response.headers().get(CACHE_CONTROL));
+
+        let bytes = crate::body::to_bytes(response.into_body())
+            .await
+            .map_err(JwtError::Jwks)?;
+
+        let jwks = Arc::new(
+            serde_json::from_slice::<JwkSet>(&bytes).map_err(|e| JwtError::Jwks(e.into()))?,
+        );
+
+        let fetched = Instant::now();
+        let ttl = max_age
+            .map(|max_age| max_age.max(MIN_REFRESH_INTERVAL))
+            .unwrap_or(self.refresh_interval);
+        *self.cache.lock().unwrap() = Some(CachedJwks {
+            fetched,
+            expires: fetched + ttl,
+            jwks: jwks.clone(),
+        });
+
+        Ok(jwks)
+    }
+}
+
+// 解析`Cache-Control`标头中的`max-age`指令。
+fn max_age(value: Option<&HeaderValue>) -> Option<Duration> {
+    value?
+        .to_str()
+        .ok()?
+        .split(',')
+        .find_map(|directive| {
+            let (name, value) = directive.trim().split_once('=')?;
+            if name.trim().eq_ignore_ascii_case("max-age") {
+                value.trim().trim_matches('"').parse().ok()
+            } else {
+                None
+            }
+        })
+        .map(Duration::from_secs)
+}
+
+/// 验证JWT时产生的错误。
+#[derive(Debug)]
+pub enum JwtError {
+    /// 请求未携带令牌。
+    MissingToken,
+    /// 令牌无效，例如签名错误或已过期。
+    InvalidToken(jsonwebtoken::errors::Error),
+    /// 找不到与令牌匹配的密钥。
+    UnknownKey,
+    /// 密钥无效。
+    InvalidKey(jsonwebtoken::errors::Error),
+    /// 获取JWKS文档失败。
+    Jwks(BoxError),
+}
+
+impl fmt::Display for JwtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JwtError::MissingToken => f.write_str("missing token"),
+            JwtError::InvalidToken(e) => write!(f, "invalid token: {}", e),
+            JwtError::UnknownKey => f.write_str("no matching key found"),
+            JwtError::InvalidKey(e) => write!(f, "invalid key: {}", e),
+            JwtError::Jwks(e) => write!(f, "failed to fetch jwks: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for JwtError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JwtError::InvalidToken(e) | JwtError::InvalidKey(e) => Some(e),
+            JwtError::Jwks(e) => Some(&**e),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for JwtError {
+    fn into_response(self) -> Response {
+        let (status, challenge) = match self {
+            JwtError::MissingToken => (StatusCode::UNAUTHORIZED, r#"Bearer"#),
+            JwtError::InvalidToken(_) | JwtError::UnknownKey => {
+                (StatusCode::UNAUTHORIZED, r#"Bearer error="invalid_token""#)
+            }
+            JwtError::InvalidKey(_) | JwtError::Jwks(_) => {
+                return StatusCode::SERVICE_UNAVAILABLE.into_response()
+            }
+        };
+
+        let mut response = status.into_response();
+        response
+            .headers_mut()
+            .insert(WWW_AUTHENTICATE, HeaderValue::from_static(challenge));
+        response
+    }
+}
+
+pub struct JwtWrap<T> {
+    keys: Keys,
+    validation: Validation,
+    cookie: Option<String>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> JwtWrap<T> {
+    pub fn new(keys: Keys) -> Self {
+        let mut validation = Validation::default();
+        validation.validate_nbf = true;
+        validation.validate_aud = false;
+
+        Self {
+            keys,
+            validation,
+            cookie: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// 设置允许的`aud`声明，令牌必须包含`aud`声明，并且包含其中之一。
+    ///
+    /// 默认不验证`aud`声明。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use std::convert::Infallible;
+    ///
+    /// use jsonwebtoken::{encode, EncodingKey, Header};
+    /// use puzz_core::http::StatusCode;
+    /// use puzz_core::service::{Service, ServiceExt};
+    /// use puzz_core::{service_fn, Request};
+    /// use puzz_middleware::jwt::{jwt, Keys};
+    /// use serde_json::{json, Value};
+    ///
+    /// fn request(claims: Value) -> Request {
+    ///     let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(b"secret")).unwrap();
+    ///     Request::builder()
+    ///         .header("authorization", format!("Bearer {}", token))
+    ///         .body(Default::default())
+    ///         .unwrap()
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+    ///     .with(jwt::<Value>(Keys::hs256("secret")).audience(["api"]));
+    ///
+    /// let response = service.call(request(json!({ "aud": "api", "exp": u64::MAX / 2 }))).await;
+    /// assert_eq!(response.unwrap().status(), StatusCode::OK);
+    ///
+    /// let response = service.call(request(json!({ "aud": "other", "exp": u64::MAX / 2 }))).await;
+    /// assert_eq!(response.unwrap().status(), StatusCode::UNAUTHORIZED);
+    ///
+    /// let response = service.call(request(json!({ "exp": u64::MAX / 2 }))).await;
+    /// assert_eq!(response.unwrap().status(), StatusCode::UNAUTHORIZED);
+    /// # }
+    /// ```
+    pub fn audience<I>(mut self, audience: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        let audience = audience.into_iter().collect::<Vec<_>>();
+        self.validation.set_audience(&audience);
+        self.validation.validate_aud = true;
+        self.validation
+            .required_spec_claims
+            .insert("aud".to_owned());
+        self
+    }
+
+    /// 设置允许的`iss`声明，令牌必须包含`iss`声明，并且是其中之一。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use std::convert::Infallible;
+    ///
+    /// use jsonwebtoken::{encode, EncodingKey, Header};
+    /// use puzz_core::http::StatusCode;
+    /// use puzz_core::service::{Service, ServiceExt};
+    /// use puzz_core::{service_fn, Request};
+    /// use puzz_middleware::jwt::{jwt, Keys};
+    /// use serde_json::{json, Value};
+    ///
+    /// fn request(claims: Value) -> Request {
+    ///     let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(b"secret")).unwrap();
+    ///     Request::builder()
+    ///         .header("authorization", format!("Bearer {}", token))
+    ///         .body(Default::default())
+    ///         .unwrap()
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+    ///     .with(jwt::<Value>(Keys::hs256("secret")).issuer(["https://auth.example.com"]));
+    ///
+    /// let claims = json!({ "iss": "https://auth.example.com", "exp": u64::MAX / 2 });
+    /// let response = service.call(request(claims)).await;
+    /// assert_eq!(response.unwrap().status(), StatusCode::OK);
+    ///
+    /// let claims = json!({ "iss": "https://evil.example.com", "exp": u64::MAX / 2 });
+    /// let response = service.call(request(claims)).await;
+    /// assert_eq!(response.unwrap().status(), StatusCode::UNAUTHORIZED);
+    ///
+    /// let response = service.call(request(json!({ "exp": u64::MAX / 2 }))).await;
+    /// assert_eq!(response.unwrap().status(), StatusCode::UNAUTHORIZED);
+    /// # }
+    /// ```
+    pub fn issuer<I>(mut self, issuer: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToString,
+    {
+        let issuer = issuer.into_iter().collect::<Vec<_>>();
+        self.validation.set_issuer(&issuer);
+        self.validation
+            .required_spec_claims
+            .insert("iss".to_owned());
+        self
+    }
+
+    /// 设置验证`exp`和`nbf`声明时允许的时钟偏差，默认为`60`秒。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use std::convert::Infallible;
+    /// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    ///
+    /// use jsonwebtoken::{encode, EncodingKey, Header};
+    /// use puzz_core::http::StatusCode;
+    /// use puzz_core::service::{Service, ServiceExt};
+    /// use puzz_core::{service_fn, Request};
+    /// use puzz_middleware::jwt::{jwt, Keys};
+    /// use serde_json::{json, Value};
+    ///
+    /// fn request(claims: Value) -> Request {
+    ///     let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(b"secret")).unwrap();
+    ///     Request::builder()
+    ///         .header("authorization", format!("Bearer {}", token))
+    ///         .body(Default::default())
+    ///         .unwrap()
+    /// }
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    /// // 令牌在30秒后才生效。
+    /// let claims = json!({ "nbf": now + 30, "exp": now + 3600 });
+    ///
+    /// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+    ///     .with(jwt::<Value>(Keys::hs256("secret")));
+    /// let response = service.call(request(claims.clone())).await;
+    /// assert_eq!(response.unwrap().status(), StatusCode::OK);
+    ///
+    /// let strict = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+    ///     .with(jwt::<Value>(Keys::hs256("secret")).leeway(Duration::ZERO));
+    /// let response = strict.call(request(claims)).await;
+    /// assert_eq!(response.unwrap().status(), StatusCode::UNAUTHORIZED);
+    /// # }
+    /// ```
+    pub fn leeway(mut self, leeway: Duration) -> Self {
+        self.validation.leeway = leeway.as_secs();
+        self
+    }
+
+    /// 当`Authorization`标头中没有令牌时，从给定名称的Cookie中提取令牌。
+    pub fn cookie<N>(mut self, name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        self.cookie = Some(name.into());
+        self
+    }
+
+    fn token<B>(&self, request: &Request<B>) -> Option<String> {
+        let bearer = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Bearer"))
+            .map(|(_, token)| token.trim());
+
+        if let Some(token) = bearer {
+            return Some(token.to_owned());
+        }
+
+        let name = self.cookie.as_deref()?;
+        request
+            .headers()
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .filter_map(|pair| pair.trim().split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, token)| token.trim_matches('"').to_owned())
+    }
+}
+
+impl<T> Clone for JwtWrap<T> {
+    fn clone(&self) -> Self {
+        Self {
+            keys: self.keys.clone(),
+            validation: self.validation.clone(),
+            cookie: self.cookie.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<S, T> Wrap<S> for JwtWrap<T> {
+    type Service = Jwt<S, T>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        Jwt {
+            inner: Arc::new(service),
+            wrap: Arc::new(self),
+        }
+    }
+}
+
+impl<T> fmt::Debug for JwtWrap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtWrap")
+            .field("keys", &self.keys)
+            .field("validation", &self.validation)
+            .field("cookie", &self.cookie)
+            .finish()
+    }
+}
+
+pub struct Jwt<S, T> {
+    // 获取密钥后才会调用服务。
+    inner: Arc<S>,
+    wrap: Arc<JwtWrap<T>>,
+}
+
+impl<S, T> Clone for Jwt<S, T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            wrap: self.wrap.clone(),
+        }
+    }
+}
+
+impl<S, T, B> Service<Request<B>> for Jwt<S, T>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+    T: DeserializeOwned + MaybeSendSync + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = JwtFuture<S, Request<B>, T>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Request<B>) -> Self::Future {
+        let token = match self.wrap.token(&request) {
+            Some(token) => token,
+            None => return JwtFuture::rejected(JwtError::MissingToken),
+        };
+
+        let header = match jsonwebtoken::decode_header(&token) {
+            Ok(header) if SUPPORTED_ALGORITHMS.contains(&header.alg) => header,
+            Ok(_) => return JwtFuture::rejected(JwtError::UnknownKey),
+            Err(e) => return JwtFuture::rejected(JwtError::InvalidToken(e)),
+        };
+
+        match &self.wrap.keys.kind {
+            KeysKind::Static { algorithm, key } => {
+                if header.alg != *algorithm {
+                    return JwtFuture::rejected(JwtError::UnknownKey);
+                }
+                match decode::<T, _>(&token, &header, key, &self.wrap) {
+                    Ok(claims) => JwtFuture::call(&self.inner, request, claims),
+                    Err(e) => JwtFuture::rejected(e),
+                }
+            }
+            KeysKind::Jwks(jwks) => JwtFuture::Resolving {
+                fut: Box::pin(jwks.clone().key(header.alg, header.kid.clone())),
+                state: Some((
+                    self.inner.clone(),
+                    request,
+                    token,
+                    header,
+                    self.wrap.clone(),
+                )),
+            },
+        }
+    }
+}
+
+impl<S, T> fmt::Debug for Jwt<S, T>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jwt")
+            .field("inner", &self.inner)
+            .field("wrap", &self.wrap)
+            .finish()
+    }
+}
+
+fn decode<T, W>(
+    token: &str,
+    header: &Header,
+    key: &DecodingKey,
+    wrap: &JwtWrap<W>,
+) -> Result<T, JwtError>
+where
+    T: DeserializeOwned,
+{
+    let mut validation = wrap.validation.clone();
+    validation.algorithms = vec![header.alg];
+
+    jsonwebtoken::decode::<T>(token, key, &validation)
+        .map(|data| data.claims)
+        .map_err(JwtError::InvalidToken)
+}
+
+pin_project! {
+    #[project = JwtFutureProj]
+    pub enum JwtFuture<S, Req, T>
+    where
+        S: Service<Req>,
+    {
+        Resolving {
+            fut: BoxFuture<Result<DecodingKey, JwtError>>,
+            state: Option<(Arc<S>, Req, String, Header, Arc<JwtWrap<T>>)>,
+        },
+        Calling {
+            #[pin]
+            fut: S::Future,
+        },
+        Rejected {
+            error: Option<JwtError>,
+        },
+    }
+}
+
+impl<S, Req, T> JwtFuture<S, Req, T>
+where
+    S: Service<Req>,
+{
+    fn rejected(error: JwtError) -> Self {
+        Self::Rejected { error: Some(error) }
+    }
+}
+
+impl<S, B, T> JwtFuture<S, Request<B>, T>
+where
+    S: Service<Request<B>>,
+    T: MaybeSendSync + 'static,
+{
+    fn call(inner: &S, mut request: Request<B>, claims: T) -> Self {
+        request.extensions_mut().insert(Authenticated(claims));
+        Self::Calling {
+            fut: inner.call(request),
+        }
+    }
+}
+
+impl<S, B, T> Future for JwtFuture<S, Request<B>, T>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+    T: DeserializeOwned + MaybeSendSync + 'static,
+{
+    type Output = Result<Response, S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                JwtFutureProj::Resolving { fut, state } => {
+                    let key = ready!(fut.as_mut().poll(cx));
+                    let (inner, request, token, header, wrap) =
+                        state.take().expect("polled after completion");
+
+                    let next =
+                        match key.and_then(|key| decode::<T, _>(&token, &header, &key, &wrap)) {
+                            Ok(claims) => JwtFuture::call(&*inner, request, claims),
+                            Err(e) => JwtFuture::rejected(e),
+                        };
+                    self.set(next);
+                }
+                JwtFutureProj::Calling { fut } => {
+                    return fut.poll(cx).map_ok(IntoResponse::into_response);
+                }
+                JwtFutureProj::Rejected { error } => {
+                    let error = error.take().expect("polled after completion");
+                    return Poll::Ready(Ok(error.into_response()));
+                }
+            }
+        }
+    }
+}
+
+impl<S, Req, T> fmt::Debug for JwtFuture<S, Req, T>
+where
+    S: Service<Req>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtFuture").finish()
+    }
+}
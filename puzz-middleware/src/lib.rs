@@ -1,4 +1,84 @@
 #![forbid(unsafe_code)]
 
+// 启用`send`特性时，中间件内部装箱的`Future`也需要实现`Send`，以便挂载到路由上。
+#[cfg(not(feature = "send"))]
+#[allow(dead_code)]
+type BoxFuture<T> = puzz_core::service::util::BoxFuture<T>;
+#[cfg(feature = "send")]
+#[allow(dead_code)]
+type BoxFuture<T> = puzz_core::service::util::SendBoxFuture<T>;
+
+// 共享可能未实现`Send`的值（例如用户提供的闭包）时使用，未启用`send`特性时不需要原子引用计数。
+#[cfg(not(feature = "send"))]
+#[allow(dead_code)]
+type MaybeArc<T> = std::rc::Rc<T>;
+#[cfg(feature = "send")]
+#[allow(dead_code)]
+type MaybeArc<T> = std::sync::Arc<T>;
+
+#[cfg(feature = "auth")]
+pub mod auth;
+
+#[cfg(feature = "buffer")]
+pub mod buffer;
+
+#[cfg(feature = "cache")]
+pub mod cache;
+
+#[cfg(feature = "catch-panic")]
+pub mod catch_panic;
+
+#[cfg(feature = "circuit-breaker")]
+pub mod circuit_breaker;
+
+#[cfg(feature = "conditional")]
+pub mod conditional;
+
 #[cfg(feature = "core")]
 pub mod core;
+
+#[cfg(feature = "csrf")]
+pub mod csrf;
+
+#[cfg(feature = "forwarded")]
+pub mod forwarded;
+
+#[cfg(feature = "jwt")]
+pub mod jwt;
+
+#[cfg(feature = "limit")]
+pub mod limit;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+#[cfg(feature = "rate-limit")]
+pub mod rate_limit;
+
+#[cfg(feature = "request-id")]
+pub mod request_id;
+
+#[cfg(feature = "retry")]
+pub mod retry;
+
+#[cfg(feature = "security-headers")]
+pub mod security_headers;
+
+#[cfg(feature = "session")]
+pub mod session;
+
+#[cfg(feature = "trace")]
+pub mod trace;
+
+#[cfg(any(
+    feature = "cache",
+    feature = "conditional",
+    feature = "jwt",
+    feature = "metrics",
+    feature = "retry",
+    feature = "trace"
+))]
+mod body;
+
+#[cfg(any(feature = "buffer", feature = "cache"))]
+mod task;
@@ -0,0 +1,523 @@
+//! 并发限制与负载卸除。
+//!
+//! [`concurrency_limit`]限制同时处理的请求数，超出限制的请求会排队等待，等待超时后返回
+//! `503 Service Unavailable`。[`load_shed`]在达到限制时不再排队，而是立即返回`503`。
+//! [`adaptive_concurrency_limit`]根据请求的延迟使用AIMD（加性增、乘性减）算法动态调整限制。
+//!
+//! 返回的`503`响应都会携带`Retry-After`标头。
+//!
+//! 调用方在调用服务之前使用`poll_ready`时，服务会预留一个许可，没有可用的许可时返回`Poll::Pending`，
+//! 并在许可释放后唤醒任务，从而向调用方施加背压。预留的许可由随后的`call`使用。
+//! 路由和服务器不会调用`poll_ready`，此时在`call`中获取许可，并按上述规则排队或返回`503`。
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_core::ready;
+use pin_project_lite::pin_project;
+use puzz_core::http::{header, HeaderValue, StatusCode};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::Response;
+use tokio::sync::{AcquireError, OwnedSemaphorePermit, Semaphore};
+use tokio::time::Sleep;
+
+use crate::BoxFuture;
+
+/// 默认的`Retry-After`时间。
+pub const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// 创建一个限制同时处理的请求数的[`Wrap`]。
+///
+/// 超出限制的请求会排队等待，默认无限期等待，可以使用[`ConcurrencyLimitWrap::max_wait`]设置最长等待时间。
+///
+/// 同一个[`ConcurrencyLimitWrap`]（及其克隆）包裹的所有服务共享同一个限制。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+/// use std::time::Duration;
+///
+/// use puzz_core::service::ServiceExt;
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::limit::concurrency_limit;
+///
+/// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+///     .with(concurrency_limit(64).max_wait(Duration::from_secs(5)));
+/// ```
+pub fn concurrency_limit(max: usize) -> ConcurrencyLimitWrap {
+    ConcurrencyLimitWrap::new(Limiter::fixed(max))
+}
+
+/// 创建一个在达到并发限制时立即返回`503 Service Unavailable`的[`Wrap`]。
+///
+/// 这等同于`concurrency_limit(max).max_wait(Duration::ZERO)`。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+/// use std::future::{pending, poll_fn};
+/// use std::task::Poll;
+///
+/// use puzz_core::http::{header, StatusCode};
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::limit::load_shed;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|_: Request| async {
+///     pending::<()>().await;
+///     Ok::<_, Infallible>("hi!")
+/// })
+/// .with(load_shed(1));
+///
+/// // 第一个请求占用了唯一的名额。
+/// let first = service.call(Request::default());
+///
+/// let response = service.call(Request::default()).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+/// assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+///
+/// // 使用`poll_ready`的调用方会等待名额释放。
+/// let other = service.clone();
+/// assert!(poll_fn(|cx| Poll::Ready(other.poll_ready(cx).is_pending())).await);
+/// drop(first);
+/// poll_fn(|cx| other.poll_ready(cx)).await.unwrap();
+/// # }
+/// ```
+pub fn load_shed(max: usize) -> ConcurrencyLimitWrap {
+    concurrency_limit(max).max_wait(Duration::ZERO)
+}
+
+/// 创建一个使用AIMD算法动态调整并发限制的[`Wrap`]。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+/// use std::time::Duration;
+///
+/// use puzz_core::service::ServiceExt;
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::limit::{adaptive_concurrency_limit, Aimd};
+///
+/// let aimd = Aimd::new(16)
+///     .min_limit(4)
+///     .max_limit(256)
+///     .latency_threshold(Duration::from_millis(200));
+///
+/// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+///     .with(adaptive_concurrency_limit(aimd));
+/// ```
+pub fn adaptive_concurrency_limit(aimd: Aimd) -> ConcurrencyLimitWrap {
+    ConcurrencyLimitWrap::new(Limiter::adaptive(aimd))
+}
+
+/// AIMD（加性增、乘性减）算法的参数。
+///
+/// 请求成功且延迟不超过阈值时，每完成约“当前限制”个请求，限制加一；
+/// 请求失败（服务返回错误或`5xx`响应）或延迟超过阈值时，限制乘以减少因子。
+#[derive(Debug, Clone, Copy)]
+pub struct Aimd {
+    initial_limit: usize,
+    min_limit: usize,
+    max_limit: usize,
+    latency_threshold: Duration,
+    decrease_factor: f64,
+}
+
+impl Aimd {
+    /// 使用给定的初始限制创建AIMD参数。
+    pub fn new(initial_limit: usize) -> Self {
+        Self {
+            initial_limit,
+            min_limit: 1,
+            max_limit: 1000,
+            latency_threshold: Duration::from_secs(1),
+            decrease_factor: 0.9,
+        }
+    }
+
+    /// 设置最小限制，默认为`1`。
+    pub fn min_limit(mut self, min_limit: usize) -> Self {
+        self.min_limit = min_limit;
+        self
+    }
+
+    /// 设置最大限制，默认为`1000`。
+    pub fn max_limit(mut self, max_limit: usize) -> Self {
+        self.max_limit = max_limit;
+        self
+    }
+
+    /// 设置延迟阈值，默认为`1`秒。
+    pub fn latency_threshold(mut self, latency_threshold: Duration) -> Self {
+        self.latency_threshold = latency_threshold;
+        self
+    }
+
+    /// 设置减少因子，默认为`0.9`。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果减少因子不在`(0, 1)`范围内，将会发生恐慌。
+    pub fn decrease_factor(mut self, decrease_factor: f64) -> Self {
+        if !(decrease_factor > 0.0 && decrease_factor < 1.0) {
+            panic!("Decrease factor must be in the range (0, 1)");
+        }
+        self.decrease_factor = decrease_factor;
+        self
+    }
+}
+
+struct Limiter {
+    semaphore: Arc<Semaphore>,
+    aimd: Option<(Aimd, Mutex<AimdState>)>,
+}
+
+struct AimdState {
+    limit: usize,
+    // 累计的增量，达到`1`时限制加一。
+    credit: f64,
+    // 需要回收的许可数量，用于减小限制。
+    to_forget: usize,
+}
+
+impl Limiter {
+    fn fixed(max: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max)),
+            aimd: None,
+        }
+    }
+
+    fn adaptive(aimd: Aimd) -> Self {
+        let limit = aimd.initial_limit.clamp(aimd.min_limit, aimd.max_limit);
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            aimd: Some((
+                aimd,
+                Mutex::new(AimdState {
+                    limit,
+                    credit: 0.0,
+                    to_forget: 0,
+                }),
+            )),
+        }
+    }
+
+    fn release(&self, permit: OwnedSemaphorePermit, latency: Duration, success: bool) {
+        let (aimd, state) = match &self.aimd {
+            Some(aimd) => aimd,
+            None => return,
+        };
+        let mut state = state.lock().unwrap();
+
+        if success && latency <= aimd.latency_threshold {
+            state.credit += 1.0 / state.limit as f64;
+            if state.credit >= 1.0 {
+                state.credit -= 1.0;
+                if state.limit < aimd.max_limit {
+                    state.limit += 1;
+                    if state.to_forget > 0 {
+                        state.to_forget -= 1;
+                    } else {
+                        self.semaphore.add_permits(1);
+                    }
+                }
+            }
+        } else {
+            let limit = ((state.limit as f64 * aimd.decrease_factor) as usize).max(aimd.min_limit);
+            state.to_forget += state.limit - limit;
+            state.limit = limit;
+            state.credit = 0.0;
+        }
+
+        if state.to_forget > 0 {
+            state.to_forget -= 1;
+            permit.forget();
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ConcurrencyLimitWrap {
+    limiter: Arc<Limiter>,
+    max_wait: Option<Duration>,
+    retry_after: Duration,
+}
+
+impl ConcurrencyLimitWrap {
+    fn new(limiter: Limiter) -> Self {
+        Self {
+            limiter: Arc::new(limiter),
+            max_wait: None,
+            retry_after: DEFAULT_RETRY_AFTER,
+        }
+    }
+
+    /// 设置请求排队等待的最长时间，默认无限期等待。
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+
+    /// 设置`503`响应中`Retry-After`标头的时间，默认为[`DEFAULT_RETRY_AFTER`]。
+    pub fn retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+}
+
+impl<S> Wrap<S> for ConcurrencyLimitWrap {
+    type Service = ConcurrencyLimit<S>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        ConcurrencyLimit {
+            inner: Arc::new(service),
+            limiter: self.limiter,
+            max_wait: self.max_wait,
+            retry_after: self.retry_after,
+            reserved: Mutex::default(),
+        }
+    }
+}
+
+impl fmt::Debug for ConcurrencyLimitWrap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrencyLimitWrap")
+            .field("max_wait", &self.max_wait)
+            .field("retry_after", &self.retry_after)
+            .finish()
+    }
+}
+
+pub struct ConcurrencyLimit<S> {
+    // 等待许可时需要在调用返回后继续持有服务。
+    inner: Arc<S>,
+    limiter: Arc<Limiter>,
+    max_wait: Option<Duration>,
+    retry_after: Duration,
+    // `poll_ready`预留的许可，每个克隆各自持有。
+    reserved: Mutex<Reserved>,
+}
+
+#[derive(Default)]
+struct Reserved {
+    permit: Option<OwnedSemaphorePermit>,
+    acquire: Option<BoxFuture<Result<OwnedSemaphorePermit, AcquireError>>>,
+}
+
+impl<S> Clone for ConcurrencyLimit<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            limiter: self.limiter.clone(),
+            max_wait: self.max_wait,
+            retry_after: self.retry_after,
+            reserved: Mutex::default(),
+        }
+    }
+}
+
+impl<S, Req> Service<Req> for ConcurrencyLimit<S>
+where
+    S: Service<Req>,
+    S::Response: IntoResponse,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = ConcurrencyLimitFuture<S, Req, S::Future>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        {
+            let mut reserved = self.reserved.lock().unwrap();
+            if reserved.permit.is_none() {
+                let semaphore = &self.limiter.semaphore;
+                let acquire = reserved
+                    .acquire
+                    .get_or_insert_with(|| Box::pin(semaphore.clone().acquire_owned()));
+
+                // 信号量不会被关闭，获取失败时交给`call`处理。
+                if let Ok(permit) = ready!(acquire.as_mut().poll(cx)) {
+                    reserved.permit = Some(permit);
+                }
+                reserved.acquire = None;
+            }
+        }
+
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Req) -> Self::Future {
+        let semaphore = &self.limiter.semaphore;
+        let reserved = self.reserved.lock().unwrap().permit.take();
+
+        if let Some(permit) = reserved.or_else(|| semaphore.clone().try_acquire_owned().ok()) {
+            return ConcurrencyLimitFuture::Running {
+                fut: self.inner.call(request),
+                permit: Some(Permit {
+                    permit,
+                    limiter: self.limiter.clone(),
+                    start: Instant::now(),
+                }),
+            };
+        }
+
+        if self.max_wait == Some(Duration::ZERO) {
+            return ConcurrencyLimitFuture::Rejected {
+                retry_after: self.retry_after,
+            };
+        }
+
+        ConcurrencyLimitFuture::Waiting {
+            acquire: Box::pin(semaphore.clone().acquire_owned()),
+            sleep: self.max_wait.map(tokio::time::sleep),
+            state: Some(Waiting {
+                inner: self.inner.clone(),
+                request,
+                limiter: self.limiter.clone(),
+                retry_after: self.retry_after,
+            }),
+        }
+    }
+}
+
+impl<S> fmt::Debug for ConcurrencyLimit<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrencyLimit")
+            .field("inner", &self.inner)
+            .field("max_wait", &self.max_wait)
+            .field("retry_after", &self.retry_after)
+            .finish()
+    }
+}
+
+struct Permit {
+    permit: OwnedSemaphorePermit,
+    limiter: Arc<Limiter>,
+    start: Instant,
+}
+
+struct Waiting<S, Req> {
+    inner: Arc<S>,
+    request: Req,
+    limiter: Arc<Limiter>,
+    retry_after: Duration,
+}
+
+pin_project! {
+    #[project = ConcurrencyLimitFutureProj]
+    pub enum ConcurrencyLimitFuture<S, Req, Fut> {
+        Waiting {
+            acquire: BoxFuture<Result<OwnedSemaphorePermit, AcquireError>>,
+            #[pin]
+            sleep: Option<Sleep>,
+            state: Option<Waiting<S, Req>>,
+        },
+        Running {
+            #[pin]
+            fut: Fut,
+            permit: Option<Permit>,
+        },
+        Rejected {
+            retry_after: Duration,
+        },
+    }
+}
+
+impl<S, Req, Res, Err> Future for ConcurrencyLimitFuture<S, Req, S::Future>
+where
+    S: Service<Req, Response = Res, Error = Err>,
+    Res: IntoResponse,
+{
+    type Output = Result<Response, Err>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                ConcurrencyLimitFutureProj::Waiting {
+                    acquire,
+                    sleep,
+                    state,
+                } => {
+                    let permit = match acquire.as_mut().poll(cx) {
+                        Poll::Ready(Ok(permit)) => permit,
+                        Poll::Ready(Err(_)) => {
+                            let retry_after = state.as_ref().unwrap().retry_after;
+                            self.set(ConcurrencyLimitFuture::Rejected { retry_after });
+                            continue;
+                        }
+                        Poll::Pending => {
+                            if let Some(sleep) = sleep.as_pin_mut() {
+                                ready!(sleep.poll(cx));
+                                let retry_after = state.as_ref().unwrap().retry_after;
+                                self.set(ConcurrencyLimitFuture::Rejected { retry_after });
+                                continue;
+                            }
+                            return Poll::Pending;
+                        }
+                    };
+
+                    let Waiting {
+                        inner,
+                        request,
+                        limiter,
+                        ..
+                    } = state.take().expect("polled after completion");
+
+                    self.set(ConcurrencyLimitFuture::Running {
+                        fut: inner.call(request),
+                        permit: Some(Permit {
+                            permit,
+                            limiter,
+                            start: Instant::now(),
+                        }),
+                    });
+                }
+                ConcurrencyLimitFutureProj::Running { fut, permit } => {
+                    let result = ready!(fut.poll(cx)).map(IntoResponse::into_response);
+                    let Permit {
+                        permit,
+                        limiter,
+                        start,
+                    } = permit.take().expect("polled after completion");
+
+                    let success =
+                        matches!(&result, Ok(response) if !response.status().is_server_error());
+                    limiter.release(permit, start.elapsed(), success);
+
+                    return Poll::Ready(result);
+                }
+                ConcurrencyLimitFutureProj::Rejected { retry_after } => {
+                    return Poll::Ready(Ok(service_unavailable(*retry_after)));
+                }
+            }
+        }
+    }
+}
+
+impl<S, Req, Fut> fmt::Debug for ConcurrencyLimitFuture<S, Req, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrencyLimitFuture").finish()
+    }
+}
+
+fn service_unavailable(retry_after: Duration) -> Response {
+    let mut response = StatusCode::SERVICE_UNAVAILABLE.into_response();
+    response.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(retry_after.as_secs().max(1)),
+    );
+    response
+}
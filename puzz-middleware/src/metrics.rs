@@ -0,0 +1,447 @@
+//! Prometheus指标。
+//!
+//! [`metrics`]记录HTTP请求的RED指标（请求速率、错误和耗时），[`Registry::exporter`]
+//! 返回一个以Prometheus文本格式输出这些指标的服务，可以直接挂载到路由器上。
+//!
+//! 记录的指标如下：
+//!
+//! - `http_requests_total`：请求总数（计数器）
+//! - `http_request_duration_seconds`：从收到请求到响应正文发送完毕的秒数（直方图）
+//! - `http_requests_in_flight`：正在处理的请求数（仪表）
+//!
+//! 前两个指标使用`method`、`status`和`route`标签，其中`status`为状态码的类别（例如`2xx`），
+//! `route`为路由器匹配到的路由（见[`MatchedPath`]），而不是请求的原始路径，以免标签基数过大。
+//! 服务返回错误时`status`为`error`，没有匹配到路由时`route`为`unmatched`。
+//! `http_requests_in_flight`只使用`method`标签。
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::{self, Write as _};
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use futures_core::ready;
+use pin_project_lite::pin_project;
+use puzz_core::body::BodyExt;
+use puzz_core::http::{header, HeaderValue, Method, StatusCode};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::{Request, Response};
+use puzz_route::MatchedPath;
+
+use crate::body::OnEndBody;
+
+/// 直方图默认的桶。
+pub const DEFAULT_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// 创建一个将指标记录到`registry`的[`Wrap`]。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::metrics::{metrics, Registry};
+/// use puzz_route::Router;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let registry = Registry::new();
+///
+/// let service = Router::new()
+///     .route("/hi", service_fn(|_| async { Ok::<_, Infallible>("hi!") }))
+///     .route("/metrics", registry.exporter())
+///     .with(metrics(registry.clone()));
+///
+/// service.call(Request::builder().uri("/hi").body(Default::default()).unwrap()).await.unwrap();
+///
+/// assert!(registry
+///     .render()
+///     .contains(r#"http_requests_total{method="GET",route="/hi",status="2xx"} 1"#));
+/// # }
+/// ```
+pub fn metrics(registry: Registry) -> MetricsWrap {
+    MetricsWrap::new(registry)
+}
+
+/// 指标的注册表。
+///
+/// 注册表可以廉价地克隆，并且可以在多个工作线程之间共享。
+#[derive(Clone)]
+pub struct Registry {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    buckets: Vec<f64>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    requests: BTreeMap<Labels, Histogram>,
+    in_flight: BTreeMap<String, i64>,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Labels {
+    method: String,
+    route: MatchedRoute,
+    status: &'static str,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum MatchedRoute {
+    Matched(String),
+    Unmatched,
+}
+
+impl MatchedRoute {
+    fn as_str(&self) -> &str {
+        match self {
+            MatchedRoute::Matched(route) => route,
+            MatchedRoute::Unmatched => "unmatched",
+        }
+    }
+}
+
+struct Histogram {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Registry {
+    /// 创建一个使用[默认桶](DEFAULT_BUCKETS)的注册表。
+    pub fn new() -> Self {
+        Self::with_buckets(DEFAULT_BUCKETS.to_vec())
+    }
+
+    /// 创建一个使用给定桶的注册表。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果桶没有按升序排列，将会发生恐慌。
+    pub fn with_buckets(buckets: Vec<f64>) -> Self {
+        if buckets.windows(2).any(|w| w[0] >= w[1]) {
+            panic!("Buckets must be sorted in ascending order");
+        }
+        Self {
+            inner: Arc::new(Inner {
+                buckets,
+                state: Mutex::new(State::default()),
+            }),
+        }
+    }
+
+    /// 返回一个以Prometheus文本格式输出指标的服务。
+    pub fn exporter(&self) -> MetricsExporter {
+        MetricsExporter {
+            registry: self.clone(),
+        }
+    }
+
+    /// 以Prometheus文本格式输出指标。
+    pub fn render(&self) -> String {
+        let state = self.inner.state.lock().unwrap();
+        let mut out = String::new();
+
+        out.push_str("# HELP http_requests_total Total number of HTTP requests.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for (labels, histogram) in &state.requests {
+            let _ = writeln!(out, "http_requests_total{{{}}} {}", labels, histogram.count);
+        }
+
+        out.push_str("# HELP http_request_duration_seconds HTTP request latency in seconds.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for (labels, histogram) in &state.requests {
+            for (le, count) in self.inner.buckets.iter().zip(&histogram.buckets) {
+                let _ = writeln!(
+                    out,
+                    "http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, le, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_sum{{{}}} {}",
+                labels, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "http_request_duration_seconds_count{{{}}} {}",
+                labels, histogram.count
+            );
+        }
+
+        out.push_str("# HELP http_requests_in_flight Number of HTTP requests in flight.\n");
+        out.push_str("# TYPE http_requests_in_flight gauge\n");
+        for (method, count) in &state.in_flight {
+            let _ = writeln!(
+                out,
+                "http_requests_in_flight{{method=\"{}\"}} {}",
+                Escape(method),
+                count
+            );
+        }
+
+        out
+    }
+
+    fn start(&self, method: &Method) {
+        let mut state = self.inner.state.lock().unwrap();
+        *state
+            .in_flight
+            .entry(method.as_str().to_owned())
+            .or_default() += 1;
+    }
+
+    fn finish(&self, method: Method, route: MatchedRoute, status: &'static str, start: Instant) {
+        let elapsed = start.elapsed().as_secs_f64();
+        let mut state = self.inner.state.lock().unwrap();
+
+        if let Some(count) = state.in_flight.get_mut(method.as_str()) {
+            *count -= 1;
+        }
+
+        let histogram = state
+            .requests
+            .entry(Labels {
+                method: method.as_str().to_owned(),
+                route,
+                status,
+            })
+            .or_insert_with(|| Histogram {
+                buckets: vec![0; self.inner.buckets.len()],
+                sum: 0.0,
+                count: 0,
+            });
+
+        for (le, count) in self.inner.buckets.iter().zip(&mut histogram.buckets) {
+            if elapsed <= *le {
+                *count += 1;
+            }
+        }
+        histogram.sum += elapsed;
+        histogram.count += 1;
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("buckets", &self.inner.buckets)
+            .finish()
+    }
+}
+
+impl fmt::Display for Labels {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "method=\"{}\",route=\"{}\",status=\"{}\"",
+            Escape(&self.method),
+            Escape(self.route.as_str()),
+            self.status
+        )
+    }
+}
+
+/// 转义标签值中的反斜杠、双引号和换行符。
+struct Escape<'a>(&'a str);
+
+impl fmt::Display for Escape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '"' => f.write_str("\\\"")?,
+                '\n' => f.write_str("\\n")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        _ => "5xx",
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsWrap {
+    registry: Registry,
+}
+
+impl MetricsWrap {
+    pub fn new(registry: Registry) -> Self {
+        Self { registry }
+    }
+}
+
+impl<S> Wrap<S> for MetricsWrap {
+    type Service = Metrics<S>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        Metrics {
+            inner: service,
+            registry: self.registry,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Metrics<S> {
+    inner: S,
+    registry: Registry,
+}
+
+impl<S, B> Service<Request<B>> for Metrics<S>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = MetricsFuture<S::Future>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Request<B>) -> Self::Future {
+        let method = request.method().clone();
+        let route = request.extensions().get::<MatchedPath>().cloned();
+
+        self.registry.start(&method);
+
+        // 在调用内部服务前记录开始时间，以便延迟包含内部服务同步执行的部分。
+        let start = Instant::now();
+
+        MetricsFuture {
+            fut: self.inner.call(request),
+            state: Some(RequestState {
+                registry: self.registry.clone(),
+                method,
+                route,
+                start,
+            }),
+        }
+    }
+}
+
+struct RequestState {
+    registry: Registry,
+    method: Method,
+    route: Option<MatchedPath>,
+    start: Instant,
+}
+
+impl RequestState {
+    fn finish(self, status: &'static str) {
+        let route = match self.route {
+            Some(route) => MatchedRoute::Matched(route.as_str().to_owned()),
+            None => MatchedRoute::Unmatched,
+        };
+        self.registry.finish(self.method, route, status, self.start);
+    }
+}
+
+pin_project! {
+    pub struct MetricsFuture<Fut> {
+        #[pin]
+        fut: Fut,
+        state: Option<RequestState>,
+    }
+
+    impl<Fut> PinnedDrop for MetricsFuture<Fut> {
+        fn drop(this: Pin<&mut Self>) {
+            // 请求在完成前被取消。
+            if let Some(state) = this.project().state.take() {
+                state.finish("error");
+            }
+        }
+    }
+}
+
+impl<Fut, Res, Err> Future for MetricsFuture<Fut>
+where
+    Fut: Future<Output = Result<Res, Err>>,
+    Res: IntoResponse,
+{
+    type Output = Result<Response, Err>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.fut.poll(cx));
+        let mut state = this.state.take().expect("polled after completion");
+
+        let response = match result {
+            Ok(response) => response.into_response(),
+            Err(err) => {
+                state.finish("error");
+                return Poll::Ready(Err(err));
+            }
+        };
+
+        if let Some(matched_path) = response.extensions().get::<MatchedPath>() {
+            state.route = Some(matched_path.clone());
+        }
+        let status = status_class(response.status());
+
+        Poll::Ready(Ok(response.map(|body| {
+            OnEndBody::new(body, move |_, _| state.finish(status)).boxed()
+        })))
+    }
+}
+
+impl<Fut> fmt::Debug for MetricsFuture<Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsFuture").finish()
+    }
+}
+
+/// 以Prometheus文本格式输出指标的服务。
+#[derive(Debug, Clone)]
+pub struct MetricsExporter {
+    registry: Registry,
+}
+
+impl<B> Service<Request<B>> for MetricsExporter {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Ready<Result<Response, Infallible>>;
+
+    fn call(&self, _: Request<B>) -> Self::Future {
+        let mut response = self.registry.render().into_response();
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+        );
+        ready(Ok(response))
+    }
+}
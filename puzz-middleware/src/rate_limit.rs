@@ -0,0 +1,692 @@
+//! 基于客户端的速率限制。
+//!
+//! [`rate_limit`]按[`KeyExtractor`]提取的键（例如客户端IP、请求标头或API密钥）分别限制请求速率，
+//! 支持[令牌桶](Algorithm::TokenBucket)和[GCRA](Algorithm::Gcra)两种算法。
+//!
+//! 每个响应都会携带`RateLimit-Limit`、`RateLimit-Remaining`和`RateLimit-Reset`标头，
+//! 超出限制的请求会收到`429 Too Many Requests`响应，并携带`Retry-After`标头。
+//!
+//! 限制状态默认保存在[`MemoryStore`]中，可以实现[`Store`]特征以使用共享的存储后端。
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::{ready, Future, Ready};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::ready;
+use pin_project_lite::pin_project;
+use puzz_core::http::{HeaderName, HeaderValue, StatusCode};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::{BoxError, Request, Response};
+use puzz_server::PeerAddr;
+use tokio::time::Instant;
+
+/// `RateLimit-Limit`标头。
+pub const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+
+/// `RateLimit-Remaining`标头。
+pub const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+
+/// `RateLimit-Reset`标头。
+pub const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// 创建一个按`key`限制请求速率的[`Wrap`]。
+///
+/// 无法提取键的请求不受限制。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::http::StatusCode;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::rate_limit::{rate_limit, HeaderKey, Quota};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+///     .with(rate_limit(Quota::per_minute(2), HeaderKey::new("x-api-key")));
+///
+/// let request = || {
+///     Request::builder()
+///         .header("x-api-key", "alice")
+///         .body(Default::default())
+///         .unwrap()
+/// };
+///
+/// let response = service.call(request()).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::OK);
+/// assert_eq!(response.headers()["ratelimit-remaining"], "1");
+///
+/// service.call(request()).await.unwrap();
+///
+/// let response = service.call(request()).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+/// assert_eq!(response.headers()["retry-after"], "30");
+/// # }
+/// ```
+pub fn rate_limit<E>(quota: Quota, key: E) -> RateLimitWrap<E, MemoryStore<E::Key>>
+where
+    E: KeyExtractor,
+    E::Key: Hash + Eq,
+{
+    RateLimitWrap::new(quota, key, MemoryStore::new())
+}
+
+/// 速率限制的配额。
+///
+/// 配额由一个周期内允许的请求数和突发容量组成，突发容量默认等于请求数。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quota {
+    limit: u64,
+    period: Duration,
+    burst: u64,
+}
+
+impl Quota {
+    /// 创建一个在`period`内允许`limit`个请求的配额。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果`limit`或`period`为零，将会发生恐慌。
+    pub fn new(limit: u64, period: Duration) -> Self {
+        if limit == 0 || period.is_zero() {
+            panic!("Quota limit and period must be greater than zero");
+        }
+        Self {
+            limit,
+            period,
+            burst: limit,
+        }
+    }
+
+    /// 创建一个每秒允许`limit`个请求的配额。
+    pub fn per_second(limit: u64) -> Self {
+        Self::new(limit, Duration::from_secs(1))
+    }
+
+    /// 创建一个每分钟允许`limit`个请求的配额。
+    pub fn per_minute(limit: u64) -> Self {
+        Self::new(limit, Duration::from_secs(60))
+    }
+
+    /// 创建一个每小时允许`limit`个请求的配额。
+    pub fn per_hour(limit: u64) -> Self {
+        Self::new(limit, Duration::from_secs(3600))
+    }
+
+    /// 设置突发容量，即短时间内最多允许的请求数。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果`burst`为零，将会发生恐慌。
+    pub fn burst(mut self, burst: u64) -> Self {
+        if burst == 0 {
+            panic!("Quota burst must be greater than zero");
+        }
+        self.burst = burst;
+        self
+    }
+
+    /// 获取一个周期内允许的请求数。
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// 获取周期。
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// 获取突发容量。
+    pub fn burst_size(&self) -> u64 {
+        self.burst
+    }
+
+    /// 两个请求之间的间隔。
+    fn emission_interval(&self) -> Duration {
+        let limit = u32::try_from(self.limit).unwrap_or(u32::MAX);
+        (self.period / limit).max(Duration::from_nanos(1))
+    }
+}
+
+/// 速率限制算法。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    /// 令牌桶算法。
+    ///
+    /// 令牌以固定的速率补充，直到达到突发容量，每个请求消耗一个令牌。
+    TokenBucket,
+    /// 通用信元速率算法（Generic Cell Rate Algorithm）。
+    ///
+    /// 与令牌桶等价，但每个键只需要保存一个时间戳。
+    #[default]
+    Gcra,
+}
+
+/// 速率限制的判定结果。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    /// 是否允许请求。
+    pub allowed: bool,
+    /// 突发容量。
+    pub limit: u64,
+    /// 剩余可用的请求数。
+    pub remaining: u64,
+    /// 配额完全恢复所需的时间。
+    pub reset: Duration,
+    /// 请求被拒绝时，需要等待多久才能重试。
+    pub retry_after: Option<Duration>,
+}
+
+/// 保存速率限制状态的存储。
+pub trait Store<K> {
+    /// 异步返回的判定结果。
+    type Future: Future<Output = Result<Decision, BoxError>>;
+
+    /// 使用给定的算法和配额，判定是否允许`key`的一个请求。
+    fn check(&self, key: K, quota: Quota, algorithm: Algorithm) -> Self::Future;
+}
+
+/// 保存在内存中的速率限制状态。
+///
+/// 已完全恢复配额的键会被定期清除。存储可以廉价地克隆，克隆后的存储共享相同的状态。
+pub struct MemoryStore<K> {
+    inner: Arc<Mutex<MemoryState<K>>>,
+}
+
+struct MemoryState<K> {
+    entries: HashMap<K, Entry>,
+    sweep_interval: Duration,
+    last_sweep: Instant,
+}
+
+enum Entry {
+    TokenBucket { tokens: f64, updated: Instant },
+    Gcra { tat: Instant },
+}
+
+impl<K> MemoryStore<K>
+where
+    K: Hash + Eq,
+{
+    /// 创建一个空的存储。
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(MemoryState {
+                entries: HashMap::new(),
+                sweep_interval: Duration::from_secs(60),
+                last_sweep: Instant::now(),
+            })),
+        }
+    }
+
+    /// 设置清除过期键的间隔，默认为`60`秒。
+    pub fn sweep_interval(self, sweep_interval: Duration) -> Self {
+        self.inner.lock().unwrap().sweep_interval = sweep_interval;
+        self
+    }
+
+    /// 获取存储中键的数量。
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// 存储是否为空。
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K> Default for MemoryStore<K>
+where
+    K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> Clone for MemoryStore<K> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<K> fmt::Debug for MemoryStore<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryStore").finish()
+    }
+}
+
+impl<K> Store<K> for MemoryStore<K>
+where
+    K: Hash + Eq,
+{
+    type Future = Ready<Result<Decision, BoxError>>;
+
+    fn check(&self, key: K, quota: Quota, algorithm: Algorithm) -> Self::Future {
+        let now = Instant::now();
+        let mut state = self.inner.lock().unwrap();
+
+        if now.duration_since(state.last_sweep) >= state.sweep_interval {
+            state.last_sweep = now;
+            state
+                .entries
+                .retain(|_, entry| !entry.is_stale(now, &quota));
+        }
+
+        let entry = state.entries.entry(key).or_insert_with(|| match algorithm {
+            Algorithm::TokenBucket => Entry::TokenBucket {
+                tokens: quota.burst as f64,
+                updated: now,
+            },
+            Algorithm::Gcra => Entry::Gcra { tat: now },
+        });
+
+        ready(Ok(entry.check(now, &quota)))
+    }
+}
+
+impl Entry {
+    fn check(&mut self, now: Instant, quota: &Quota) -> Decision {
+        match self {
+            Entry::TokenBucket { tokens, updated } => {
+                let rate = quota.limit as f64 / quota.period.as_secs_f64();
+                let burst = quota.burst as f64;
+
+                *tokens = (*tokens + now.duration_since(*updated).as_secs_f64() * rate).min(burst);
+                *updated = now;
+
+                let allowed = *tokens >= 1.0;
+                let retry_after = if allowed {
+                    *tokens -= 1.0;
+                    None
+                } else {
+                    Some(Duration::from_secs_f64((1.0 - *tokens) / rate))
+                };
+
+                Decision {
+                    allowed,
+                    limit: quota.burst,
+                    remaining: *tokens as u64,
+                    reset: Duration::from_secs_f64((burst - *tokens) / rate),
+                    retry_after,
+                }
+            }
+            Entry::Gcra { tat } => {
+                let interval = quota.emission_interval();
+                // 突发容量很大时，容差会被限制在`Duration::MAX`以内。
+                let tolerance = u32::try_from(quota.burst)
+                    .ok()
+                    .and_then(|burst| interval.checked_mul(burst))
+                    .unwrap_or(Duration::MAX);
+
+                let new_tat = (*tat).max(now) + interval;
+                // `new_tat`总是晚于`now`，比较二者的差值可以避免`Instant`的下溢。
+                let ahead = new_tat.duration_since(now);
+
+                if ahead > tolerance {
+                    return Decision {
+                        allowed: false,
+                        limit: quota.burst,
+                        remaining: 0,
+                        reset: tat.saturating_duration_since(now),
+                        retry_after: Some(ahead - tolerance),
+                    };
+                }
+
+                *tat = new_tat;
+
+                Decision {
+                    allowed: true,
+                    limit: quota.burst,
+                    remaining: ((tolerance - ahead).as_nanos() / interval.as_nanos()) as u64,
+                    reset: ahead,
+                    retry_after: None,
+                }
+            }
+        }
+    }
+
+    fn is_stale(&self, now: Instant, quota: &Quota) -> bool {
+        match self {
+            Entry::TokenBucket { tokens, updated } => {
+                let rate = quota.limit as f64 / quota.period.as_secs_f64();
+                *tokens + now.duration_since(*updated).as_secs_f64() * rate >= quota.burst as f64
+            }
+            Entry::Gcra { tat } => *tat <= now,
+        }
+    }
+}
+
+/// 从请求中提取速率限制的键。
+pub trait KeyExtractor {
+    /// 提取的键。
+    type Key;
+
+    /// 从请求中提取键，如果无法提取则返回[`None`]。
+    fn extract(&self, request: &Request) -> Option<Self::Key>;
+}
+
+impl<F, K> KeyExtractor for F
+where
+    F: Fn(&Request) -> Option<K>,
+{
+    type Key = K;
+
+    fn extract(&self, request: &Request) -> Option<Self::Key> {
+        self(request)
+    }
+}
+
+/// 使用对端的IP地址（见[`PeerAddr`]）作为键。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PeerIp;
+
+impl KeyExtractor for PeerIp {
+    type Key = IpAddr;
+
+    fn extract(&self, request: &Request) -> Option<Self::Key> {
+        request
+            .extensions()
+            .get::<PeerAddr>()
+            .map(|peer_addr| peer_addr.0.ip())
+    }
+}
+
+/// 使用请求标头的值作为键。
+#[derive(Debug, Clone)]
+pub struct HeaderKey(HeaderName);
+
+impl HeaderKey {
+    /// 使用给定名称的标头创建提取器。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果`name`不是有效的标头名称，将会发生恐慌。
+    pub fn new<N>(name: N) -> Self
+    where
+        N: TryInto<HeaderName>,
+        N::Error: fmt::Debug,
+    {
+        Self(name.try_into().expect("invalid header name"))
+    }
+}
+
+impl KeyExtractor for HeaderKey {
+    type Key = HeaderValue;
+
+    fn extract(&self, request: &Request) -> Option<Self::Key> {
+        request.headers().get(&self.0).cloned()
+    }
+}
+
+/// 使用请求扩展中的值（例如认证中间件插入的API密钥）作为键。
+pub struct ExtensionKey<T>(PhantomData<fn() -> T>);
+
+impl<T> ExtensionKey<T> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T> Default for ExtensionKey<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for ExtensionKey<T> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for ExtensionKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ExtensionKey")
+            .field(&std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+impl<T> KeyExtractor for ExtensionKey<T>
+where
+    T: Clone + 'static,
+{
+    type Key = T;
+
+    fn extract(&self, request: &Request) -> Option<Self::Key> {
+        request.extensions().get::<T>().cloned()
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitWrap<E, St> {
+    quota: Quota,
+    algorithm: Algorithm,
+    key: E,
+    store: St,
+}
+
+impl<E, St> RateLimitWrap<E, St> {
+    pub fn new(quota: Quota, key: E, store: St) -> Self {
+        Self {
+            quota,
+            algorithm: Algorithm::default(),
+            key,
+            store,
+        }
+    }
+
+    /// 设置速率限制算法，默认为[`Algorithm::Gcra`]。
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// 设置保存速率限制状态的存储。
+    pub fn store<T>(self, store: T) -> RateLimitWrap<E, T> {
+        RateLimitWrap {
+            quota: self.quota,
+            algorithm: self.algorithm,
+            key: self.key,
+            store,
+        }
+    }
+}
+
+impl<S, E, St> Wrap<S> for RateLimitWrap<E, St> {
+    type Service = RateLimit<S, E, St>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        RateLimit {
+            inner: Arc::new(service),
+            quota: self.quota,
+            algorithm: self.algorithm,
+            key: self.key,
+            store: self.store,
+        }
+    }
+}
+
+impl<E, St> fmt::Debug for RateLimitWrap<E, St> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimitWrap")
+            .field("quota", &self.quota)
+            .field("algorithm", &self.algorithm)
+            .field("key", &std::any::type_name::<E>())
+            .field("store", &std::any::type_name::<St>())
+            .finish()
+    }
+}
+
+pub struct RateLimit<S, E, St> {
+    // 判定完成后才会调用服务。
+    inner: Arc<S>,
+    quota: Quota,
+    algorithm: Algorithm,
+    key: E,
+    store: St,
+}
+
+impl<S, E, St> Clone for RateLimit<S, E, St>
+where
+    E: Clone,
+    St: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            quota: self.quota,
+            algorithm: self.algorithm,
+            key: self.key.clone(),
+            store: self.store.clone(),
+        }
+    }
+}
+
+impl<S, E, St> Service<Request> for RateLimit<S, E, St>
+where
+    S: Service<Request>,
+    S::Response: IntoResponse,
+    E: KeyExtractor,
+    St: Store<E::Key>,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = RateLimitFuture<S, St::Future>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Request) -> Self::Future {
+        match self.key.extract(&request) {
+            Some(key) => RateLimitFuture::Checking {
+                check: self.store.check(key, self.quota, self.algorithm),
+                state: Some((self.inner.clone(), request)),
+            },
+            None => RateLimitFuture::Calling {
+                fut: self.inner.call(request),
+                decision: None,
+            },
+        }
+    }
+}
+
+impl<S, E, St> fmt::Debug for RateLimit<S, E, St>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimit")
+            .field("inner", &self.inner)
+            .field("quota", &self.quota)
+            .field("algorithm", &self.algorithm)
+            .field("key", &std::any::type_name::<E>())
+            .field("store", &std::any::type_name::<St>())
+            .finish()
+    }
+}
+
+pin_project! {
+    #[project = RateLimitFutureProj]
+    pub enum RateLimitFuture<S, F>
+    where
+        S: Service<Request>,
+    {
+        Checking {
+            #[pin]
+            check: F,
+            state: Option<(Arc<S>, Request)>,
+        },
+        Calling {
+            #[pin]
+            fut: S::Future,
+            decision: Option<Decision>,
+        },
+    }
+}
+
+impl<S, F> Future for RateLimitFuture<S, F>
+where
+    S: Service<Request>,
+    S::Response: IntoResponse,
+    F: Future<Output = Result<Decision, BoxError>>,
+{
+    type Output = Result<Response, S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                RateLimitFutureProj::Checking { check, state } => {
+                    // 存储出错时放行请求，以免存储故障导致服务不可用。
+                    let decision = ready!(check.poll(cx)).ok();
+                    let (inner, request) = state.take().expect("polled after completion");
+
+                    if let Some(decision) = decision.filter(|decision| !decision.allowed) {
+                        let mut response = StatusCode::TOO_MANY_REQUESTS.into_response();
+                        insert_headers(&mut response, &decision);
+                        return Poll::Ready(Ok(response));
+                    }
+
+                    self.set(RateLimitFuture::Calling {
+                        fut: inner.call(request),
+                        decision,
+                    });
+                }
+                RateLimitFutureProj::Calling { fut, decision } => {
+                    let mut response = ready!(fut.poll(cx))?.into_response();
+                    if let Some(decision) = decision {
+                        insert_headers(&mut response, decision);
+                    }
+                    return Poll::Ready(Ok(response));
+                }
+            }
+        }
+    }
+}
+
+impl<S, F> fmt::Debug for RateLimitFuture<S, F>
+where
+    S: Service<Request>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimitFuture").finish()
+    }
+}
+
+fn insert_headers(response: &mut Response, decision: &Decision) {
+    let headers = response.headers_mut();
+
+    headers.insert(RATELIMIT_LIMIT, HeaderValue::from(decision.limit));
+    headers.insert(RATELIMIT_REMAINING, HeaderValue::from(decision.remaining));
+    headers.insert(
+        RATELIMIT_RESET,
+        HeaderValue::from(ceil_secs(decision.reset)),
+    );
+
+    if let Some(retry_after) = decision.retry_after {
+        headers.insert(
+            puzz_core::http::header::RETRY_AFTER,
+            HeaderValue::from(ceil_secs(retry_after).max(1)),
+        );
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
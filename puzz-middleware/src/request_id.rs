@@ -0,0 +1,369 @@
+//! 请求ID的生成与传递。
+//!
+//! [`request_id`]从请求标头中读取请求ID，如果请求没有携带或携带的ID无效，则生成一个新的ID。
+//! 请求ID会被插入到请求扩展中，并在响应中原样返回，以便在多个服务之间关联日志。
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::ready;
+use pin_project_lite::pin_project;
+use puzz_core::http::{HeaderName, HeaderValue, StatusCode};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::{Request, Response};
+use tokio::task::futures::TaskLocalFuture;
+
+/// 默认的请求ID标头。
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// 请求ID的默认最大长度。
+pub const DEFAULT_MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+/// 创建一个设置请求ID的[`Wrap`]。
+///
+/// 默认从`X-Request-Id`标头读取请求ID，并使用UUID v4生成新的请求ID。
+/// 可以使用[`SetRequestIdWrap::make_request_id`]改为使用[`MakeUlid`]或自定义的方式生成。
+///
+/// 被包裹的服务（包括`handle_error`的错误处理函数）
+/// 可以通过[`RequestId::current`]获取当前请求的ID。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::request_id::{request_id, RequestId, X_REQUEST_ID};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|request: Request| async move {
+///     let id = request.extensions().get::<RequestId>().unwrap();
+///     assert_eq!(id.as_str(), "abc-123");
+///     Ok::<_, Infallible>("hi!")
+/// })
+/// .with(request_id());
+///
+/// let request = Request::builder()
+///     .header(X_REQUEST_ID, "abc-123")
+///     .body(Default::default())
+///     .unwrap();
+///
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.headers()[X_REQUEST_ID], "abc-123");
+/// # }
+/// ```
+pub fn request_id() -> SetRequestIdWrap<MakeUuid> {
+    SetRequestIdWrap::new()
+}
+
+/// 请求ID。
+///
+/// 请求ID只包含可见的ASCII字符，并且长度不超过配置的最大长度。
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct RequestId(HeaderValue);
+
+impl RequestId {
+    /// 使用给定的标头值创建请求ID。
+    ///
+    /// 如果标头值为空或包含不可见字符，则返回[`None`]。
+    pub fn new(value: HeaderValue) -> Option<Self> {
+        if is_valid(value.as_bytes(), usize::MAX) {
+            Some(Self(value))
+        } else {
+            None
+        }
+    }
+
+    /// 获取当前请求的ID。
+    ///
+    /// 只有在[`request_id`]包裹的服务中调用时才会返回[`Some`]。
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    /// 获取请求ID的字符串形式。
+    pub fn as_str(&self) -> &str {
+        // 创建时已经检查过只包含可见的ASCII字符。
+        self.0.to_str().unwrap()
+    }
+
+    /// 获取请求ID的标头值。
+    pub fn header_value(&self) -> &HeaderValue {
+        &self.0
+    }
+
+    /// 将请求ID转换为标头值。
+    pub fn into_header_value(self) -> HeaderValue {
+        self.0
+    }
+}
+
+impl fmt::Debug for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RequestId").field(&self.as_str()).finish()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+fn is_valid(bytes: &[u8], max_len: usize) -> bool {
+    !bytes.is_empty() && bytes.len() <= max_len && bytes.iter().all(|b| b.is_ascii_graphic())
+}
+
+/// 生成请求ID。
+pub trait MakeRequestId {
+    /// 生成一个新的请求ID。
+    fn make_request_id(&self) -> RequestId;
+}
+
+impl<F> MakeRequestId for F
+where
+    F: Fn() -> RequestId,
+{
+    fn make_request_id(&self) -> RequestId {
+        self()
+    }
+}
+
+/// 使用UUID v4生成请求ID。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MakeUuid;
+
+impl MakeRequestId for MakeUuid {
+    fn make_request_id(&self) -> RequestId {
+        let mut buf = uuid::Uuid::encode_buffer();
+        let id = uuid::Uuid::new_v4().hyphenated().encode_lower(&mut buf);
+        RequestId(HeaderValue::from_str(id).unwrap())
+    }
+}
+
+/// 使用[ULID](https://github.com/ulid/spec)生成请求ID。
+///
+/// ULID由48位的毫秒时间戳和80位的随机数组成，编码为26个字符，按字典序排序时大致按生成时间排序。
+/// 同一毫秒内生成的ID之间没有顺序。
+///
+/// # 例子
+///
+/// ```
+/// use puzz_middleware::request_id::{MakeRequestId, MakeUlid};
+///
+/// let id = MakeUlid.make_request_id();
+/// assert_eq!(id.as_str().len(), 26);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MakeUlid;
+
+impl MakeRequestId for MakeUlid {
+    fn make_request_id(&self) -> RequestId {
+        const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+
+        // UUID v4的低62位都是随机的，两个UUID可以提供80位随机数。
+        let mask = (1u128 << 62) - 1;
+        let random = (uuid::Uuid::new_v4().as_u128() & mask)
+            | ((uuid::Uuid::new_v4().as_u128() & mask) << 62);
+
+        let value = (u128::from(timestamp & 0xFFFF_FFFF_FFFF) << 80) | (random & ((1 << 80) - 1));
+
+        let mut buf = [0; 26];
+        for (i, c) in buf.iter_mut().enumerate() {
+            *c = ALPHABET[((value >> (125 - 5 * i)) & 0x1F) as usize];
+        }
+        RequestId(HeaderValue::from_bytes(&buf).unwrap())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SetRequestIdWrap<M> {
+    header: HeaderName,
+    max_len: usize,
+    reject_invalid: bool,
+    make: M,
+}
+
+impl SetRequestIdWrap<MakeUuid> {
+    pub fn new() -> Self {
+        Self {
+            header: X_REQUEST_ID,
+            max_len: DEFAULT_MAX_LEN,
+            reject_invalid: false,
+            make: MakeUuid,
+        }
+    }
+}
+
+impl Default for SetRequestIdWrap<MakeUuid> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<M> SetRequestIdWrap<M> {
+    /// 设置读取和返回请求ID的标头，默认为`X-Request-Id`。
+    pub fn header(mut self, header: HeaderName) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// 设置请求ID的最大长度，默认为[`DEFAULT_MAX_LEN`]。
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// 设置是否拒绝携带无效请求ID的请求。
+    ///
+    /// 默认会为这些请求重新生成请求ID；如果设置为`true`，则直接返回`400 Bad Request`。
+    pub fn reject_invalid(mut self, reject: bool) -> Self {
+        self.reject_invalid = reject;
+        self
+    }
+
+    /// 设置生成请求ID的方式。
+    pub fn make_request_id<T>(self, make: T) -> SetRequestIdWrap<T>
+    where
+        T: MakeRequestId,
+    {
+        SetRequestIdWrap {
+            header: self.header,
+            max_len: self.max_len,
+            reject_invalid: self.reject_invalid,
+            make,
+        }
+    }
+}
+
+impl<S, M> Wrap<S> for SetRequestIdWrap<M> {
+    type Service = SetRequestId<S, M>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        SetRequestId {
+            inner: service,
+            header: self.header,
+            max_len: self.max_len,
+            reject_invalid: self.reject_invalid,
+            make: self.make,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SetRequestId<S, M> {
+    inner: S,
+    header: HeaderName,
+    max_len: usize,
+    reject_invalid: bool,
+    make: M,
+}
+
+impl<S, M, B> Service<Request<B>> for SetRequestId<S, M>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+    M: MakeRequestId,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = SetRequestIdFuture<S::Future>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, mut request: Request<B>) -> Self::Future {
+        let id = match request.headers().get(&self.header) {
+            Some(value) if is_valid(value.as_bytes(), self.max_len) => RequestId(value.clone()),
+            Some(_) if self.reject_invalid => {
+                return SetRequestIdFuture::Rejected;
+            }
+            _ => self.make.make_request_id(),
+        };
+
+        request
+            .headers_mut()
+            .insert(self.header.clone(), id.header_value().clone());
+        request.extensions_mut().insert(id.clone());
+
+        let fut = CURRENT.sync_scope(id.clone(), || self.inner.call(request));
+
+        SetRequestIdFuture::Future {
+            fut: CURRENT.scope(id.clone(), fut),
+            header: self.header.clone(),
+            id,
+        }
+    }
+}
+
+impl<S, M> fmt::Debug for SetRequestId<S, M>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SetRequestId")
+            .field("inner", &self.inner)
+            .field("header", &self.header)
+            .field("max_len", &self.max_len)
+            .field("reject_invalid", &self.reject_invalid)
+            .field("make", &std::any::type_name::<M>())
+            .finish()
+    }
+}
+
+pin_project! {
+    #[project = SetRequestIdFutureProj]
+    pub enum SetRequestIdFuture<Fut> {
+        Future {
+            #[pin]
+            fut: TaskLocalFuture<RequestId, Fut>,
+            header: HeaderName,
+            id: RequestId,
+        },
+        Rejected,
+    }
+}
+
+impl<Fut, Res, Err> Future for SetRequestIdFuture<Fut>
+where
+    Fut: Future<Output = Result<Res, Err>>,
+    Res: IntoResponse,
+{
+    type Output = Result<Response, Err>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            SetRequestIdFutureProj::Future { fut, header, id } => {
+                let mut response = ready!(fut.poll(cx))?.into_response();
+                response
+                    .headers_mut()
+                    .insert(header.clone(), id.header_value().clone());
+                response.extensions_mut().insert(id.clone());
+                Poll::Ready(Ok(response))
+            }
+            SetRequestIdFutureProj::Rejected => {
+                Poll::Ready(Ok(StatusCode::BAD_REQUEST.into_response()))
+            }
+        }
+    }
+}
+
+impl<Fut> fmt::Debug for SetRequestIdFuture<Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SetRequestIdFuture").finish()
+    }
+}
@@ -0,0 +1,444 @@
+//! 重试失败的请求。
+//!
+//! [`RetryPolicy`]实现了[`Policy`]，配合[`ServiceExt::retry`](puzz_core::service::ServiceExt::retry)使用，
+//! 支持最大尝试次数、带抖动的[指数退避](Backoff)以及限制重试比例的[预算](Budget)。
+//! 是否需要重试由[`Classify`]决定，默认重试错误和部分服务器错误响应。
+//!
+//! 重试时需要克隆请求，因此请求的正文必须实现[`Clone`]。可以使用[`buffer_request`]
+//! 读取完整的正文，得到可以重放的请求。克隆的请求不包含原请求的扩展。
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use pin_project_lite::pin_project;
+use puzz_core::body::{Body, Bytes};
+use puzz_core::http::StatusCode;
+use puzz_core::service::util::Policy;
+use puzz_core::{BoxError, Request, Response};
+use tokio::time::{Instant, Sleep};
+
+/// 读取请求的完整正文，返回可以重放的请求。
+///
+/// # 例子
+///
+/// ```
+/// use puzz_core::body::BodyExt;
+/// use puzz_core::Request;
+/// use puzz_middleware::retry::buffer_request;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let request = Request::builder()
+///     .uri("/users")
+///     .body("hi!".boxed())
+///     .unwrap();
+///
+/// let request = buffer_request(request).await.unwrap();
+/// assert_eq!(request.body(), "hi!");
+/// # }
+/// ```
+pub async fn buffer_request<B>(request: Request<B>) -> Result<Request<Bytes>, BoxError>
+where
+    B: Body + Unpin,
+    B::Error: Into<BoxError>,
+{
+    let (head, body) = request.into_head();
+    let body = crate::body::to_bytes(body).await?;
+    Ok(Request::from_head(head, body))
+}
+
+/// 判断请求的结果是否需要重试。
+pub trait Classify<Res, E> {
+    /// 返回`true`表示需要重试。
+    fn should_retry(&self, result: Result<&Res, &E>) -> bool;
+}
+
+impl<F, Res, E> Classify<Res, E> for F
+where
+    F: Fn(Result<&Res, &E>) -> bool,
+{
+    fn should_retry(&self, result: Result<&Res, &E>) -> bool {
+        self(result)
+    }
+}
+
+/// 重试错误，以及`408 Request Timeout`、`429 Too Many Requests`和除
+/// `501 Not Implemented`以外的服务器错误响应。
+///
+/// 它不会检查请求方法是否幂等，非幂等的请求应该使用自定义的[`Classify`]。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServerErrors;
+
+impl<B, E> Classify<Response<B>, E> for ServerErrors {
+    fn should_retry(&self, result: Result<&Response<B>, &E>) -> bool {
+        match result {
+            Ok(response) => {
+                let status = response.status();
+                (status.is_server_error() && status != StatusCode::NOT_IMPLEMENTED)
+                    || status == StatusCode::REQUEST_TIMEOUT
+                    || status == StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+/// 带抖动的指数退避。
+///
+/// 第`n`次重试前等待`min(base * 2^(n-1), max)`，并随机减少其中`jitter`比例的时间。
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    jitter: f64,
+}
+
+impl Backoff {
+    /// 创建一个从`base`开始、不超过`max`的指数退避，默认抖动比例为`1.0`。
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            jitter: 1.0,
+        }
+    }
+
+    /// 设置抖动比例，取值范围为`0.0`到`1.0`，`0.0`表示不抖动。
+    ///
+    /// # 恐慌
+    ///
+    /// 比例不在`0.0`到`1.0`之间时会发生恐慌。
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        assert!((0.0..=1.0).contains(&jitter), "jitter must be in 0.0..=1.0");
+        self.jitter = jitter;
+        self
+    }
+
+    /// 获取第`retry`次（从`1`开始）重试前需要等待的时间。
+    pub fn delay(&self, retry: u32) -> Duration {
+        let delay = self
+            .base
+            .checked_mul(1 << retry.saturating_sub(1).min(31))
+            .map_or(self.max, |delay| delay.min(self.max));
+        delay.mul_f64(1.0 - self.jitter * random())
+    }
+}
+
+impl Default for Backoff {
+    /// 从100毫秒开始、不超过10秒的指数退避。
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100), Duration::from_secs(10))
+    }
+}
+
+// 返回`[0, 1)`之间的随机数。
+fn random() -> f64 {
+    // 版本和变体位都在高位，低53位是随机的。
+    let bits = uuid::Uuid::new_v4().as_u128() as u64 & ((1 << 53) - 1);
+    bits as f64 / (1u64 << 53) as f64
+}
+
+/// 重试预算，限制重试请求占全部请求的比例。
+///
+/// 每个请求存入`ratio`个令牌，每次重试取出一个令牌，令牌不足时不再重试。
+/// 另外每秒补充`min_per_sec`个令牌，使请求较少时也可以重试。
+/// 预算可以廉价地克隆，克隆后的预算共享相同的令牌。
+///
+/// # 例子
+///
+/// ```
+/// use puzz_middleware::retry::Budget;
+///
+/// let budget = Budget::new(0.25, 0);
+///
+/// for _ in 0..8 {
+///     budget.deposit();
+/// }
+///
+/// assert!(budget.withdraw());
+/// assert!(budget.withdraw());
+/// assert!(!budget.withdraw());
+/// ```
+#[derive(Clone)]
+pub struct Budget {
+    inner: Arc<Mutex<BudgetState>>,
+}
+
+struct BudgetState {
+    ratio: f64,
+    min_per_sec: u32,
+    tokens: f64,
+    reserve: f64,
+    refilled_at: Instant,
+}
+
+impl Budget {
+    /// 创建一个重试比例不超过`ratio`、每秒至少允许`min_per_sec`次重试的预算。
+    ///
+    /// # 恐慌
+    ///
+    /// `ratio`为负数时会发生恐慌。
+    pub fn new(ratio: f64, min_per_sec: u32) -> Self {
+        assert!(ratio >= 0.0, "ratio must not be negative");
+        Self {
+            inner: Arc::new(Mutex::new(BudgetState {
+                ratio,
+                min_per_sec,
+                tokens: 0.0,
+                reserve: min_per_sec as f64,
+                refilled_at: Instant::now(),
+            })),
+        }
+    }
+
+    /// 存入一个请求。
+    pub fn deposit(&self) {
+        let mut state = self.inner.lock().unwrap();
+        // 令牌最多累积相当于1000个请求的数量，避免长时间的成功请求后出现大量重试。
+        state.tokens = (state.tokens + state.ratio).min(state.ratio * 1000.0);
+    }
+
+    /// 尝试取出一次重试，预算不足时返回`false`。
+    pub fn withdraw(&self) -> bool {
+        let mut state = self.inner.lock().unwrap();
+
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(state.refilled_at);
+        let min_per_sec = state.min_per_sec as f64;
+        state.reserve = (state.reserve + elapsed.as_secs_f64() * min_per_sec).min(min_per_sec);
+        state.refilled_at = now;
+
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            true
+        } else if state.reserve >= 1.0 {
+            state.reserve -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Default for Budget {
+    /// 重试比例不超过20%、每秒至少允许10次重试的预算。
+    fn default() -> Self {
+        Self::new(0.2, 10)
+    }
+}
+
+impl fmt::Debug for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.lock().unwrap();
+        f.debug_struct("Budget")
+            .field("ratio", &state.ratio)
+            .field("min_per_sec", &state.min_per_sec)
+            .field("tokens", &state.tokens)
+            .finish()
+    }
+}
+
+/// 按最大尝试次数、退避和预算重试请求的[`Policy`]。
+///
+/// # 例子
+///
+/// ```
+/// use std::cell::Cell;
+/// use std::convert::Infallible;
+/// use std::rc::Rc;
+/// use std::time::Duration;
+///
+/// use puzz_core::body::{BodyExt, Bytes};
+/// use puzz_core::http::StatusCode;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request, Response};
+/// use puzz_middleware::retry::{buffer_request, Backoff, RetryPolicy};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let calls = Rc::new(Cell::new(0));
+///
+/// let service = service_fn({
+///     let calls = calls.clone();
+///     move |_: Request<Bytes>| {
+///         calls.set(calls.get() + 1);
+///         let status = if calls.get() < 3 {
+///             StatusCode::SERVICE_UNAVAILABLE
+///         } else {
+///             StatusCode::OK
+///         };
+///         async move { Ok::<_, Infallible>(Response::builder().status(status).body(()).unwrap()) }
+///     }
+/// })
+/// .retry(
+///     RetryPolicy::new()
+///         .max_attempts(3)
+///         .backoff(Backoff::new(Duration::from_millis(1), Duration::from_millis(10))),
+/// );
+///
+/// let request = Request::builder().body("hi!".boxed()).unwrap();
+/// let request = buffer_request(request).await.unwrap();
+///
+/// let response = service.call(request).await.unwrap();
+/// assert_eq!(response.status(), StatusCode::OK);
+/// assert_eq!(calls.get(), 3);
+/// # }
+/// ```
+pub struct RetryPolicy<C = ServerErrors> {
+    classify: Arc<C>,
+    max_attempts: u32,
+    attempts: u32,
+    backoff: Backoff,
+    budget: Option<Budget>,
+}
+
+impl RetryPolicy {
+    /// 创建一个最多尝试3次、使用默认[`Backoff`]且不限制预算的策略。
+    pub fn new() -> Self {
+        Self {
+            classify: Arc::new(ServerErrors),
+            max_attempts: 3,
+            attempts: 1,
+            backoff: Backoff::default(),
+            budget: None,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> RetryPolicy<C> {
+    /// 设置判断是否需要重试的方式。
+    pub fn classify<T>(self, classify: T) -> RetryPolicy<T> {
+        RetryPolicy {
+            classify: Arc::new(classify),
+            max_attempts: self.max_attempts,
+            attempts: self.attempts,
+            backoff: self.backoff,
+            budget: self.budget,
+        }
+    }
+
+    /// 设置最大尝试次数（包括第一次请求），默认为3。
+    ///
+    /// # 恐慌
+    ///
+    /// 次数为0时会发生恐慌。
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        assert!(max_attempts > 0, "max_attempts must be greater than 0");
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// 设置重试前的退避。
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// 设置重试预算。
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+}
+
+impl<C> Clone for RetryPolicy<C> {
+    fn clone(&self) -> Self {
+        Self {
+            classify: self.classify.clone(),
+            max_attempts: self.max_attempts,
+            attempts: self.attempts,
+            backoff: self.backoff,
+            budget: self.budget.clone(),
+        }
+    }
+}
+
+impl<C> fmt::Debug for RetryPolicy<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("classify", &std::any::type_name::<C>())
+            .field("max_attempts", &self.max_attempts)
+            .field("attempts", &self.attempts)
+            .field("backoff", &self.backoff)
+            .field("budget", &self.budget)
+            .finish()
+    }
+}
+
+impl<C, B, Res, E> Policy<Request<B>, Res, E> for RetryPolicy<C>
+where
+    C: Classify<Res, E>,
+    B: Clone,
+{
+    type Future = RetryDelay<C>;
+
+    fn retry(&self, _: &Request<B>, result: Result<&Res, &E>) -> Option<Self::Future> {
+        if self.attempts >= self.max_attempts || !self.classify.should_retry(result) {
+            return None;
+        }
+
+        if let Some(budget) = &self.budget {
+            if !budget.withdraw() {
+                return None;
+            }
+        }
+
+        let mut policy = self.clone();
+        policy.attempts += 1;
+
+        Some(RetryDelay {
+            sleep: tokio::time::sleep(self.backoff.delay(self.attempts)),
+            policy: Some(policy),
+        })
+    }
+
+    fn clone_request(&self, request: &Request<B>) -> Option<Request<B>> {
+        if self.attempts == 1 {
+            if let Some(budget) = &self.budget {
+                budget.deposit();
+            }
+        }
+
+        let mut cloned = Request::new(request.body().clone());
+        *cloned.method_mut() = request.method().clone();
+        *cloned.uri_mut() = request.uri().clone();
+        *cloned.version_mut() = request.version();
+        *cloned.headers_mut() = request.headers().clone();
+        Some(cloned)
+    }
+}
+
+pin_project! {
+    /// 等待重试的[`Future`]。
+    pub struct RetryDelay<C> {
+        #[pin]
+        sleep: Sleep,
+        policy: Option<RetryPolicy<C>>,
+    }
+}
+
+impl<C> Future for RetryDelay<C> {
+    type Output = RetryPolicy<C>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        futures_core::ready!(this.sleep.poll(cx));
+        Poll::Ready(this.policy.take().expect("polled after completion"))
+    }
+}
+
+impl<C> fmt::Debug for RetryDelay<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryDelay").finish()
+    }
+}
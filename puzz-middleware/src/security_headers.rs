@@ -0,0 +1,392 @@
+//! 安全相关的响应标头。
+//!
+//! [`security_headers`]为响应设置`Strict-Transport-Security`、`Content-Security-Policy`、`X-Content-Type-Options`、
+//! `Referrer-Policy`、`Permissions-Policy`、`Cross-Origin-Opener-Policy`、`Cross-Origin-Embedder-Policy`和`X-Frame-Options`标头。
+//!
+//! 每个标头都可以单独设置或禁用，服务已经设置的标头不会被覆盖。
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures_core::ready;
+use pin_project_lite::pin_project;
+use puzz_core::http::header::{
+    CONTENT_SECURITY_POLICY, CONTENT_SECURITY_POLICY_REPORT_ONLY, REFERRER_POLICY,
+    STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+use puzz_core::http::{HeaderName, HeaderValue};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::{Request, Response};
+
+/// `Permissions-Policy`标头。
+pub const PERMISSIONS_POLICY: HeaderName = HeaderName::from_static("permissions-policy");
+
+/// `Cross-Origin-Opener-Policy`标头。
+pub const CROSS_ORIGIN_OPENER_POLICY: HeaderName =
+    HeaderName::from_static("cross-origin-opener-policy");
+
+/// `Cross-Origin-Embedder-Policy`标头。
+pub const CROSS_ORIGIN_EMBEDDER_POLICY: HeaderName =
+    HeaderName::from_static("cross-origin-embedder-policy");
+
+/// 内容安全策略中的随机数占位符。
+///
+/// 每个请求都会生成一个新的随机数替换策略中的占位符，并以[`CspNonce`]的形式插入请求扩展。
+pub const NONCE_PLACEHOLDER: &str = "{nonce}";
+
+/// 创建一个使用推荐配置设置安全标头的[`Wrap`]。
+///
+/// 推荐配置设置以下标头：
+///
+/// - `Strict-Transport-Security: max-age=31536000; includeSubDomains`
+/// - `Content-Security-Policy: default-src 'self'; base-uri 'self'; object-src 'none'; frame-ancestors 'self'; script-src 'self' 'nonce-{nonce}'`
+/// - `X-Content-Type-Options: nosniff`
+/// - `Referrer-Policy: strict-origin-when-cross-origin`
+/// - `Cross-Origin-Opener-Policy: same-origin`
+/// - `X-Frame-Options: SAMEORIGIN`
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::http::HeaderMap;
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::security_headers::{security_headers, CspNonce};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let service = service_fn(|request: Request| async move {
+///     let nonce = request.extensions().get::<CspNonce>().unwrap();
+///     let body = format!(r#"<script nonce="{}">alert("hi!")</script>"#, nonce);
+///
+///     let mut headers = HeaderMap::new();
+///     headers.insert("x-frame-options", "DENY".parse().unwrap());
+///     Ok::<_, Infallible>((headers, body))
+/// })
+/// .with(security_headers().permissions_policy("geolocation=()"));
+///
+/// let response = service.call(Request::default()).await.unwrap();
+/// let headers = response.headers();
+/// assert_eq!(headers["x-content-type-options"], "nosniff");
+/// assert_eq!(headers["permissions-policy"], "geolocation=()");
+/// // 服务设置的标头不会被覆盖。
+/// assert_eq!(headers["x-frame-options"], "DENY");
+///
+/// let csp = headers["content-security-policy"].to_str().unwrap();
+/// assert!(csp.contains("'nonce-"));
+/// assert!(!csp.contains("{nonce}"));
+/// # }
+/// ```
+pub fn security_headers() -> SecurityHeadersWrap {
+    SecurityHeadersWrap::new()
+}
+
+/// 当前请求的内容安全策略随机数。
+///
+/// 模板可以将其用于`<script nonce="...">`或`<style nonce="...">`。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CspNonce(String);
+
+impl CspNonce {
+    fn generate() -> Self {
+        Self(STANDARD.encode(uuid::Uuid::new_v4().as_bytes()))
+    }
+
+    /// 获取随机数的字符串形式。
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CspNonce {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Csp {
+    header: HeaderName,
+    policy: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SecurityHeadersWrap {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    csp: Option<Csp>,
+}
+
+impl SecurityHeadersWrap {
+    /// 使用推荐配置创建，参考[`security_headers`]。
+    pub fn new() -> Self {
+        Self::empty()
+            .strict_transport_security("max-age=31536000; includeSubDomains")
+            .content_security_policy(
+                "default-src 'self'; base-uri 'self'; object-src 'none'; \
+                 frame-ancestors 'self'; script-src 'self' 'nonce-{nonce}'",
+            )
+            .x_content_type_options("nosniff")
+            .referrer_policy("strict-origin-when-cross-origin")
+            .cross_origin_opener_policy("same-origin")
+            .x_frame_options("SAMEORIGIN")
+    }
+
+    /// 使用更严格的配置创建。
+    ///
+    /// 在推荐配置的基础上，禁止页面被嵌入、禁用常见的浏览器功能，并要求跨源资源明确授权：
+    ///
+    /// - `Strict-Transport-Security: max-age=63072000; includeSubDomains; preload`
+    /// - `Content-Security-Policy: default-src 'self'; base-uri 'none'; object-src 'none'; frame-ancestors 'none'; form-action 'self'; script-src 'nonce-{nonce}' 'strict-dynamic'`
+    /// - `Referrer-Policy: no-referrer`
+    /// - `Permissions-Policy: camera=(), microphone=(), geolocation=(), payment=(), usb=()`
+    /// - `Cross-Origin-Embedder-Policy: require-corp`
+    /// - `X-Frame-Options: DENY`
+    pub fn strict() -> Self {
+        Self::new()
+            .strict_transport_security("max-age=63072000; includeSubDomains; preload")
+            .content_security_policy(
+                "default-src 'self'; base-uri 'none'; object-src 'none'; frame-ancestors 'none'; \
+                 form-action 'self'; script-src 'nonce-{nonce}' 'strict-dynamic'",
+            )
+            .referrer_policy("no-referrer")
+            .permissions_policy("camera=(), microphone=(), geolocation=(), payment=(), usb=()")
+            .cross_origin_embedder_policy("require-corp")
+            .x_frame_options("DENY")
+    }
+
+    /// 创建一个不设置任何标头的配置。
+    pub fn empty() -> Self {
+        Self {
+            headers: Vec::new(),
+            csp: None,
+        }
+    }
+
+    /// 设置`Strict-Transport-Security`标头，[`None`]表示不设置。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果值不是有效的标头值，将会发生恐慌。
+    pub fn strict_transport_security<'a>(self, value: impl Into<Option<&'a str>>) -> Self {
+        self.header(STRICT_TRANSPORT_SECURITY, value.into())
+    }
+
+    /// 设置`Content-Security-Policy`标头，[`None`]表示不设置。
+    ///
+    /// 策略中的[`{nonce}`](NONCE_PLACEHOLDER)会被替换为每个请求生成的随机数。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果值不是有效的标头值，将会发生恐慌。
+    pub fn content_security_policy<'a>(mut self, policy: impl Into<Option<&'a str>>) -> Self {
+        self.csp = policy.into().map(|policy| {
+            // 提前检查策略，避免在处理请求时才发现错误。
+            HeaderValue::from_str(&policy.replace(NONCE_PLACEHOLDER, "")).unwrap();
+
+            Csp {
+                header: self
+                    .csp
+                    .as_ref()
+                    .map_or(CONTENT_SECURITY_POLICY, |csp| csp.header.clone()),
+                policy: policy.to_owned(),
+            }
+        });
+        self
+    }
+
+    /// 设置是否只报告而不执行内容安全策略（使用`Content-Security-Policy-Report-Only`标头），默认为`false`。
+    ///
+    /// 没有设置内容安全策略时，此设置不起作用。
+    pub fn content_security_policy_report_only(mut self, report_only: bool) -> Self {
+        if let Some(csp) = &mut self.csp {
+            csp.header = if report_only {
+                CONTENT_SECURITY_POLICY_REPORT_ONLY
+            } else {
+                CONTENT_SECURITY_POLICY
+            };
+        }
+        self
+    }
+
+    /// 设置`X-Content-Type-Options`标头，[`None`]表示不设置。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果值不是有效的标头值，将会发生恐慌。
+    pub fn x_content_type_options<'a>(self, value: impl Into<Option<&'a str>>) -> Self {
+        self.header(X_CONTENT_TYPE_OPTIONS, value.into())
+    }
+
+    /// 设置`Referrer-Policy`标头，[`None`]表示不设置。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果值不是有效的标头值，将会发生恐慌。
+    pub fn referrer_policy<'a>(self, value: impl Into<Option<&'a str>>) -> Self {
+        self.header(REFERRER_POLICY, value.into())
+    }
+
+    /// 设置`Permissions-Policy`标头，[`None`]表示不设置。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果值不是有效的标头值，将会发生恐慌。
+    pub fn permissions_policy<'a>(self, value: impl Into<Option<&'a str>>) -> Self {
+        self.header(PERMISSIONS_POLICY, value.into())
+    }
+
+    /// 设置`Cross-Origin-Opener-Policy`标头，[`None`]表示不设置。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果值不是有效的标头值，将会发生恐慌。
+    pub fn cross_origin_opener_policy<'a>(self, value: impl Into<Option<&'a str>>) -> Self {
+        self.header(CROSS_ORIGIN_OPENER_POLICY, value.into())
+    }
+
+    /// 设置`Cross-Origin-Embedder-Policy`标头，[`None`]表示不设置。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果值不是有效的标头值，将会发生恐慌。
+    pub fn cross_origin_embedder_policy<'a>(self, value: impl Into<Option<&'a str>>) -> Self {
+        self.header(CROSS_ORIGIN_EMBEDDER_POLICY, value.into())
+    }
+
+    /// 设置`X-Frame-Options`标头，[`None`]表示不设置。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果值不是有效的标头值，将会发生恐慌。
+    pub fn x_frame_options<'a>(self, value: impl Into<Option<&'a str>>) -> Self {
+        self.header(X_FRAME_OPTIONS, value.into())
+    }
+
+    fn header(mut self, name: HeaderName, value: Option<&str>) -> Self {
+        self.headers.retain(|(n, _)| *n != name);
+        if let Some(value) = value {
+            self.headers
+                .push((name, HeaderValue::from_str(value).unwrap()));
+        }
+        self
+    }
+}
+
+impl Default for SecurityHeadersWrap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Wrap<S> for SecurityHeadersWrap {
+    type Service = SecurityHeaders<S>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        SecurityHeaders {
+            inner: service,
+            headers: self.headers.into(),
+            csp: self.csp.map(Arc::new),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SecurityHeaders<S> {
+    inner: S,
+    headers: Arc<[(HeaderName, HeaderValue)]>,
+    csp: Option<Arc<Csp>>,
+}
+
+impl<S, B> Service<Request<B>> for SecurityHeaders<S>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = SecurityHeadersFuture<S::Future>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, mut request: Request<B>) -> Self::Future {
+        let csp = self.csp.as_ref().map(|csp| {
+            let policy = if csp.policy.contains(NONCE_PLACEHOLDER) {
+                let nonce = CspNonce::generate();
+                let policy = csp.policy.replace(NONCE_PLACEHOLDER, nonce.as_str());
+                request.extensions_mut().insert(nonce);
+                policy
+            } else {
+                csp.policy.clone()
+            };
+
+            // 策略在设置时已经检查过，随机数只包含Base64字符。
+            (csp.header.clone(), HeaderValue::try_from(policy).unwrap())
+        });
+
+        SecurityHeadersFuture {
+            fut: self.inner.call(request),
+            headers: self.headers.clone(),
+            csp,
+        }
+    }
+}
+
+impl<S> fmt::Debug for SecurityHeaders<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecurityHeaders")
+            .field("inner", &self.inner)
+            .field("headers", &self.headers)
+            .field("csp", &self.csp)
+            .finish()
+    }
+}
+
+pin_project! {
+    pub struct SecurityHeadersFuture<Fut> {
+        #[pin]
+        fut: Fut,
+        headers: Arc<[(HeaderName, HeaderValue)]>,
+        csp: Option<(HeaderName, HeaderValue)>,
+    }
+}
+
+impl<Fut, Res, Err> Future for SecurityHeadersFuture<Fut>
+where
+    Fut: Future<Output = Result<Res, Err>>,
+    Res: IntoResponse,
+{
+    type Output = Result<Response, Err>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let mut response = ready!(this.fut.poll(cx))?.into_response();
+        let headers = response.headers_mut();
+
+        for (name, value) in this.headers.iter().chain(this.csp.as_ref()) {
+            if !headers.contains_key(name) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+
+        Poll::Ready(Ok(response))
+    }
+}
+
+impl<Fut> fmt::Debug for SecurityHeadersFuture<Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecurityHeadersFuture").finish()
+    }
+}
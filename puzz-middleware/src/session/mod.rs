@@ -0,0 +1,575 @@
+//! 会话。
+//!
+//! [`session`]根据会话ID Cookie从[`SessionStore`]加载会话，并以[`Session`]的形式插入请求扩展。
+//! 处理请求后，只有会话发生了变化才会保存到存储中。
+//!
+//! 会话在[空闲超时](SessionWrap::idle_timeout)后过期。在权限发生变化（例如登录）时，
+//! 应调用[`Session::rotate`]更换会话ID，以防止会话固定攻击。
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use cookie::Cookie;
+use futures_core::ready;
+use pin_project_lite::pin_project;
+use puzz_core::http::header::{COOKIE, SET_COOKIE};
+use puzz_core::http::{HeaderValue, StatusCode};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::{BoxError, Request, Response};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::BoxFuture;
+
+pub mod store;
+pub use cookie::SameSite;
+pub use store::{FileStore, MemoryStore, SessionRecord, SessionStore};
+
+const DEFAULT_COOKIE_NAME: &str = "sid";
+
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// 创建一个使用`store`保存会话的[`Wrap`]。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::service::{Service, ServiceExt};
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::session::{session, MemoryStore, Session};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let store = MemoryStore::new();
+///
+/// let service = service_fn(|request: Request| async move {
+///     let session = request.extensions().get::<Session>().unwrap();
+///     let visits = session.get::<u32>("visits").unwrap_or(0) + 1;
+///     session.insert("visits", visits).unwrap();
+///     Ok::<_, Infallible>(visits.to_string())
+/// })
+/// .with(session(store.clone()).secure(false));
+///
+/// let response = service.call(Request::default()).await.unwrap();
+/// let cookie = response.headers()["set-cookie"].to_str().unwrap();
+/// assert!(cookie.starts_with("sid="));
+/// assert_eq!(store.len(), 1);
+///
+/// let request = Request::builder()
+///     .header("cookie", cookie.split(';').next().unwrap())
+///     .body(Default::default())
+///     .unwrap();
+/// let response = service.call(request).await.unwrap();
+/// // 会话ID没有变化，无需再次设置Cookie。
+/// assert!(response.headers().get("set-cookie").is_none());
+/// # }
+/// ```
+pub fn session<St>(store: St) -> SessionWrap<St> {
+    SessionWrap::new(store)
+}
+
+/// 当前请求的会话。
+///
+/// 会话可以廉价地克隆，克隆后的会话共享相同的数据。
+#[derive(Clone)]
+pub struct Session {
+    inner: Arc<Mutex<SessionInner>>,
+}
+
+struct SessionInner {
+    id: Option<String>,
+    data: HashMap<String, Value>,
+    expires_at: Option<SystemTime>,
+    status: Status,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Unchanged,
+    Changed,
+    Rotated,
+    Destroyed,
+}
+
+impl Session {
+    fn new(id: Option<String>, record: Option<SessionRecord>) -> Self {
+        let (data, expires_at) = match record {
+            Some(record) => (record.data, Some(record.expires_at)),
+            None => (HashMap::new(), None),
+        };
+
+        Self {
+            inner: Arc::new(Mutex::new(SessionInner {
+                id: expires_at.and(id),
+                data,
+                expires_at,
+                status: Status::Unchanged,
+            })),
+        }
+    }
+
+    /// 获取会话ID，新的会话在保存前没有ID。
+    pub fn id(&self) -> Option<String> {
+        self.inner.lock().unwrap().id.clone()
+    }
+
+    /// 获取并反序列化给定键的值。
+    pub fn get<T>(&self, key: &str) -> Option<T>
+    where
+        T: DeserializeOwned,
+    {
+        let inner = self.inner.lock().unwrap();
+        serde_json::from_value(inner.data.get(key)?.clone()).ok()
+    }
+
+    /// 序列化并插入给定键的值。
+    pub fn insert<T>(&self, key: impl Into<String>, value: T) -> Result<(), serde_json::Error>
+    where
+        T: Serialize,
+    {
+        let key = key.into();
+        let value = serde_json::to_value(value)?;
+        let mut inner = self.inner.lock().unwrap();
+        if inner.data.get(&key) != Some(&value) {
+            inner.data.insert(key, value);
+            inner.mark(Status::Changed);
+        }
+        Ok(())
+    }
+
+    /// 删除并返回给定键的值。
+    pub fn remove<T>(&self, key: &str) -> Option<T>
+    where
+        T: DeserializeOwned,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        let value = inner.data.remove(key)?;
+        inner.mark(Status::Changed);
+        serde_json::from_value(value).ok()
+    }
+
+    /// 清空会话数据。
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.data.is_empty() {
+            inner.data.clear();
+            inner.mark(Status::Changed);
+        }
+    }
+
+    /// 会话数据是否为空。
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().unwrap().data.is_empty()
+    }
+
+    /// 保留会话数据并更换会话ID。
+    ///
+    /// 应在权限发生变化（例如登录或提升权限）时调用，旧的会话ID会立即失效。
+    pub fn rotate(&self) {
+        self.inner.lock().unwrap().mark(Status::Rotated);
+    }
+
+    /// 销毁会话，删除存储中的会话并使Cookie过期。
+    pub fn destroy(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.data.clear();
+        inner.status = Status::Destroyed;
+    }
+}
+
+impl SessionInner {
+    fn mark(&mut self, status: Status) {
+        // 销毁后再写入的数据需要使用新的会话ID。
+        self.status = match (self.status, status) {
+            (Status::Destroyed | Status::Rotated, _) | (_, Status::Rotated) => Status::Rotated,
+            _ => status,
+        };
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock().unwrap();
+        f.debug_struct("Session")
+            .field("id", &inner.id.as_ref().map(|_| ".."))
+            .field("data", &inner.data)
+            .finish()
+    }
+}
+
+#[derive(Clone)]
+pub struct SessionWrap<St> {
+    store: St,
+    config: Config,
+}
+
+#[derive(Debug, Clone)]
+struct Config {
+    cookie_name: String,
+    idle_timeout: Duration,
+    secure: bool,
+    same_site: SameSite,
+    path: String,
+    domain: Option<String>,
+}
+
+impl<St> SessionWrap<St> {
+    pub fn new(store: St) -> Self {
+        Self {
+            store,
+            config: Config {
+                cookie_name: DEFAULT_COOKIE_NAME.to_owned(),
+                idle_timeout: DEFAULT_IDLE_TIMEOUT,
+                secure: true,
+                same_site: SameSite::Lax,
+                path: "/".to_owned(),
+                domain: None,
+            },
+        }
+    }
+
+    /// 设置会话ID Cookie的名称，默认为`sid`。
+    pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.config.cookie_name = name.into();
+        self
+    }
+
+    /// 设置空闲超时，会话在此期间没有被访问将会过期，默认为`30`分钟。
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.config.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// 设置Cookie的`Secure`属性，默认为`true`。
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.config.secure = secure;
+        self
+    }
+
+    /// 设置Cookie的`SameSite`属性，默认为[`SameSite::Lax`]。
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.config.same_site = same_site;
+        self
+    }
+
+    /// 设置Cookie的`Path`属性，默认为`/`。
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.config.path = path.into();
+        self
+    }
+
+    /// 设置Cookie的`Domain`属性。
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.config.domain = Some(domain.into());
+        self
+    }
+}
+
+impl<S, St> Wrap<S> for SessionWrap<St> {
+    type Service = SessionService<S, St>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        SessionService {
+            inner: Arc::new(service),
+            shared: Arc::new(Shared {
+                store: self.store,
+                config: self.config,
+            }),
+        }
+    }
+}
+
+impl<St> fmt::Debug for SessionWrap<St>
+where
+    St: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionWrap")
+            .field("store", &self.store)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+struct Shared<St> {
+    store: St,
+    config: Config,
+}
+
+impl<St> Shared<St>
+where
+    St: SessionStore,
+{
+    fn session_id<B>(&self, request: &Request<B>) -> Option<String> {
+        request
+            .headers()
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(Cookie::split_parse)
+            .filter_map(Result::ok)
+            .find(|cookie| cookie.name() == self.config.cookie_name)
+            .map(|cookie| cookie.value().to_owned())
+            .filter(|id| !id.is_empty())
+    }
+
+    fn cookie(&self, id: String) -> Cookie<'static> {
+        let mut cookie = Cookie::build((self.config.cookie_name.clone(), id))
+            .path(self.config.path.clone())
+            .http_only(true)
+            .secure(self.config.secure)
+            .same_site(self.config.same_site);
+        if let Some(domain) = &self.config.domain {
+            cookie = cookie.domain(domain.clone());
+        }
+        cookie.build()
+    }
+
+    /// 根据会话的变化生成存储操作和需要设置的Cookie。
+    fn commit(&self, session: &Session) -> Option<(SaveFuture, Option<Cookie<'static>>)> {
+        let inner = session.inner.lock().unwrap();
+        let now = SystemTime::now();
+        let idle_timeout = self.config.idle_timeout;
+        let record = || SessionRecord {
+            data: inner.data.clone(),
+            expires_at: now + idle_timeout,
+        };
+
+        let removal = || {
+            let mut cookie = self.cookie(String::new());
+            cookie.make_removal();
+            Some(cookie)
+        };
+
+        let (delete, save, cookie) = match (inner.status, &inner.id) {
+            (Status::Unchanged, Some(id)) => {
+                // 剩余时间不足空闲超时的一半时才延长过期时间，避免每个请求都写入存储。
+                let remaining = inner
+                    .expires_at
+                    .and_then(|expires_at| expires_at.duration_since(now).ok())
+                    .unwrap_or_default();
+                if remaining >= idle_timeout / 2 {
+                    return None;
+                }
+                (None, Some(id.clone()), None)
+            }
+            (Status::Unchanged, None) => return None,
+            (_, None) if inner.data.is_empty() => return None,
+            (_, Some(id)) if inner.status == Status::Destroyed || inner.data.is_empty() => {
+                (Some(id.clone()), None, removal())
+            }
+            (Status::Changed, Some(id)) => (None, Some(id.clone()), None),
+            (_, id) => {
+                let new_id = generate_id();
+                let cookie = Some(self.cookie(new_id.clone()));
+                (id.clone(), Some(new_id), cookie)
+            }
+        };
+
+        let delete = delete.map(|id| self.store.delete(&id));
+        let save = save.map(|id| self.store.save(&id, &record()));
+
+        let fut = Box::pin(async move {
+            if let Some(delete) = delete {
+                delete.await?;
+            }
+            if let Some(save) = save {
+                save.await?;
+            }
+            Ok(())
+        });
+
+        Some((fut, cookie))
+    }
+}
+
+type SaveFuture = BoxFuture<Result<(), BoxError>>;
+
+fn generate_id() -> String {
+    let mut buf = uuid::Uuid::encode_buffer();
+    uuid::Uuid::new_v4()
+        .simple()
+        .encode_lower(&mut buf)
+        .to_owned()
+}
+
+pub struct SessionService<S, St> {
+    // 加载会话后才会调用服务。
+    inner: Arc<S>,
+    shared: Arc<Shared<St>>,
+}
+
+impl<S, St> Clone for SessionService<S, St> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<S, St, B> Service<Request<B>> for SessionService<S, St>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+    St: SessionStore,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = SessionFuture<S, Request<B>, St>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Request<B>) -> Self::Future {
+        match self.shared.session_id(&request) {
+            Some(id) => SessionFuture::Loading {
+                fut: self.shared.store.load(&id),
+                state: Some((self.inner.clone(), request, id)),
+                shared: self.shared.clone(),
+            },
+            None => SessionFuture::call(
+                &*self.inner,
+                request,
+                Session::new(None, None),
+                self.shared.clone(),
+            ),
+        }
+    }
+}
+
+impl<S, St> fmt::Debug for SessionService<S, St>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionService")
+            .field("inner", &self.inner)
+            .field("config", &self.shared.config)
+            .finish()
+    }
+}
+
+pin_project! {
+    #[project = SessionFutureProj]
+    pub enum SessionFuture<S, Req, St>
+    where
+        S: Service<Req>,
+    {
+        Loading {
+            fut: BoxFuture<Result<Option<SessionRecord>, BoxError>>,
+            state: Option<(Arc<S>, Req, String)>,
+            shared: Arc<Shared<St>>,
+        },
+        Calling {
+            #[pin]
+            fut: S::Future,
+            session: Session,
+            shared: Arc<Shared<St>>,
+        },
+        Saving {
+            fut: SaveFuture,
+            response: Option<Response>,
+        },
+    }
+}
+
+impl<S, B, St> SessionFuture<S, Request<B>, St>
+where
+    S: Service<Request<B>>,
+{
+    fn call(inner: &S, mut request: Request<B>, session: Session, shared: Arc<Shared<St>>) -> Self {
+        request.extensions_mut().insert(session.clone());
+        Self::Calling {
+            fut: inner.call(request),
+            session,
+            shared,
+        }
+    }
+}
+
+impl<S, B, St> Future for SessionFuture<S, Request<B>, St>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+    St: SessionStore,
+{
+    type Output = Result<Response, S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                SessionFutureProj::Loading { fut, state, shared } => {
+                    let record = match ready!(fut.as_mut().poll(cx)) {
+                        Ok(record) => record.filter(|record| !record.is_expired()),
+                        Err(_) => {
+                            return Poll::Ready(Ok(
+                                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                            ))
+                        }
+                    };
+                    let (inner, request, id) = state.take().expect("polled after completion");
+                    let shared = shared.clone();
+
+                    self.set(SessionFuture::call(
+                        &*inner,
+                        request,
+                        Session::new(Some(id), record),
+                        shared,
+                    ));
+                }
+                SessionFutureProj::Calling {
+                    fut,
+                    session,
+                    shared,
+                } => {
+                    let mut response = ready!(fut.poll(cx))?.into_response();
+
+                    let (fut, cookie) = match shared.commit(session) {
+                        Some(commit) => commit,
+                        None => return Poll::Ready(Ok(response)),
+                    };
+
+                    if let Some(value) =
+                        cookie.and_then(|cookie| HeaderValue::try_from(cookie.to_string()).ok())
+                    {
+                        response.headers_mut().append(SET_COOKIE, value);
+                    }
+
+                    self.set(SessionFuture::Saving {
+                        fut,
+                        response: Some(response),
+                    });
+                }
+                SessionFutureProj::Saving { fut, response } => {
+                    let result = ready!(fut.as_mut().poll(cx));
+                    let response = response.take().expect("polled after completion");
+
+                    return Poll::Ready(Ok(match result {
+                        Ok(()) => response,
+                        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                    }));
+                }
+            }
+        }
+    }
+}
+
+impl<S, Req, St> fmt::Debug for SessionFuture<S, Req, St>
+where
+    S: Service<Req>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionFuture").finish()
+    }
+}
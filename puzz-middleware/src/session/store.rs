@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::ready;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use puzz_core::BoxError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::BoxFuture;
+
+/// 保存在存储中的会话记录。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    /// 会话数据。
+    pub data: HashMap<String, Value>,
+    /// 会话的过期时间。
+    pub expires_at: SystemTime,
+}
+
+impl SessionRecord {
+    /// 会话是否已经过期。
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= SystemTime::now()
+    }
+}
+
+/// 保存会话的存储。
+///
+/// 实现此特征即可使用其它存储后端（例如Redis），存储可以根据[`SessionRecord::expires_at`]设置过期时间。
+pub trait SessionStore {
+    /// 加载会话，如果会话不存在则返回[`None`]。
+    fn load(&self, id: &str) -> BoxFuture<Result<Option<SessionRecord>, BoxError>>;
+
+    /// 保存会话。
+    fn save(&self, id: &str, record: &SessionRecord) -> BoxFuture<Result<(), BoxError>>;
+
+    /// 删除会话。
+    fn delete(&self, id: &str) -> BoxFuture<Result<(), BoxError>>;
+}
+
+/// 保存在内存中的会话。
+///
+/// 过期的会话会被定期清除。存储可以廉价地克隆，克隆后的存储共享相同的会话。
+#[derive(Clone)]
+pub struct MemoryStore {
+    inner: Arc<Mutex<MemoryState>>,
+}
+
+struct MemoryState {
+    records: HashMap<String, SessionRecord>,
+    last_sweep: SystemTime,
+}
+
+// 清除过期会话的间隔。
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+impl MemoryStore {
+    /// 创建一个空的存储。
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(MemoryState {
+                records: HashMap::new(),
+                last_sweep: SystemTime::now(),
+            })),
+        }
+    }
+
+    /// 获取存储中会话的数量。
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().records.len()
+    }
+
+    /// 存储是否为空。
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for MemoryStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryStore").finish()
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> BoxFuture<Result<Option<SessionRecord>, BoxError>> {
+        let mut state = self.inner.lock().unwrap();
+        let record = match state.records.get(id) {
+            Some(record) if record.is_expired() => {
+                state.records.remove(id);
+                None
+            }
+            record => record.cloned(),
+        };
+        Box::pin(ready(Ok(record)))
+    }
+
+    fn save(&self, id: &str, record: &SessionRecord) -> BoxFuture<Result<(), BoxError>> {
+        let mut state = self.inner.lock().unwrap();
+        let now = SystemTime::now();
+
+        if now
+            .duration_since(state.last_sweep)
+            .is_ok_and(|elapsed| elapsed >= SWEEP_INTERVAL)
+        {
+            state.last_sweep = now;
+            state.records.retain(|_, record| !record.is_expired());
+        }
+
+        state.records.insert(id.to_owned(), record.clone());
+        Box::pin(ready(Ok(())))
+    }
+
+    fn delete(&self, id: &str) -> BoxFuture<Result<(), BoxError>> {
+        self.inner.lock().unwrap().records.remove(id);
+        Box::pin(ready(Ok(())))
+    }
+}
+
+/// 以JSON文件的形式保存在目录中的会话。
+///
+/// 每个会话保存为一个名为`<会话ID>.json`的文件。保存时先写入同一目录中的临时文件，再重命名为会话文件，
+/// 因此同时加载会话时不会读到写了一半的文件。
+///
+/// 过期的会话在加载时删除，保存会话时也会定期清除目录中过期的会话文件和遗留的临时文件。
+/// 存储可以廉价地克隆，克隆后的存储共享清除的时间。
+///
+/// # 例子
+///
+/// ```
+/// use std::collections::HashMap;
+/// use std::time::{Duration, SystemTime};
+///
+/// use puzz_middleware::session::{FileStore, SessionRecord, SessionStore};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let dir = std::env::temp_dir().join(format!("puzz-sessions-{}", std::process::id()));
+/// let store = FileStore::new(&dir);
+///
+/// let record = SessionRecord {
+///     data: HashMap::new(),
+///     expires_at: SystemTime::now() + Duration::from_secs(60),
+/// };
+/// store.save("abc", &record).await.unwrap();
+/// assert_eq!(store.load("abc").await.unwrap(), Some(record));
+///
+/// // 临时文件已经被重命名为会话文件。
+/// let names: Vec<_> = std::fs::read_dir(&dir)
+///     .unwrap()
+///     .map(|entry| entry.unwrap().file_name())
+///     .collect();
+/// assert_eq!(names, ["abc.json"]);
+///
+/// std::fs::remove_dir_all(&dir).unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: Arc<PathBuf>,
+    last_sweep: Arc<Mutex<SystemTime>>,
+}
+
+impl FileStore {
+    /// 使用给定的目录创建存储，目录不存在时会在首次保存会话时创建。
+    pub fn new<P>(dir: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            dir: Arc::new(dir.as_ref().to_owned()),
+            last_sweep: Arc::new(Mutex::new(SystemTime::now())),
+        }
+    }
+
+    fn path(&self, id: &str) -> Option<PathBuf> {
+        // 会话ID来自客户端，需要防止路径穿越。
+        if id.is_empty() || !id.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(self.dir.join(format!("{}.json", id)))
+    }
+
+    fn should_sweep(&self) -> bool {
+        let mut last_sweep = self.last_sweep.lock().unwrap();
+        let now = SystemTime::now();
+
+        if now
+            .duration_since(*last_sweep)
+            .is_ok_and(|elapsed| elapsed >= SWEEP_INTERVAL)
+        {
+            *last_sweep = now;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl SessionStore for FileStore {
+    fn load(&self, id: &str) -> BoxFuture<Result<Option<SessionRecord>, BoxError>> {
+        let path = self.path(id);
+
+        Box::pin(async move {
+            let path = match path {
+                Some(path) => path,
+                None => return Ok(None),
+            };
+
+            let bytes = match tokio::fs::read(&path).await {
+                Ok(bytes) => bytes,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+
+            let record = serde_json::from_slice::<SessionRecord>(&bytes)?;
+            if record.is_expired() {
+                remove_file(&path).await?;
+                return Ok(None);
+            }
+
+            Ok(Some(record))
+        })
+    }
+
+    fn save(&self, id: &str, record: &SessionRecord) -> BoxFuture<Result<(), BoxError>> {
+        let path = self.path(id);
+        let dir = self.dir.clone();
+        let bytes = serde_json::to_vec(record);
+        let sweep = self.should_sweep();
+
+        Box::pin(async move {
+            let path = path.ok_or("invalid session id")?;
+            let bytes = bytes?;
+            tokio::fs::create_dir_all(&*dir).await?;
+
+            if sweep {
+                // 清除失败不影响保存，下次清除时会重试。
+                let _ = sweep_dir(&dir).await;
+            }
+
+            let mut buf = uuid::Uuid::encode_buffer();
+            let tmp = dir.join(format!(
+                ".{}.tmp",
+                uuid::Uuid::new_v4().simple().encode_lower(&mut buf)
+            ));
+
+            if let Err(e) = tokio::fs::write(&tmp, bytes).await {
+                let _ = tokio::fs::remove_file(&tmp).await;
+                return Err(e.into());
+            }
+            if let Err(e) = tokio::fs::rename(&tmp, &path).await {
+                let _ = tokio::fs::remove_file(&tmp).await;
+                return Err(e.into());
+            }
+            Ok(())
+        })
+    }
+
+    fn delete(&self, id: &str) -> BoxFuture<Result<(), BoxError>> {
+        let path = self.path(id);
+
+        Box::pin(async move {
+            match path {
+                Some(path) => remove_file(&path).await,
+                None => Ok(()),
+            }
+        })
+    }
+}
+
+// 删除过期的会话文件，以及保存中断后遗留超过清除间隔的临时文件。
+async fn sweep_dir(dir: &Path) -> io::Result<()> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+
+        if name.starts_with('.') && name.ends_with(".tmp") {
+            let stale = entry
+                .metadata()
+                .await
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| {
+                    modified
+                        .elapsed()
+                        .is_ok_and(|elapsed| elapsed >= SWEEP_INTERVAL)
+                });
+            if stale {
+                let _ = remove_file(&path).await;
+            }
+        } else if name.ends_with(".json") {
+            let expired = match tokio::fs::read(&path).await {
+                Ok(bytes) => serde_json::from_slice::<SessionRecord>(&bytes)
+                    .is_ok_and(|record| record.is_expired()),
+                Err(_) => false,
+            };
+            if expired {
+                let _ = remove_file(&path).await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn remove_file(path: &Path) -> Result<(), BoxError> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
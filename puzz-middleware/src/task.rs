@@ -0,0 +1,30 @@
+//! 在后台运行任务。
+
+use std::future::Future;
+
+use puzz_core::http::marker::MaybeSend;
+
+/// 当前线程是否在Tokio运行时中，可以调用[`spawn`]。
+pub(crate) fn can_spawn() -> bool {
+    tokio::runtime::Handle::try_current().is_ok()
+}
+
+/// 在后台运行任务，不在Tokio运行时中时将任务返回给调用者。
+///
+/// 启用`send`特性时使用[`tokio::spawn`]；否则任务可能未实现[`Send`]，使用[`tokio::task::spawn_local`]，
+/// 因此需要在[`LocalSet`](tokio::task::LocalSet)中调用（服务器的工作线程已经满足这一点）。
+pub(crate) fn spawn<F>(fut: F) -> Result<(), F>
+where
+    F: Future<Output = ()> + MaybeSend + 'static,
+{
+    if !can_spawn() {
+        return Err(fut);
+    }
+
+    #[cfg(feature = "send")]
+    tokio::spawn(fut);
+    #[cfg(not(feature = "send"))]
+    tokio::task::spawn_local(fut);
+
+    Ok(())
+}
@@ -0,0 +1,602 @@
+//! 请求追踪与访问日志。
+//!
+//! [`trace`]基于[`tracing`]为每个请求创建一个span，[`access_log`]将访问日志以Apache组合格式或JSON行写入任意的[`io::Write`]。
+//!
+//! 两者都会在响应正文发送完毕（而不是响应头部发送完毕）时记录状态码、延迟和正文字节数。
+
+use std::fmt::{self, Write as _};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use futures_core::ready;
+use pin_project_lite::pin_project;
+use puzz_core::body::BodyExt;
+use puzz_core::http::marker::MaybeSend;
+use puzz_core::http::{header, Method, StatusCode, Uri, Version};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, Wrap};
+use puzz_core::{Request, Response};
+use puzz_route::MatchedPath;
+use puzz_server::PeerAddr;
+use tracing::field::Empty;
+use tracing::instrument::{Instrument, Instrumented};
+use tracing::Span;
+
+use crate::body::{BodyEnd, OnEndBody};
+
+/// 创建一个追踪请求的[`Wrap`]。
+///
+/// 每个请求都会创建一个名为`request`的span，包含以下字段：
+///
+/// - `method`：请求的方法
+/// - `uri`：请求的URI
+/// - `version`：请求的HTTP版本
+/// - `route`：路由器匹配到的路由（见[`MatchedPath`]）
+/// - `peer_addr`：对端地址（见[`PeerAddr`]）
+/// - `request_id`：请求ID（需要启用`request-id`特性）
+/// - `status`：响应的状态码
+/// - `latency_ms`：从收到请求到响应正文发送完毕的毫秒数
+/// - `body_bytes`：响应正文的字节数
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::service::ServiceExt;
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::trace::trace;
+///
+/// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") }).with(trace());
+/// ```
+pub fn trace() -> TraceWrap {
+    TraceWrap::new()
+}
+
+/// 创建一个将访问日志写入`writer`的[`Wrap`]。
+///
+/// 默认使用Apache组合日志格式，可以使用[`AccessLogWrap::format`]修改。
+///
+/// # 例子
+///
+/// ```
+/// use std::convert::Infallible;
+///
+/// use puzz_core::service::ServiceExt;
+/// use puzz_core::{service_fn, Request};
+/// use puzz_middleware::trace::{access_log, LogFormat};
+///
+/// let service = service_fn(|_: Request| async { Ok::<_, Infallible>("hi!") })
+///     .with(access_log(std::io::stdout()).format(LogFormat::Json));
+/// ```
+pub fn access_log<W>(writer: W) -> AccessLogWrap<W>
+where
+    W: io::Write,
+{
+    AccessLogWrap::new(writer)
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TraceWrap {
+    _priv: (),
+}
+
+impl TraceWrap {
+    pub fn new() -> Self {
+        Self { _priv: () }
+    }
+}
+
+impl<S> Wrap<S> for TraceWrap {
+    type Service = Trace<S>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        Trace { inner: service }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Trace<S> {
+    inner: S,
+}
+
+impl<S, B> Service<Request<B>> for Trace<S>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+    S::Error: fmt::Display,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = TraceFuture<S::Future>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Request<B>) -> Self::Future {
+        let span = tracing::info_span!(
+            "request",
+            method = %request.method(),
+            uri = %request.uri(),
+            version = ?request.version(),
+            route = Empty,
+            peer_addr = Empty,
+            request_id = Empty,
+            status = Empty,
+            latency_ms = Empty,
+            body_bytes = Empty,
+        );
+
+        if let Some(matched_path) = request.extensions().get::<MatchedPath>() {
+            span.record("route", matched_path.as_str());
+        }
+        if let Some(peer_addr) = request.extensions().get::<PeerAddr>() {
+            span.record("peer_addr", tracing::field::display(peer_addr));
+        }
+        #[cfg(feature = "request-id")]
+        if let Some(id) = request.extensions().get::<crate::request_id::RequestId>() {
+            span.record("request_id", id.as_str());
+        }
+
+        let start = Instant::now();
+        let fut = span.in_scope(|| self.inner.call(request));
+
+        TraceFuture {
+            fut: fut.instrument(span.clone()),
+            span,
+            start,
+        }
+    }
+}
+
+pin_project! {
+    pub struct TraceFuture<Fut> {
+        #[pin]
+        fut: Instrumented<Fut>,
+        span: Span,
+        start: Instant,
+    }
+}
+
+impl<Fut, Res, Err> Future for TraceFuture<Fut>
+where
+    Fut: Future<Output = Result<Res, Err>>,
+    Res: IntoResponse,
+    Err: fmt::Display,
+{
+    type Output = Result<Response, Err>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let span = this.span.clone();
+        let start = *this.start;
+
+        let response = match ready!(this.fut.poll(cx)) {
+            Ok(response) => response.into_response(),
+            Err(err) => {
+                let latency_ms = latency_ms(start);
+                span.record("latency_ms", latency_ms);
+                tracing::error!(parent: &span, error = %err, latency_ms, "failed to process request");
+                return Poll::Ready(Err(err));
+            }
+        };
+
+        let status = response.status();
+        span.record("status", status.as_u16());
+        if let Some(matched_path) = response.extensions().get::<MatchedPath>() {
+            span.record("route", matched_path.as_str());
+        }
+
+        Poll::Ready(Ok(response.map(|body| {
+            OnEndBody::new(body, move |body_bytes, end| {
+                let latency_ms = latency_ms(start);
+                span.record("latency_ms", latency_ms);
+                span.record("body_bytes", body_bytes);
+                match end {
+                    BodyEnd::Complete => tracing::info!(
+                        parent: &span,
+                        status = status.as_u16(),
+                        latency_ms,
+                        body_bytes,
+                        "finished processing request"
+                    ),
+                    BodyEnd::Error(err) => tracing::error!(
+                        parent: &span,
+                        status = status.as_u16(),
+                        latency_ms,
+                        body_bytes,
+                        error = %err,
+                        "response body failed"
+                    ),
+                    BodyEnd::Dropped => tracing::warn!(
+                        parent: &span,
+                        status = status.as_u16(),
+                        latency_ms,
+                        body_bytes,
+                        "response body dropped before completion"
+                    ),
+                }
+            })
+            .boxed()
+        })))
+    }
+}
+
+impl<Fut> fmt::Debug for TraceFuture<Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraceFuture")
+            .field("span", &self.span)
+            .field("start", &self.start)
+            .finish()
+    }
+}
+
+fn latency_ms(start: Instant) -> f64 {
+    start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// 访问日志的格式。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Apache组合日志格式。
+    ///
+    /// ```text
+    /// 127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /index.html HTTP/1.1" 200 2326 "-" "curl/7.79.1"
+    /// ```
+    #[default]
+    Combined,
+    /// 每行一个JSON对象。
+    Json,
+}
+
+pub struct AccessLogWrap<W> {
+    writer: Arc<Mutex<W>>,
+    format: LogFormat,
+}
+
+impl<W> AccessLogWrap<W>
+where
+    W: io::Write,
+{
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(writer)),
+            format: LogFormat::default(),
+        }
+    }
+
+    /// 设置访问日志的格式。
+    pub fn format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+impl<W> Clone for AccessLogWrap<W> {
+    fn clone(&self) -> Self {
+        Self {
+            writer: self.writer.clone(),
+            format: self.format,
+        }
+    }
+}
+
+impl<S, W> Wrap<S> for AccessLogWrap<W> {
+    type Service = AccessLog<S, W>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        AccessLog {
+            inner: service,
+            writer: self.writer,
+            format: self.format,
+        }
+    }
+}
+
+impl<W> fmt::Debug for AccessLogWrap<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLogWrap")
+            .field("writer", &std::any::type_name::<W>())
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+pub struct AccessLog<S, W> {
+    inner: S,
+    writer: Arc<Mutex<W>>,
+    format: LogFormat,
+}
+
+impl<S, W> Clone for AccessLog<S, W>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            writer: self.writer.clone(),
+            format: self.format,
+        }
+    }
+}
+
+impl<S, W, B> Service<Request<B>> for AccessLog<S, W>
+where
+    S: Service<Request<B>>,
+    S::Response: IntoResponse,
+    W: io::Write + MaybeSend + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = AccessLogFuture<S::Future, W>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Request<B>) -> Self::Future {
+        let header = |name| {
+            request
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToOwned::to_owned)
+        };
+
+        let entry = LogEntry {
+            format: self.format,
+            time: SystemTime::now(),
+            start: Instant::now(),
+            peer_addr: request.extensions().get::<PeerAddr>().copied(),
+            method: request.method().clone(),
+            uri: request.uri().clone(),
+            version: request.version(),
+            referer: header(header::REFERER),
+            user_agent: header(header::USER_AGENT),
+            route: request.extensions().get::<MatchedPath>().cloned(),
+            #[cfg(feature = "request-id")]
+            request_id: request
+                .extensions()
+                .get::<crate::request_id::RequestId>()
+                .cloned(),
+            status: None,
+        };
+
+        AccessLogFuture {
+            fut: self.inner.call(request),
+            log: Some((entry, self.writer.clone())),
+        }
+    }
+}
+
+impl<S, W> fmt::Debug for AccessLog<S, W>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLog")
+            .field("inner", &self.inner)
+            .field("writer", &std::any::type_name::<W>())
+            .field("format", &self.format)
+            .finish()
+    }
+}
+
+pin_project! {
+    pub struct AccessLogFuture<Fut, W> {
+        #[pin]
+        fut: Fut,
+        log: Option<(LogEntry, Arc<Mutex<W>>)>,
+    }
+}
+
+impl<Fut, W, Res, Err> Future for AccessLogFuture<Fut, W>
+where
+    Fut: Future<Output = Result<Res, Err>>,
+    Res: IntoResponse,
+    W: io::Write + MaybeSend + 'static,
+{
+    type Output = Result<Response, Err>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.fut.poll(cx));
+        let (mut entry, writer) = this.log.take().expect("polled after completion");
+
+        let response = match result {
+            Ok(response) => response.into_response(),
+            Err(err) => {
+                entry.write(&writer, 0);
+                return Poll::Ready(Err(err));
+            }
+        };
+
+        entry.status = Some(response.status());
+        if let Some(matched_path) = response.extensions().get::<MatchedPath>() {
+            entry.route = Some(matched_path.clone());
+        }
+
+        Poll::Ready(Ok(response.map(|body| {
+            OnEndBody::new(body, move |bytes, _| entry.write(&writer, bytes)).boxed()
+        })))
+    }
+}
+
+impl<Fut, W> fmt::Debug for AccessLogFuture<Fut, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLogFuture").finish()
+    }
+}
+
+struct LogEntry {
+    format: LogFormat,
+    time: SystemTime,
+    start: Instant,
+    peer_addr: Option<PeerAddr>,
+    method: Method,
+    uri: Uri,
+    version: Version,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    route: Option<MatchedPath>,
+    #[cfg(feature = "request-id")]
+    request_id: Option<crate::request_id::RequestId>,
+    status: Option<StatusCode>,
+}
+
+impl LogEntry {
+    fn write<W>(&self, writer: &Mutex<W>, bytes: u64)
+    where
+        W: io::Write,
+    {
+        let line = match self.format {
+            LogFormat::Combined => self.combined(bytes),
+            LogFormat::Json => self.json(bytes),
+        };
+
+        // 日志写入失败不应影响请求的处理。
+        if let Ok(mut writer) = writer.lock() {
+            let _ = writer.write_all(line.as_bytes());
+        }
+    }
+
+    fn combined(&self, bytes: u64) -> String {
+        let mut line = String::new();
+
+        let _ = write!(
+            line,
+            "{} - - [{}] \"{} {} {:?}\" ",
+            self.peer_addr
+                .map(|addr| addr.0.ip().to_string())
+                .unwrap_or_else(|| "-".into()),
+            DateTime::new(self.time).clf(),
+            self.method,
+            self.uri
+                .path_and_query()
+                .map(|p| p.as_str())
+                .unwrap_or_else(|| self.uri.path()),
+            self.version,
+        );
+        match self.status {
+            Some(status) => {
+                let _ = write!(line, "{} ", status.as_u16());
+            }
+            None => line.push_str("- "),
+        }
+        match bytes {
+            0 => line.push('-'),
+            bytes => {
+                let _ = write!(line, "{}", bytes);
+            }
+        }
+        let _ = writeln!(
+            line,
+            " \"{}\" \"{}\"",
+            escape(self.referer.as_deref().unwrap_or("-")),
+            escape(self.user_agent.as_deref().unwrap_or("-")),
+        );
+
+        line
+    }
+
+    fn json(&self, bytes: u64) -> String {
+        #[cfg_attr(not(feature = "request-id"), allow(unused_mut))]
+        let mut value = serde_json::json!({
+            "time": DateTime::new(self.time).rfc3339(),
+            "remote_addr": self.peer_addr.map(|addr| addr.0.ip().to_string()),
+            "method": self.method.as_str(),
+            "uri": self.uri.to_string(),
+            "version": format!("{:?}", self.version),
+            "route": self.route.as_ref().map(MatchedPath::as_str),
+            "status": self.status.map(|status| status.as_u16()),
+            "bytes": bytes,
+            "latency_ms": latency_ms(self.start),
+            "referer": self.referer,
+            "user_agent": self.user_agent,
+        });
+
+        #[cfg(feature = "request-id")]
+        {
+            value["request_id"] = self.request_id.as_ref().map(|id| id.as_str()).into();
+        }
+
+        let mut line = value.to_string();
+        line.push('\n');
+        line
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.escape_default().to_string()
+}
+
+/// UTC时间。
+struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    millis: u32,
+}
+
+impl DateTime {
+    fn new(time: SystemTime) -> Self {
+        let duration = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = duration.as_secs() as i64;
+        let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (secs / 3600) as u32,
+            minute: (secs % 3600 / 60) as u32,
+            second: (secs % 60) as u32,
+            millis: duration.subsec_millis(),
+        }
+    }
+
+    /// Common Log Format中的时间，例如`10/Oct/2000:13:55:36 +0000`。
+    fn clf(&self) -> String {
+        const MONTHS: [&str; 12] = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+
+        format!(
+            "{:02}/{}/{:04}:{:02}:{:02}:{:02} +0000",
+            self.day,
+            MONTHS[self.month as usize - 1],
+            self.year,
+            self.hour,
+            self.minute,
+            self.second
+        )
+    }
+
+    /// RFC 3339格式的时间，例如`2000-10-10T13:55:36.000Z`。
+    fn rfc3339(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second, self.millis
+        )
+    }
+}
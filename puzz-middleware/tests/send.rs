@@ -0,0 +1,248 @@
+//! 启用`send`特性时，所有中间件包裹的服务都必须能够挂载到[`Router`]上。
+//!
+//! 这里只检查类型，不会调用服务。`retry`要求请求正文为`Bytes`，无法直接挂载，因此不在此列。
+
+#![cfg(feature = "send")]
+#![allow(unused_imports)]
+
+use std::convert::Infallible;
+use std::future::Ready;
+use std::task::{Context, Poll};
+
+use puzz_core::http::marker::{MaybeSend, MaybeSendSync};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, ServiceExt};
+use puzz_core::{service_fn, BoxError, Request};
+use puzz_route::Router;
+
+#[derive(Clone)]
+struct Hello;
+
+impl Service<Request> for Hello {
+    type Response = &'static str;
+    type Error = Infallible;
+    type Future = Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&self, _: Request) -> Self::Future {
+        std::future::ready(Ok("hi!"))
+    }
+}
+
+fn handler() -> Hello {
+    Hello
+}
+
+fn assert_send<S>(_: &S)
+where
+    S: Service<Request> + Send + Sync + 'static,
+    S::Future: Send,
+    S::Response: IntoResponse,
+    S::Error: Into<BoxError>,
+{
+}
+
+#[tokio::test]
+async fn middlewares_are_send() {
+    // `buffer`的工作任务需要在`LocalSet`中启动。
+    let local = tokio::task::LocalSet::new();
+    let _guard = local.enter();
+
+    let service = handler();
+    assert_send(&service);
+    let router = Router::new().route("/", service);
+
+    #[cfg(feature = "auth")]
+    let router = {
+        use puzz_middleware::auth::{self, ApiKey, BasicCredentials};
+
+        let basic = handler().with(auth::basic(|_: BasicCredentials| async { Some(()) }));
+        let bearer = handler().with(auth::bearer(|_: String| async { Some(()) }));
+        let api_key = handler().with(auth::api_key(ApiKey::query("api_key"), |_: String| async {
+            Some(())
+        }));
+        assert_send(&basic);
+        assert_send(&bearer);
+        assert_send(&api_key);
+        router
+            .route("/basic", basic)
+            .route("/bearer", bearer)
+            .route("/api-key", api_key)
+    };
+
+    #[cfg(feature = "buffer")]
+    let router = {
+        use puzz_middleware::buffer::buffer;
+
+        let service = handler().with(buffer(16));
+        assert_send(&service);
+        router.route("/buffer", service)
+    };
+
+    #[cfg(feature = "cache")]
+    let router = {
+        use puzz_middleware::cache::cache;
+
+        let service = handler().with(cache());
+        assert_send(&service);
+        router.route("/cache", service)
+    };
+
+    #[cfg(feature = "catch-panic")]
+    let router = {
+        use puzz_middleware::catch_panic::catch_panic;
+
+        let service = handler().with(catch_panic());
+        assert_send(&service);
+        router.route("/catch-panic", service)
+    };
+
+    #[cfg(feature = "circuit-breaker")]
+    let router = {
+        use puzz_middleware::circuit_breaker::circuit_breaker;
+
+        let service = handler()
+            .map_response(IntoResponse::into_response)
+            .with(circuit_breaker());
+        assert_send(&service);
+        router.route("/circuit-breaker", service)
+    };
+
+    #[cfg(feature = "conditional")]
+    let router = {
+        use puzz_middleware::conditional::{conditional, Validators};
+
+        let service =
+            handler().with(conditional().validators(|_: &Request| async { Validators::default() }));
+        assert_send(&service);
+        router.route("/conditional", service)
+    };
+
+    #[cfg(feature = "csrf")]
+    let router = {
+        use puzz_middleware::csrf::csrf;
+
+        let service = handler().with(csrf().exempt(|_| false));
+        assert_send(&service);
+        router.route("/csrf", service)
+    };
+
+    #[cfg(feature = "forwarded")]
+    let router = {
+        use puzz_middleware::forwarded::forwarded;
+
+        let service = handler().with(forwarded());
+        assert_send(&service);
+        router.route("/forwarded", service)
+    };
+
+    #[cfg(feature = "jwt")]
+    let router = {
+        use puzz_middleware::jwt::{jwt, Jwks, Keys};
+
+        let fetcher = service_fn(|_: Request| async { Ok::<_, Infallible>("{}") });
+        let hs256 = handler().with(jwt::<serde_json::Value>(Keys::hs256("secret")));
+        let jwks = handler().with(jwt::<serde_json::Value>(Jwks::new(
+            "http://auth/jwks.json",
+            fetcher,
+        )));
+        assert_send(&hs256);
+        assert_send(&jwks);
+        router.route("/jwt", hs256).route("/jwks", jwks)
+    };
+
+    #[cfg(feature = "limit")]
+    let router = {
+        use puzz_middleware::limit::{
+            adaptive_concurrency_limit, concurrency_limit, load_shed, Aimd,
+        };
+
+        let limit = handler().with(concurrency_limit(16));
+        let shed = handler().with(load_shed(16));
+        let adaptive = handler().with(adaptive_concurrency_limit(Aimd::new(16)));
+        assert_send(&limit);
+        assert_send(&shed);
+        assert_send(&adaptive);
+        router
+            .route("/limit", limit)
+            .route("/load-shed", shed)
+            .route("/adaptive", adaptive)
+    };
+
+    #[cfg(feature = "metrics")]
+    let router = {
+        use puzz_middleware::metrics::{metrics, Registry};
+
+        let service = handler().with(metrics(Registry::new()));
+        assert_send(&service);
+        router.route("/metrics", service)
+    };
+
+    #[cfg(feature = "rate-limit")]
+    let router = {
+        use puzz_middleware::rate_limit::{rate_limit, HeaderKey, Quota};
+
+        let service = handler().with(rate_limit(
+            Quota::per_second(1),
+            HeaderKey::new("x-api-key"),
+        ));
+        assert_send(&service);
+        router.route("/rate-limit", service)
+    };
+
+    #[cfg(feature = "request-id")]
+    let router = {
+        use puzz_middleware::request_id::request_id;
+
+        let service = handler().with(request_id());
+        assert_send(&service);
+        router.route("/request-id", service)
+    };
+
+    #[cfg(feature = "security-headers")]
+    let router = {
+        use puzz_middleware::security_headers::security_headers;
+
+        let service = handler().with(security_headers());
+        assert_send(&service);
+        router.route("/security-headers", service)
+    };
+
+    #[cfg(feature = "session")]
+    let router = {
+        use puzz_middleware::session::{session, FileStore, MemoryStore};
+
+        let memory = handler().with(session(MemoryStore::new()));
+        let file = handler().with(session(FileStore::new("sessions")));
+        assert_send(&memory);
+        assert_send(&file);
+        router
+            .route("/session", memory)
+            .route("/file-session", file)
+    };
+
+    #[cfg(feature = "trace")]
+    let router = {
+        use puzz_middleware::trace::{access_log, trace};
+
+        let traced = handler().with(trace());
+        let logged = handler().with(access_log(std::io::sink()));
+        assert_send(&traced);
+        assert_send(&logged);
+        router.route("/trace", traced).route("/access-log", logged)
+    };
+
+    #[cfg(feature = "core")]
+    let router = {
+        use puzz_middleware::core::add_extension::add_extension;
+
+        let service = handler().with(add_extension(|| 42u32));
+        assert_send(&service);
+        router.route("/add-extension", service)
+    };
+
+    drop(router);
+}
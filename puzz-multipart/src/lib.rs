@@ -19,9 +19,9 @@ pin_project! {
 }
 
 impl Multipart {
-    pub fn new<S>(headers: &HeaderMap, stream: S) -> Result<Self, MultipartError>
+    pub fn new<S, E>(headers: &HeaderMap, stream: S) -> Result<Self, MultipartError>
     where
-        S: Stream<Item = Result<Bytes, Box<dyn std::error::Error>>> + 'static,
+        S: Stream<Item = Result<Bytes, E>> + 'static,
     {
         Self::boundary(headers)?;
 
@@ -4,9 +4,12 @@ use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+use futures_core::ready;
 use pin_project_lite::pin_project;
-use puzz_core::service::util::BoxFuture;
-use puzz_core::{BoxError, Response};
+use puzz_core::http::marker::{MaybeSend, MaybeSendSync};
+use puzz_core::response::IntoResponse;
+use puzz_core::service::{Service, ServiceExt};
+use puzz_core::{BoxError, Request, Response};
 
 mod method;
 mod router;
@@ -16,12 +19,40 @@ pub mod error;
 pub use method::*;
 pub use router::*;
 
+#[cfg(not(feature = "send"))]
+type RouteService = puzz_core::service::util::BoxService<Request, Response, BoxError>;
+#[cfg(feature = "send")]
+type RouteService = puzz_core::service::util::SendBoxService<Request, Response, BoxError>;
+
+#[cfg(not(feature = "send"))]
+type BoxFuture<T> = puzz_core::service::util::BoxFuture<T>;
+#[cfg(feature = "send")]
+type BoxFuture<T> = puzz_core::service::util::SendBoxFuture<T>;
+
+fn into_route_service<S>(service: S) -> RouteService
+where
+    S: Service<Request> + MaybeSendSync + 'static,
+    S::Future: MaybeSend,
+    S::Response: IntoResponse,
+    S::Error: Into<BoxError>,
+{
+    let service = service
+        .map_response(IntoResponse::into_response)
+        .map_err(Into::into);
+
+    #[cfg(not(feature = "send"))]
+    return service.boxed();
+    #[cfg(feature = "send")]
+    return service.send_boxed();
+}
+
 pin_project! {
     #[project = RouteFutureProj]
     pub enum RouteFuture {
         Future {
             #[pin]
             fut: BoxFuture<Result<Response, BoxError>>,
+            matched_path: Option<MatchedPath>,
         },
         Error {
             err: Option<BoxError>,
@@ -34,7 +65,16 @@ impl Future for RouteFuture {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match self.project() {
-            RouteFutureProj::Future { fut } => fut.poll(cx),
+            RouteFutureProj::Future { fut, matched_path } => {
+                let mut response = ready!(fut.poll(cx))?;
+                if let Some(matched_path) = matched_path.take() {
+                    let extensions = response.extensions_mut();
+                    if extensions.get::<MatchedPath>().is_none() {
+                        extensions.insert(matched_path);
+                    }
+                }
+                Poll::Ready(Ok(response))
+            }
             RouteFutureProj::Error { err } => {
                 Poll::Ready(Err(err.take().expect("polled after completion").into()))
             }
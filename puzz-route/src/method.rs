@@ -1,33 +1,34 @@
+use puzz_core::http::marker::{MaybeSend, MaybeSendSync};
 use puzz_core::http::Method;
 use puzz_core::response::IntoResponse;
-use puzz_core::service::util::BoxService;
-use puzz_core::service::{Service, ServiceExt};
+use puzz_core::service::Service;
 use puzz_core::{BoxError, Request, Response};
 
 use crate::error::MethodNotAllowed;
-use crate::RouteFuture;
+use crate::{into_route_service, RouteFuture, RouteService};
 
 #[derive(Debug)]
 pub struct MethodRouter {
-    options: Option<BoxService<Request, Response, BoxError>>,
-    get: Option<BoxService<Request, Response, BoxError>>,
-    post: Option<BoxService<Request, Response, BoxError>>,
-    put: Option<BoxService<Request, Response, BoxError>>,
-    delete: Option<BoxService<Request, Response, BoxError>>,
-    head: Option<BoxService<Request, Response, BoxError>>,
-    trace: Option<BoxService<Request, Response, BoxError>>,
-    patch: Option<BoxService<Request, Response, BoxError>>,
+    options: Option<RouteService>,
+    get: Option<RouteService>,
+    post: Option<RouteService>,
+    put: Option<RouteService>,
+    delete: Option<RouteService>,
+    head: Option<RouteService>,
+    trace: Option<RouteService>,
+    patch: Option<RouteService>,
 }
 
 macro_rules! router_impl_method_fn {
     ($method:ident) => {
         pub fn $method<S>(mut self, service: S) -> Self
         where
-            S: Service<Request> + 'static,
+            S: Service<Request> + MaybeSendSync + 'static,
+            S::Future: MaybeSend,
             S::Response: IntoResponse,
             S::Error: Into<BoxError>,
         {
-            self.$method = Some(Self::into_box_service(service));
+            self.$method = Some(into_route_service(service));
             self
         }
     };
@@ -55,18 +56,6 @@ impl MethodRouter {
     router_impl_method_fn!(head);
     router_impl_method_fn!(trace);
     router_impl_method_fn!(patch);
-
-    fn into_box_service<S>(service: S) -> BoxService<Request, Response, BoxError>
-    where
-        S: Service<Request> + 'static,
-        S::Response: IntoResponse,
-        S::Error: Into<BoxError>,
-    {
-        service
-            .map_response(IntoResponse::into_response)
-            .map_err(Into::into)
-            .boxed()
-    }
 }
 
 impl Service<Request> for MethodRouter {
@@ -81,6 +70,7 @@ impl Service<Request> for MethodRouter {
                     if let Some(svc) = $svc {
                         return RouteFuture::Future {
                             fut: svc.call($req),
+                            matched_path: None,
                         };
                     }
                 }
@@ -107,7 +97,8 @@ macro_rules! impl_method_fn {
     ($method:ident) => {
         pub fn $method<S>(service: S) -> MethodRouter
         where
-            S: Service<Request> + 'static,
+            S: Service<Request> + MaybeSendSync + 'static,
+            S::Future: MaybeSend,
             S::Response: IntoResponse,
             S::Error: Into<BoxError>,
         {
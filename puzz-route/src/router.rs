@@ -1,21 +1,22 @@
 use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
 
 use matchit::Match;
+use puzz_core::http::marker::{MaybeSend, MaybeSendSync};
 use puzz_core::http::uri::{Parts, PathAndQuery, Uri};
 use puzz_core::response::IntoResponse;
-use puzz_core::service::util::BoxService;
-use puzz_core::service::{Service, ServiceExt};
+use puzz_core::service::Service;
 use puzz_core::{BoxError, Request, Response};
 
 use crate::error::NotFound;
-use crate::RouteFuture;
+use crate::{into_route_service, RouteFuture, RouteService};
 
 const PRIVATE_TAIL_PARAM: &'static str = "__private__tail_param";
 
 enum Endpoint {
-    Full(BoxService<Request, Response, BoxError>),
-    Nest(BoxService<Request, Response, BoxError>),
+    Full(MatchedPath, RouteService),
+    Nest(MatchedPath, RouteService),
 }
 
 /// 路由器
@@ -60,19 +61,24 @@ impl Router {
     /// ```
     pub fn route<S>(self, path: &str, service: S) -> Self
     where
-        S: Service<Request> + 'static,
+        S: Service<Request> + MaybeSendSync + 'static,
+        S::Future: MaybeSend,
         S::Response: IntoResponse,
         S::Error: Into<BoxError>,
     {
         if !path.starts_with('/') {
             panic!("Path must start with a `/`");
         }
+        let matched_path = MatchedPath::new(path);
         let path = if path.ends_with('*') {
             format!("{path}{PRIVATE_TAIL_PARAM}")
         } else {
             path.into()
         };
-        self.add_route(path, Endpoint::Full(Self::into_box_service(service)))
+        self.add_route(
+            path,
+            Endpoint::Full(matched_path, into_route_service(service)),
+        )
     }
 
     /// 将服务挂载到一条嵌套路由上。
@@ -90,19 +96,24 @@ impl Router {
     /// ```
     pub fn nest<S>(self, path: &str, service: S) -> Self
     where
-        S: Service<Request> + 'static,
+        S: Service<Request> + MaybeSendSync + 'static,
+        S::Future: MaybeSend,
         S::Response: IntoResponse,
         S::Error: Into<BoxError>,
     {
         if !path.starts_with('/') {
             panic!("Path must start with a `/`");
         }
+        let matched_path = MatchedPath::new(path.trim_end_matches('/'));
         let path = if path.ends_with('/') {
             format!("{path}*{PRIVATE_TAIL_PARAM}")
         } else {
             format!("{path}/*{PRIVATE_TAIL_PARAM}")
         };
-        self.add_route(path, Endpoint::Nest(Self::into_box_service(service)))
+        self.add_route(
+            path,
+            Endpoint::Nest(matched_path, into_route_service(service)),
+        )
     }
 
     fn add_route(mut self, path: String, endpoint: Endpoint) -> Self {
@@ -111,18 +122,6 @@ impl Router {
         }
         self
     }
-
-    fn into_box_service<S>(service: S) -> BoxService<Request, Response, BoxError>
-    where
-        S: Service<Request> + 'static,
-        S::Response: IntoResponse,
-        S::Error: Into<BoxError>,
-    {
-        service
-            .map_response(IntoResponse::into_response)
-            .map_err(Into::into)
-            .boxed()
-    }
 }
 
 impl fmt::Debug for Router {
@@ -138,22 +137,27 @@ impl Service<Request> for Router {
 
     fn call(&self, mut request: Request) -> Self::Future {
         match self.inner.at(request.uri().path()) {
-            Ok(Match { value, params }) => {
-                let fut = match value {
-                    Endpoint::Full(service) => {
-                        let (params, _) = take_params(params);
-                        insert_params(&mut request, params);
-                        service.call(request)
+            Ok(Match { value, params }) => match value {
+                Endpoint::Full(matched_path, service) => {
+                    let (params, _) = take_params(params);
+                    insert_params(&mut request, params);
+                    let matched_path = insert_matched_path(&mut request, matched_path);
+                    RouteFuture::Future {
+                        fut: service.call(request),
+                        matched_path: Some(matched_path),
                     }
-                    Endpoint::Nest(service) => {
-                        let (params, tail) = take_params(params);
-                        insert_params(&mut request, params);
-                        replace_path(&mut request, &tail.unwrap());
-                        service.call(request)
+                }
+                Endpoint::Nest(matched_path, service) => {
+                    let (params, tail) = take_params(params);
+                    insert_params(&mut request, params);
+                    insert_matched_path(&mut request, matched_path);
+                    replace_path(&mut request, &tail.unwrap());
+                    RouteFuture::Future {
+                        fut: service.call(request),
+                        matched_path: None,
                     }
-                };
-                RouteFuture::Future { fut }
-            }
+                }
+            },
             Err(_) => RouteFuture::Error {
                 err: Some(NotFound::new(request).into()),
             },
@@ -179,6 +183,32 @@ impl Params {
     }
 }
 
+/// 路由器匹配到的路由。
+///
+/// 路由器会将匹配到的路由插入到请求和响应的扩展中，嵌套路由会与外层路由拼接在一起，
+/// 例如`/api`中嵌套的`/users/:id`会得到`/api/users/:id`。
+///
+/// 与请求的原始路径不同，匹配到的路由不包含路径参数的值，适合用作日志和指标的标签。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MatchedPath(Arc<str>);
+
+impl MatchedPath {
+    pub(crate) fn new(path: &str) -> Self {
+        Self(path.into())
+    }
+
+    /// 获取匹配到的路由。
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for MatchedPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
 fn take_params(params: matchit::Params) -> (Vec<(String, String)>, Option<String>) {
     let mut path = None;
     (
@@ -210,6 +240,18 @@ fn insert_params(request: &mut Request, captures: Vec<(String, String)>) {
     params.0.extend(captures);
 }
 
+fn insert_matched_path(request: &mut Request, matched_path: &MatchedPath) -> MatchedPath {
+    let extensions = request.extensions_mut();
+
+    let matched_path = match extensions.get::<MatchedPath>() {
+        Some(prefix) => MatchedPath::new(&format!("{}{}", prefix, matched_path)),
+        None => matched_path.clone(),
+    };
+
+    extensions.insert(matched_path.clone());
+    matched_path
+}
+
 fn replace_path(request: &mut Request, path: &str) {
     let uri = request.uri_mut();
 
@@ -14,6 +14,50 @@ use puzz_core::{BoxError, Request};
 
 use crate::PeerAddr;
 
+#[cfg(not(feature = "send"))]
+fn into_puzz_body(payload: Payload) -> BoxBody {
+    IntoPuzzBody { body: payload }.boxed()
+}
+
+// actix的请求正文不能跨线程移动，启用`send`特性时，通过通道将正文转发给可以跨线程移动的正文。
+#[cfg(feature = "send")]
+fn into_puzz_body(mut payload: Payload) -> BoxBody {
+    if let Payload::None = payload {
+        return BoxBody::default();
+    }
+
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+
+    tokio::task::spawn_local(async move {
+        while let Some(chunk) =
+            std::future::poll_fn(|cx| Pin::new(&mut payload).poll_next(cx)).await
+        {
+            if tx.send(chunk).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    ChannelBody { rx }.boxed()
+}
+
+#[cfg(feature = "send")]
+struct ChannelBody {
+    rx: tokio::sync::mpsc::Receiver<Result<Bytes, actix_http::error::PayloadError>>,
+}
+
+#[cfg(feature = "send")]
+impl Body for ChannelBody {
+    type Error = BoxError;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        self.rx.poll_recv(cx).map_err(Into::into)
+    }
+}
+
 pin_project! {
     struct IntoPuzzBody {
         #[pin]
@@ -40,7 +84,7 @@ pin_project! {
 }
 
 impl MessageBody for IntoActixBody {
-    type Error = BoxError;
+    type Error = Box<dyn std::error::Error>;
 
     fn size(&self) -> BodySize {
         if let Some(size) = self.body.size_hint().exact() {
@@ -54,7 +98,10 @@ impl MessageBody for IntoActixBody {
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Result<Bytes, Self::Error>>> {
-        self.project().body.poll_next(cx)
+        self.project()
+            .body
+            .poll_next(cx)
+            .map_err(|err| -> Self::Error { err })
     }
 }
 
@@ -87,7 +134,7 @@ where
                 request = request.header(k, v);
             }
 
-            request.body(IntoPuzzBody { body }.boxed()).unwrap()
+            request.body(into_puzz_body(body)).unwrap()
         })
         .map_response(|response: S::Response| {
             let (head, body) = response.into_response().into_head();
@@ -101,3 +148,31 @@ where
             response.message_body(IntoActixBody { body }).unwrap()
         })
 }
+
+/// 将服务转换为actix的服务，actix在读取下一个请求之前会检查服务是否就绪。
+pub(crate) struct ActixService<S> {
+    inner: S,
+}
+
+impl<S> ActixService<S> {
+    pub(crate) fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S> actix_service::Service<actix_http::Request> for ActixService<S>
+where
+    S: Service<actix_http::Request>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: actix_http::Request) -> Self::Future {
+        self.inner.call(request)
+    }
+}
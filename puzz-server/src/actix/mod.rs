@@ -3,7 +3,6 @@ use std::fmt;
 use std::net::SocketAddr;
 
 use actix_http::HttpService;
-use actix_service::IntoService;
 use puzz_core::response::IntoResponse;
 use puzz_core::service::Service;
 use puzz_core::{BoxError, Request};
@@ -83,9 +82,8 @@ where
 
         let factory = move || {
             let service = compat::into_actix_service(factory());
-            let service = move |request: actix_http::Request| service.call(request);
 
-            async move { Ok::<_, Infallible>(service.into_service()) }
+            async move { Ok::<_, Infallible>(compat::ActixService::new(service)) }
         };
 
         let mut server = actix_server::Server::build();
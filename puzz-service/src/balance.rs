@@ -0,0 +1,410 @@
+//! 在动态的服务集合中均衡负载。
+//!
+//! [`Balance`]使用“两次随机选择”（power of two choices）算法：每个请求随机选出两个服务，
+//! 交给其中正在处理的请求较少的一个。服务集合由[`Discover`]提供，可以在运行时增加或移除服务。
+
+use alloc::{boxed::Box, sync::Arc, vec::Vec};
+use core::{
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+use std::sync::Mutex;
+
+use futures_util::task::noop_waker_ref;
+use pin_project_lite::pin_project;
+
+use crate::discover::{Change, Discover};
+use crate::Service;
+
+/// 使用“两次随机选择”算法在[`Discover`]提供的服务中均衡负载。
+///
+/// 服务集合的变化在[`poll_ready`](Service::poll_ready)和[`call`](Service::call)时处理，
+/// [`call`](Service::call)使用最近一次就绪检查的唤醒器轮询[`Discover`]。
+/// 就绪检查时返回错误的服务会被移除。
+///
+/// 每个请求只在最近一次就绪检查通过的服务中选择，被选中的服务需要再次通过就绪检查才会被选中。
+/// 如果没有这样的服务（例如调用者没有检查就绪状态），则在所有服务中选择。
+///
+/// 内部状态保存在[`Mutex`]中。[`Discover`]实现[`Send`]，且服务及其键实现[`Send`]和[`Sync`]时，
+/// [`Balance`]同时实现[`Send`]和[`Sync`]，可以在多个线程之间共享。
+///
+/// # 例子
+///
+/// ```
+/// use futures_util::FutureExt;
+/// use puzz_service::balance::Balance;
+/// use puzz_service::discover::ServiceList;
+/// use puzz_service::util::{service_fn, BoxService};
+/// use puzz_service::{Service, ServiceExt};
+///
+/// let services: Vec<BoxService<u32, u32, ()>> = vec![
+///     service_fn(|request: u32| async move { Ok(request + 1) }).boxed(),
+///     service_fn(|request: u32| async move { Ok(request + 1) }).boxed(),
+/// ];
+///
+/// let service = Balance::new(ServiceList::new(services));
+///
+/// assert_eq!(service.call(1).now_or_never(), Some(Ok(2)));
+/// assert_eq!(service.len(), 2);
+/// ```
+///
+/// 就绪检查之后，请求不会交给未就绪的服务：
+///
+/// ```
+/// use std::future::{ready, Ready};
+/// use std::task::{Context, Poll};
+///
+/// use futures_util::FutureExt;
+/// use puzz_service::balance::Balance;
+/// use puzz_service::discover::ServiceList;
+/// use puzz_service::{Service, ServiceExt};
+///
+/// struct Endpoint(&'static str, bool);
+///
+/// impl Service<()> for Endpoint {
+///     type Response = &'static str;
+///     type Error = ();
+///     type Future = Ready<Result<&'static str, ()>>;
+///
+///     fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), ()>> {
+///         if self.1 {
+///             Poll::Ready(Ok(()))
+///         } else {
+///             Poll::Pending
+///         }
+///     }
+///
+///     fn call(&self, _: ()) -> Self::Future {
+///         ready(Ok(self.0))
+///     }
+/// }
+///
+/// let service = Balance::new(ServiceList::new([Endpoint("busy", false), Endpoint("idle", true)]));
+///
+/// for _ in 0..10 {
+///     let response = async { service.ready().await.unwrap().call(()).await };
+///     assert_eq!(response.now_or_never(), Some(Ok("idle")));
+/// }
+///
+/// fn assert_send_sync<T: Send + Sync>(_: &T) {}
+/// assert_send_sync(&service);
+/// ```
+pub struct Balance<D, Req>
+where
+    D: Discover,
+{
+    state: Mutex<State<D>>,
+    _req: PhantomData<fn(Req)>,
+}
+
+struct State<D>
+where
+    D: Discover,
+{
+    discover: Pin<Box<D>>,
+    exhausted: bool,
+    endpoints: Vec<Arc<Endpoint<D::Key, D::Service>>>,
+    // 最近一次就绪检查的唤醒器。
+    waker: Option<Waker>,
+    rng: u64,
+}
+
+struct Endpoint<K, S> {
+    key: K,
+    service: S,
+    ready: AtomicBool,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl<D, Req> Balance<D, Req>
+where
+    D: Discover,
+{
+    /// 使用给定的[`Discover`]创建一个[`Balance`]。
+    pub fn new(discover: D) -> Self {
+        let discover = Box::pin(discover);
+        // 使用分配的地址作为随机数种子，使不同的实例产生不同的序列。
+        let seed = &*discover as *const D as *const () as usize as u64;
+
+        Self {
+            state: Mutex::new(State {
+                discover,
+                exhausted: false,
+                endpoints: Vec::new(),
+                waker: None,
+                rng: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+            }),
+            _req: PhantomData,
+        }
+    }
+
+    /// 获取当前的服务数量。
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().endpoints.len()
+    }
+
+    /// 判断当前是否没有任何服务。
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn discover(state: &mut State<D>, cx: &mut Context<'_>) -> Result<(), D::Error> {
+        while !state.exhausted {
+            match state.discover.as_mut().poll_discover(cx) {
+                Poll::Ready(Some(Ok(Change::Insert(key, service)))) => {
+                    state.endpoints.retain(|endpoint| endpoint.key != key);
+                    state.endpoints.push(Arc::new(Endpoint {
+                        key,
+                        service,
+                        ready: AtomicBool::new(false),
+                        in_flight: Arc::new(AtomicUsize::new(0)),
+                    }));
+                }
+                Poll::Ready(Some(Ok(Change::Remove(key)))) => {
+                    state.endpoints.retain(|endpoint| endpoint.key != key);
+                }
+                Poll::Ready(Some(Err(err))) => return Err(err),
+                Poll::Ready(None) => state.exhausted = true,
+                Poll::Pending => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn pick(state: &mut State<D>) -> Option<Arc<Endpoint<D::Key, D::Service>>> {
+        let ready = state
+            .endpoints
+            .iter()
+            .filter(|endpoint| endpoint.ready.load(Ordering::Relaxed))
+            .count();
+        let endpoints = &state.endpoints;
+        let candidates = || {
+            endpoints
+                .iter()
+                .filter(move |endpoint| ready == 0 || endpoint.ready.load(Ordering::Relaxed))
+        };
+
+        let len = if ready == 0 { endpoints.len() } else { ready };
+
+        let endpoint = match len {
+            0 => return None,
+            1 => candidates().next().unwrap(),
+            len => {
+                let a = random(&mut state.rng) as usize % len;
+                let mut b = random(&mut state.rng) as usize % (len - 1);
+                if b >= a {
+                    b += 1;
+                }
+                let a = candidates().nth(a).unwrap();
+                let b = candidates().nth(b).unwrap();
+                if a.in_flight.load(Ordering::Relaxed) <= b.in_flight.load(Ordering::Relaxed) {
+                    a
+                } else {
+                    b
+                }
+            }
+        };
+
+        // 就绪状态已经被这个请求使用。
+        endpoint.ready.store(false, Ordering::Relaxed);
+        Some(endpoint.clone())
+    }
+}
+
+// xorshift64*
+fn random(rng: &mut u64) -> u64 {
+    let mut x = *rng;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    *rng = x;
+    x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+}
+
+impl<D, Req> Service<Req> for Balance<D, Req>
+where
+    D: Discover,
+    D::Service: Service<Req>,
+{
+    type Response = <D::Service as Service<Req>>::Response;
+    type Error = BalanceError<<D::Service as Service<Req>>::Error, D::Error>;
+    type Future = BalanceFuture<
+        <D::Service as Service<Req>>::Future,
+        <D::Service as Service<Req>>::Error,
+        D::Error,
+    >;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        if !state
+            .waker
+            .as_ref()
+            .is_some_and(|waker| waker.will_wake(cx.waker()))
+        {
+            state.waker = Some(cx.waker().clone());
+        }
+
+        Self::discover(state, cx).map_err(BalanceError::Discover)?;
+
+        let mut ready = false;
+
+        state
+            .endpoints
+            .retain(|endpoint| match endpoint.service.poll_ready(cx) {
+                Poll::Ready(Ok(())) => {
+                    endpoint.ready.store(true, Ordering::Relaxed);
+                    ready = true;
+                    true
+                }
+                Poll::Ready(Err(_)) => false,
+                Poll::Pending => {
+                    endpoint.ready.store(false, Ordering::Relaxed);
+                    true
+                }
+            });
+
+        if ready {
+            Poll::Ready(Ok(()))
+        } else if state.endpoints.is_empty() && state.exhausted {
+            Poll::Ready(Err(BalanceError::NoEndpoints))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn call(&self, request: Req) -> Self::Future {
+        // 调用者可能没有检查就绪状态，这里也处理一次服务集合的变化。
+        let endpoint = {
+            let mut state = self.state.lock().unwrap();
+            let state = &mut *state;
+            let waker = state.waker.clone();
+            let mut cx = Context::from_waker(waker.as_ref().unwrap_or(noop_waker_ref()));
+            if let Err(err) = Self::discover(state, &mut cx) {
+                return BalanceFuture::failed(BalanceError::Discover(err));
+            }
+
+            Self::pick(state)
+        };
+
+        // 在锁外调用服务，避免服务的`call`阻塞其他调用者。
+        match endpoint {
+            Some(endpoint) => {
+                endpoint.in_flight.fetch_add(1, Ordering::Relaxed);
+                BalanceFuture::called(
+                    endpoint.service.call(request),
+                    InFlight(endpoint.in_flight.clone()),
+                )
+            }
+            None => BalanceFuture::failed(BalanceError::NoEndpoints),
+        }
+    }
+}
+
+impl<D, Req> fmt::Debug for Balance<D, Req>
+where
+    D: Discover,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Balance")
+            .field("discover", &core::any::type_name::<D>())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+struct InFlight(Arc<AtomicUsize>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pin_project! {
+    /// [`Balance`]返回的[`Future`]。
+    pub struct BalanceFuture<F, E, D> {
+        #[pin]
+        state: FutureState<F, E, D>,
+    }
+}
+
+pin_project! {
+    #[project = FutureStateProj]
+    enum FutureState<F, E, D> {
+        Called {
+            #[pin]
+            fut: F,
+            guard: InFlight,
+        },
+        Failed {
+            error: Option<BalanceError<E, D>>,
+        },
+    }
+}
+
+impl<F, E, D> BalanceFuture<F, E, D> {
+    fn called(fut: F, guard: InFlight) -> Self {
+        Self {
+            state: FutureState::Called { fut, guard },
+        }
+    }
+
+    fn failed(error: BalanceError<E, D>) -> Self {
+        Self {
+            state: FutureState::Failed { error: Some(error) },
+        }
+    }
+}
+
+impl<F, R, E, D> Future for BalanceFuture<F, E, D>
+where
+    F: Future<Output = Result<R, E>>,
+{
+    type Output = Result<R, BalanceError<E, D>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project().state.project() {
+            FutureStateProj::Called { fut, .. } => fut.poll(cx).map_err(BalanceError::Service),
+            FutureStateProj::Failed { error } => {
+                Poll::Ready(Err(error.take().expect("polled after completion")))
+            }
+        }
+    }
+}
+
+impl<F, E, D> fmt::Debug for BalanceFuture<F, E, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BalanceFuture").finish()
+    }
+}
+
+/// [`Balance`]返回的错误。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BalanceError<E, D> {
+    /// 服务处理请求时返回的错误。
+    Service(E),
+    /// 发现服务时返回的错误。
+    Discover(D),
+    /// 没有可用的服务。
+    NoEndpoints,
+}
+
+impl<E, D> fmt::Display for BalanceError<E, D>
+where
+    E: fmt::Display,
+    D: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BalanceError::Service(e) => e.fmt(f),
+            BalanceError::Discover(e) => write!(f, "failed to discover services: {}", e),
+            BalanceError::NoEndpoints => f.write_str("no endpoints available"),
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! 以声明的方式组合多个[`Wrap`]。
+
+use core::fmt;
+
+use crate::Wrap;
+
+/// 以声明的方式组合多个[`Wrap`]，并将它们应用到服务上。
+///
+/// 先添加的包裹位于外层，先处理请求、后处理响应。例如
+/// `ServiceBuilder::new().wrap(a).wrap(b).service(s)`等同于`s.with(b).with(a)`，
+/// 请求依次经过`a`、`b`到达`s`。
+///
+/// [`ServiceBuilder`]本身也实现了[`Wrap`]，实现[`Clone`]时可以保存下来并应用到多个服务上。
+///
+/// # 例子
+///
+/// ```
+/// use futures_util::FutureExt;
+/// use puzz_service::builder::ServiceBuilder;
+/// use puzz_service::util::{service_fn, wrap_fn, BoxService};
+/// use puzz_service::{Service, ServiceExt};
+///
+/// type Svc = BoxService<u32, u32, ()>;
+///
+/// let stack = ServiceBuilder::new()
+///     .option_wrap(Some(wrap_fn(|s: Svc| s.map_request(|r: u32| r * 10).boxed())))
+///     .wrap(wrap_fn(|s: Svc| s.map_request(|r: u32| r + 1).boxed()));
+///
+/// // 请求先乘以10，再加1。
+/// let service = stack.service(service_fn(|r: u32| async move { Ok(r) }).boxed());
+/// assert_eq!(service.call(1).now_or_never(), Some(Ok(11)));
+///
+/// // 同一组包裹可以应用到其它服务上。
+/// let other = service_fn(|r: u32| async move { Ok(r * 2) }).boxed().with(stack);
+/// assert_eq!(other.call(1).now_or_never(), Some(Ok(22)));
+/// ```
+#[derive(Clone)]
+pub struct ServiceBuilder<W> {
+    wrap: W,
+}
+
+impl ServiceBuilder<Identity> {
+    /// 创建一个不包含任何包裹的[`ServiceBuilder`]。
+    pub fn new() -> Self {
+        Self { wrap: Identity }
+    }
+}
+
+impl Default for ServiceBuilder<Identity> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W> ServiceBuilder<W> {
+    /// 添加一个包裹，它位于已经添加的包裹内层。
+    pub fn wrap<T>(self, wrap: T) -> ServiceBuilder<Stack<T, W>> {
+        ServiceBuilder {
+            wrap: Stack::new(wrap, self.wrap),
+        }
+    }
+
+    /// 添加一个可选的包裹，为[`None`]时不包裹服务。
+    pub fn option_wrap<T>(self, wrap: Option<T>) -> ServiceBuilder<Stack<Option<T>, W>> {
+        self.wrap(wrap)
+    }
+
+    /// 获取组合后的包裹。
+    pub fn into_inner(self) -> W {
+        self.wrap
+    }
+
+    /// 将所有包裹应用到服务上，返回包裹后的服务。
+    pub fn service<S>(&self, service: S) -> W::Service
+    where
+        W: Wrap<S> + Clone,
+    {
+        self.wrap.clone().wrap(service)
+    }
+}
+
+impl<W, S> Wrap<S> for ServiceBuilder<W>
+where
+    W: Wrap<S>,
+{
+    type Service = W::Service;
+
+    fn wrap(self, service: S) -> Self::Service {
+        self.wrap.wrap(service)
+    }
+}
+
+impl<W> fmt::Debug for ServiceBuilder<W>
+where
+    W: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ServiceBuilder").field(&self.wrap).finish()
+    }
+}
+
+/// 不做任何处理的[`Wrap`]，直接返回原服务。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Identity;
+
+impl Identity {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Wrap<S> for Identity {
+    type Service = S;
+
+    fn wrap(self, service: S) -> Self::Service {
+        service
+    }
+}
+
+/// 组合两个[`Wrap`]，先应用`Inner`，再应用`Outer`。
+#[derive(Debug, Clone, Copy)]
+pub struct Stack<Inner, Outer> {
+    inner: Inner,
+    outer: Outer,
+}
+
+impl<Inner, Outer> Stack<Inner, Outer> {
+    pub fn new(inner: Inner, outer: Outer) -> Self {
+        Self { inner, outer }
+    }
+}
+
+impl<S, Inner, Outer> Wrap<S> for Stack<Inner, Outer>
+where
+    Inner: Wrap<S>,
+    Outer: Wrap<Inner::Service>,
+{
+    type Service = Outer::Service;
+
+    fn wrap(self, service: S) -> Self::Service {
+        self.outer.wrap(self.inner.wrap(service))
+    }
+}
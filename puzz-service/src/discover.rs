@@ -0,0 +1,130 @@
+//! 在运行时发现服务的变化。
+
+use core::{
+    convert::Infallible,
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+
+/// 服务集合的一次变化。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<K, S> {
+    /// 插入一个服务，如果键已经存在，则替换原来的服务。
+    Insert(K, S),
+    /// 移除键对应的服务。
+    Remove(K),
+}
+
+type Discovered<K, S, E> = Poll<Option<Result<Change<K, S>, E>>>;
+
+/// 持续产生服务集合的变化。
+///
+/// 对于产生`Result<Change<K, S>, E>`的[`Stream`]，已经实现了该特征。
+///
+/// # 例子
+///
+/// ```
+/// use futures_util::stream;
+/// use puzz_service::discover::{Change, Discover};
+///
+/// fn assert_discover<D: Discover<Key = &'static str, Service = u32>>(_: D) {}
+///
+/// assert_discover(stream::iter([
+///     Ok::<_, ()>(Change::Insert("a", 1)),
+///     Ok(Change::Insert("b", 2)),
+///     Ok(Change::Remove("a")),
+/// ]));
+/// ```
+pub trait Discover {
+    /// 用于区分服务的键。
+    type Key: Eq;
+
+    /// 发现的服务。
+    type Service;
+
+    /// 发现服务时产生的错误。
+    type Error;
+
+    /// 获取下一个变化，返回`Poll::Ready(None)`表示不会再有新的变化。
+    fn poll_discover(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Discovered<Self::Key, Self::Service, Self::Error>;
+}
+
+impl<D, K, S, E> Discover for D
+where
+    D: Stream<Item = Result<Change<K, S>, E>> + ?Sized,
+    K: Eq,
+{
+    type Key = K;
+    type Service = S;
+    type Error = E;
+
+    fn poll_discover(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Discovered<K, S, E> {
+        self.poll_next(cx)
+    }
+}
+
+/// 由固定的服务列表构成的[`Discover`]，服务的键是它在列表中的索引，不会产生错误。
+///
+/// # 例子
+///
+/// ```
+/// use puzz_service::discover::ServiceList;
+///
+/// let discover = ServiceList::new(["a", "b"]);
+/// ```
+pub struct ServiceList<I> {
+    inner: I,
+    index: usize,
+}
+
+impl<I> ServiceList<I>
+where
+    I: Iterator,
+{
+    /// 使用给定的服务列表创建一个[`ServiceList`]。
+    pub fn new<T>(services: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        Self {
+            inner: services.into_iter(),
+            index: 0,
+        }
+    }
+}
+
+impl<I> Unpin for ServiceList<I> {}
+
+impl<I> Stream for ServiceList<I>
+where
+    I: Iterator,
+{
+    type Item = Result<Change<usize, I::Item>, Infallible>;
+
+    fn poll_next(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        Poll::Ready(this.inner.next().map(|service| {
+            let index = this.index;
+            this.index += 1;
+            Ok(Change::Insert(index, service))
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<I> fmt::Debug for ServiceList<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServiceList")
+            .field("index", &self.index)
+            .finish()
+    }
+}
@@ -3,12 +3,25 @@
 
 #[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 use core::future::Future;
+use core::task::{Context, Poll};
 
 #[macro_use]
 mod macros;
 
+#[cfg(feature = "balance")]
+pub mod balance;
+#[cfg(feature = "util")]
+pub mod builder;
+#[cfg(feature = "discover")]
+pub mod discover;
+#[cfg(feature = "steer")]
+pub mod steer;
+#[cfg(feature = "tower")]
+pub mod tower;
 #[cfg(feature = "util")]
 pub mod util;
 #[cfg(feature = "util")]
@@ -25,6 +38,18 @@ pub trait Service<Request> {
     /// 异步返回的响应。
     type Future: Future<Output = Result<Self::Response, Self::Error>>;
 
+    /// 检查服务是否可以处理新的请求。
+    ///
+    /// 返回`Poll::Pending`表示服务暂时无法处理请求（例如达到了并发上限），服务就绪时会唤醒当前任务；
+    /// 返回错误表示服务已经无法再处理请求。调用者应该在服务就绪后再调用[`call`](Service::call)，
+    /// 但服务不能依赖于此，即使没有检查就绪状态也必须能够处理请求。
+    ///
+    /// 默认实现总是返回`Poll::Ready(Ok(()))`。
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let _ = cx;
+        Poll::Ready(Ok(()))
+    }
+
     /// 处理请求并异步返回响应。
     fn call(&self, request: Request) -> Self::Future;
 }
@@ -37,6 +62,10 @@ where
     type Error = S::Error;
     type Future = S::Future;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        (**self).poll_ready(cx)
+    }
+
     fn call(&self, request: Request) -> Self::Future {
         (**self).call(request)
     }
@@ -50,6 +79,10 @@ where
     type Error = S::Error;
     type Future = S::Future;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        (**self).poll_ready(cx)
+    }
+
     fn call(&self, request: Request) -> Self::Future {
         (**self).call(request)
     }
@@ -64,6 +97,10 @@ where
     type Error = S::Error;
     type Future = S::Future;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        (**self).poll_ready(cx)
+    }
+
     fn call(&self, request: Request) -> Self::Future {
         (**self).call(request)
     }
@@ -78,6 +115,10 @@ where
     type Error = S::Error;
     type Future = S::Future;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        (**self).poll_ready(cx)
+    }
+
     fn call(&self, request: Request) -> Self::Future {
         (**self).call(request)
     }
@@ -0,0 +1,143 @@
+//! 根据请求在多个服务中选择一个处理请求。
+
+use alloc::vec::Vec;
+use core::{
+    fmt,
+    marker::PhantomData,
+    task::{Context, Poll},
+};
+
+use crate::Service;
+
+/// 选择处理请求的服务。
+///
+/// 对于闭包`Fn(&Req, &[S]) -> usize`，已经实现了该特征。
+pub trait Picker<S, Req> {
+    /// 返回处理请求的服务在列表中的索引，索引必须小于`services.len()`。
+    fn pick(&self, request: &Req, services: &[S]) -> usize;
+}
+
+impl<F, S, Req> Picker<S, Req> for F
+where
+    F: Fn(&Req, &[S]) -> usize,
+{
+    fn pick(&self, request: &Req, services: &[S]) -> usize {
+        self(request, services)
+    }
+}
+
+/// 使用[`Picker`]在多个服务中选择一个处理请求的服务。
+///
+/// 只有所有服务都就绪时，[`Steer`]才就绪。
+///
+/// # 恐慌
+///
+/// 调用时，如果[`Picker`]返回的索引超出了服务列表的范围（包括服务列表为空时），将会发生恐慌。
+///
+/// # 例子
+///
+/// ```
+/// use futures_util::FutureExt;
+/// use puzz_service::steer::Steer;
+/// use puzz_service::util::{service_fn, BoxService};
+/// use puzz_service::{Service, ServiceExt};
+///
+/// let services: Vec<BoxService<u32, &str, ()>> = vec![
+///     service_fn(|_: u32| async { Ok("even") }).boxed(),
+///     service_fn(|_: u32| async { Ok("odd") }).boxed(),
+/// ];
+///
+/// let service = Steer::new(services, |request: &u32, _: &[_]| (request % 2) as usize);
+///
+/// assert_eq!(service.call(2).now_or_never(), Some(Ok("even")));
+/// assert_eq!(service.call(3).now_or_never(), Some(Ok("odd")));
+/// ```
+pub struct Steer<S, P, Req> {
+    services: Vec<S>,
+    picker: P,
+    _req: PhantomData<fn(Req)>,
+}
+
+impl<S, P, Req> Steer<S, P, Req> {
+    /// 使用给定的服务列表和[`Picker`]创建一个[`Steer`]。
+    pub fn new<I>(services: I, picker: P) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        P: Picker<S, Req>,
+    {
+        Self {
+            services: services.into_iter().collect(),
+            picker,
+            _req: PhantomData,
+        }
+    }
+
+    /// 获取服务列表。
+    pub fn services(&self) -> &[S] {
+        &self.services
+    }
+}
+
+impl<S, P, Req> Service<Req> for Steer<S, P, Req>
+where
+    S: Service<Req>,
+    P: Picker<S, Req>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut ready = true;
+        for service in &self.services {
+            match service.poll_ready(cx) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => ready = false,
+            }
+        }
+        if ready {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn call(&self, request: Req) -> Self::Future {
+        let index = self.picker.pick(&request, &self.services);
+        match self.services.get(index) {
+            Some(service) => service.call(request),
+            None => panic!(
+                "picker returned index {} but there are {} services",
+                index,
+                self.services.len()
+            ),
+        }
+    }
+}
+
+impl<S, P, Req> Clone for Steer<S, P, Req>
+where
+    S: Clone,
+    P: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            services: self.services.clone(),
+            picker: self.picker.clone(),
+            _req: PhantomData,
+        }
+    }
+}
+
+impl<S, P, Req> fmt::Debug for Steer<S, P, Req>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Steer")
+            .field("services", &self.services)
+            .field("picker", &core::any::type_name::<P>())
+            .finish()
+    }
+}
@@ -0,0 +1,356 @@
+//! 与[tower](https://docs.rs/tower)生态互相转换的适配器。
+//!
+//! - [`IntoTower`]将[`Service`]转换为`tower_service::Service`，就绪状态直接转发。
+//! - [`FromTower`]将`tower_service::Service`转换为[`Service`]，
+//!   返回的[`Future`]在服务就绪后立即调用服务，因此不需要先检查就绪状态。
+//! - [`IntoTowerLayer`]和[`FromTowerLayer`]在[`Wrap`]和`tower_layer::Layer`之间转换。
+
+use alloc::vec::Vec;
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use std::sync::{Arc, Mutex};
+use std::task::Wake;
+
+use crate::{Service, Wrap};
+
+/// 将[`Service`]转换为`tower_service::Service`。
+///
+/// # 例子
+///
+/// ```
+/// use futures_util::FutureExt;
+/// use puzz_service::tower::IntoTower;
+/// use puzz_service::util::service_fn;
+/// use tower::ServiceExt;
+///
+/// let service = IntoTower::new(service_fn(|request: u32| async move { Ok::<_, ()>(request + 1) }));
+///
+/// assert_eq!(service.oneshot(1).now_or_never(), Some(Ok(2)));
+/// ```
+#[derive(Clone, Copy)]
+pub struct IntoTower<S> {
+    inner: S,
+}
+
+impl<S> IntoTower<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    /// 获取内部的服务。
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, Req> tower_service::Service<Req> for IntoTower<S>
+where
+    S: Service<Req>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        self.inner.call(request)
+    }
+}
+
+impl<S> fmt::Debug for IntoTower<S>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntoTower")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+/// 将`tower_service::Service`转换为[`Service`]。
+///
+/// tower的服务需要可变引用，因此被保存在[`Mutex`]中，克隆后的服务共享同一个tower服务。
+/// tower服务实现[`Send`]时，[`FromTower`]和返回的[`Future`]也实现[`Send`]和[`Sync`]，可以继续交给要求[`Send`]的tower生态使用。
+/// 每个请求都会等待服务就绪，并在就绪后立即调用服务。服务就绪时会唤醒所有等待的请求，而不只是最后一个。
+///
+/// 注意克隆后的服务也共享就绪状态：一个克隆在[`poll_ready`](Service::poll_ready)中为tower服务预留的容量
+/// （例如`tower::limit::ConcurrencyLimit`的许可）可能被另一个克隆的请求使用。
+/// 如果每个克隆都需要独立的就绪状态，应该在外面使用`puzz_middleware::buffer`或`tower::buffer::Buffer`。
+///
+/// # 例子
+///
+/// ```
+/// use futures_util::FutureExt;
+/// use puzz_service::tower::FromTower;
+/// use puzz_service::Service;
+///
+/// fn assert_send<T: Send + Sync>(_: &T) {}
+///
+/// let service = FromTower::new(tower::service_fn(|request: u32| async move {
+///     Ok::<_, ()>(request + 1)
+/// }));
+/// assert_send(&service);
+///
+/// assert_eq!(service.call(1).now_or_never(), Some(Ok(2)));
+/// ```
+pub struct FromTower<T> {
+    inner: Arc<Shared<T>>,
+}
+
+struct Shared<T> {
+    service: Mutex<T>,
+    waiters: Arc<Waiters>,
+}
+
+impl<T> FromTower<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner: Arc::new(Shared {
+                service: Mutex::new(inner),
+                waiters: Arc::new(Waiters(Mutex::new(Vec::new()))),
+            }),
+        }
+    }
+}
+
+impl<T> Shared<T> {
+    // tower服务只会保存最近一次`poll_ready`的唤醒器，因此传入一个唤醒所有等待者的唤醒器。
+    fn poll_ready<Req>(&self, service: &mut T, cx: &mut Context<'_>) -> Poll<Result<(), T::Error>>
+    where
+        T: tower_service::Service<Req>,
+    {
+        // 在轮询前注册，避免错过轮询和注册之间的唤醒。
+        self.waiters.register(cx.waker());
+        let waker = Waker::from(self.waiters.clone());
+        let poll = service.poll_ready(&mut Context::from_waker(&waker));
+        if poll.is_ready() {
+            self.waiters.unregister(cx.waker());
+        }
+        poll
+    }
+}
+
+struct Waiters(Mutex<Vec<Waker>>);
+
+impl Waiters {
+    fn register(&self, waker: &Waker) {
+        let mut waiters = self.0.lock().unwrap();
+        if !waiters.iter().any(|w| w.will_wake(waker)) {
+            waiters.push(waker.clone());
+        }
+    }
+
+    fn unregister(&self, waker: &Waker) {
+        self.0.lock().unwrap().retain(|w| !w.will_wake(waker));
+    }
+}
+
+impl Wake for Waiters {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let waiters = core::mem::take(&mut *self.0.lock().unwrap());
+        for waker in waiters {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Clone for FromTower<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T, Req> Service<Req> for FromTower<T>
+where
+    T: tower_service::Service<Req>,
+{
+    type Response = T::Response;
+    type Error = T::Error;
+    type Future = FromTowerFuture<T, Req>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let mut service = self.inner.service.lock().unwrap();
+        self.inner.poll_ready(&mut *service, cx)
+    }
+
+    fn call(&self, request: Req) -> Self::Future {
+        FromTowerFuture::Ready {
+            state: Some((self.inner.clone(), request)),
+        }
+    }
+}
+
+impl<T> fmt::Debug for FromTower<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FromTower")
+            .field("inner", &self.inner.service)
+            .finish()
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// [`FromTower`]返回的[`Future`]。
+    #[project = FromTowerFutureProj]
+    pub enum FromTowerFuture<T, Req>
+    where
+        T: tower_service::Service<Req>,
+    {
+        Ready {
+            state: Option<(Arc<Shared<T>>, Req)>,
+        },
+        Calling {
+            #[pin]
+            fut: T::Future,
+        },
+    }
+}
+
+impl<T, Req> Future for FromTowerFuture<T, Req>
+where
+    T: tower_service::Service<Req>,
+{
+    type Output = Result<T::Response, T::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                FromTowerFutureProj::Ready { state } => {
+                    let shared = state.as_ref().expect("polled after completion").0.clone();
+                    let mut service = shared.service.lock().unwrap();
+                    // 就绪后在同一次加锁中调用，避免其它请求在此之间占用服务的就绪状态。
+                    match futures_core::ready!(shared.poll_ready(&mut *service, cx)) {
+                        Ok(()) => {
+                            let (_, request) = state.take().unwrap();
+                            let fut = service.call(request);
+                            drop(service);
+                            self.set(FromTowerFuture::Calling { fut });
+                        }
+                        Err(err) => {
+                            drop(service);
+                            state.take();
+                            return Poll::Ready(Err(err));
+                        }
+                    }
+                }
+                FromTowerFutureProj::Calling { fut } => return fut.poll(cx),
+            }
+        }
+    }
+}
+
+impl<T, Req> fmt::Debug for FromTowerFuture<T, Req>
+where
+    T: tower_service::Service<Req>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FromTowerFuture").finish()
+    }
+}
+
+/// 将[`Wrap`]转换为`tower_layer::Layer`。
+///
+/// 包裹必须实现[`Clone`]，每次应用时克隆包裹。被包裹的tower服务通过[`FromTower`]转换，
+/// 包裹后的服务通过[`IntoTower`]转换。
+#[derive(Clone, Copy)]
+pub struct IntoTowerLayer<W> {
+    inner: W,
+}
+
+impl<W> IntoTowerLayer<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner }
+    }
+}
+
+impl<W, S> tower_layer::Layer<S> for IntoTowerLayer<W>
+where
+    W: Wrap<FromTower<S>> + Clone,
+{
+    type Service = IntoTower<W::Service>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IntoTower::new(self.inner.clone().wrap(FromTower::new(inner)))
+    }
+}
+
+impl<W> fmt::Debug for IntoTowerLayer<W>
+where
+    W: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntoTowerLayer")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+/// 将`tower_layer::Layer`转换为[`Wrap`]。
+///
+/// 被包裹的服务通过[`IntoTower`]转换，包裹后的tower服务通过[`FromTower`]转换。
+///
+/// # 例子
+///
+/// ```
+/// use futures_util::FutureExt;
+/// use puzz_service::tower::FromTowerLayer;
+/// use puzz_service::util::service_fn;
+/// use puzz_service::{Service, ServiceExt};
+///
+/// let layer = tower::layer::layer_fn(|service| {
+///     tower::ServiceExt::map_request(service, |request: u32| request * 10)
+/// });
+///
+/// let service = service_fn(|request: u32| async move { Ok::<_, ()>(request + 1) })
+///     .with(FromTowerLayer::new(layer));
+///
+/// assert_eq!(service.call(1).now_or_never(), Some(Ok(11)));
+/// ```
+#[derive(Clone, Copy)]
+pub struct FromTowerLayer<L> {
+    inner: L,
+}
+
+impl<L> FromTowerLayer<L> {
+    pub fn new(inner: L) -> Self {
+        Self { inner }
+    }
+}
+
+impl<L, S> Wrap<S> for FromTowerLayer<L>
+where
+    L: tower_layer::Layer<IntoTower<S>>,
+{
+    type Service = FromTower<L::Service>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        FromTower::new(self.inner.layer(IntoTower::new(service)))
+    }
+}
+
+impl<L> fmt::Debug for FromTowerLayer<L>
+where
+    L: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FromTowerLayer")
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
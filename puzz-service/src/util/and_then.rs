@@ -1,4 +1,8 @@
-use core::{fmt, future::Future};
+use core::{
+    fmt,
+    future::Future,
+    task::{Context, Poll},
+};
 
 use futures_util::TryFutureExt;
 
@@ -30,6 +34,10 @@ where
     type Error = S::Error;
     type Future = AndThenFuture<S::Future, Fut, F>;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
     fn call(&self, request: Req) -> Self::Future {
         AndThenFuture::new(self.inner.call(request).and_then(self.f.clone()))
     }
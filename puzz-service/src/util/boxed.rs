@@ -1,4 +1,9 @@
-use core::{fmt, future::Future, pin::Pin};
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
 
 use alloc::{boxed::Box, rc::Rc};
 
@@ -35,6 +40,10 @@ impl<Req, Res, Err> Service<Req> for BoxService<Req, Res, Err> {
     type Error = Err;
     type Future = BoxFuture<Result<Res, Err>>;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
     fn call(&self, request: Req) -> BoxFuture<Result<Res, Err>> {
         self.inner.call(request)
     }
@@ -83,6 +92,10 @@ impl<Req, Res, Err> Service<Req> for BoxCloneService<Req, Res, Err> {
     type Error = Err;
     type Future = BoxFuture<Result<Res, Err>>;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
     fn call(&self, request: Req) -> BoxFuture<Result<Res, Err>> {
         self.inner.call(request)
     }
@@ -129,6 +142,10 @@ impl<Req, Res, Err> Service<Req> for RcService<Req, Res, Err> {
     type Error = Err;
     type Future = BoxFuture<Result<Res, Err>>;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
     fn call(&self, request: Req) -> BoxFuture<Result<Res, Err>> {
         self.inner.call(request)
     }
@@ -160,3 +177,151 @@ where
         Box::new(self.clone())
     }
 }
+
+/// 可以跨线程移动的装箱[`Future`]特征对象。
+pub type SendBoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+/// 可以跨线程共享的装箱[`Service`]特征对象。
+///
+/// 这与[`BoxService`]类似，只是[`SendBoxService`]要求服务实现[`Send`]和[`Sync`]，
+/// 并且返回的[`Future`]实现[`Send`]，因此可以在多线程运行时中使用。
+///
+/// # 例子
+///
+/// ```
+/// use puzz_service::util::{service_fn, SendBoxService};
+/// use puzz_service::ServiceExt;
+///
+/// fn assert_send_sync<T: Send + Sync>(_: &T) {}
+///
+/// let service: SendBoxService<u32, u32, ()> =
+///     service_fn(|request: u32| async move { Ok(request + 1) }).send_boxed();
+/// assert_send_sync(&service);
+/// ```
+pub struct SendBoxService<Req, Res, Err> {
+    inner: Box<
+        dyn Service<Req, Response = Res, Error = Err, Future = SendBoxFuture<Result<Res, Err>>>
+            + Send
+            + Sync,
+    >,
+}
+
+impl<Req, Res, Err> SendBoxService<Req, Res, Err> {
+    pub fn new<S>(inner: S) -> Self
+    where
+        S: Service<Req, Response = Res, Error = Err> + Send + Sync + 'static,
+        S::Future: Send + 'static,
+    {
+        Self {
+            inner: Box::new(inner.map_future(|f| Box::pin(f) as _)),
+        }
+    }
+}
+
+impl<Req, Res, Err> Service<Req> for SendBoxService<Req, Res, Err> {
+    type Response = Res;
+    type Error = Err;
+    type Future = SendBoxFuture<Result<Res, Err>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Req) -> SendBoxFuture<Result<Res, Err>> {
+        self.inner.call(request)
+    }
+}
+
+impl<Req, Res, Err> fmt::Debug for SendBoxService<Req, Res, Err> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SendBoxService").finish()
+    }
+}
+
+/// 可以跨线程共享的装箱[`Service`]特征对象。
+///
+/// 这与[`SendBoxService`]类似，只是[`SendBoxCloneService`]实现了[`Clone`]。
+pub struct SendBoxCloneService<Req, Res, Err> {
+    inner: Box<
+        dyn SendCloneService<
+                Req,
+                Response = Res,
+                Error = Err,
+                Future = SendBoxFuture<Result<Res, Err>>,
+            > + Send
+            + Sync,
+    >,
+}
+
+impl<Req, Res, Err> Clone for SendBoxCloneService<Req, Res, Err> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone_box(),
+        }
+    }
+}
+
+impl<Req, Res, Err> SendBoxCloneService<Req, Res, Err> {
+    pub fn new<S>(inner: S) -> Self
+    where
+        S: Service<Req, Response = Res, Error = Err> + Clone + Send + Sync + 'static,
+        S::Future: Send + 'static,
+    {
+        Self {
+            inner: Box::new(inner.map_future(|f| Box::pin(f) as _)),
+        }
+    }
+}
+
+impl<Req, Res, Err> Service<Req> for SendBoxCloneService<Req, Res, Err> {
+    type Response = Res;
+    type Error = Err;
+    type Future = SendBoxFuture<Result<Res, Err>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Req) -> SendBoxFuture<Result<Res, Err>> {
+        self.inner.call(request)
+    }
+}
+
+impl<Req, Res, Err> fmt::Debug for SendBoxCloneService<Req, Res, Err> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SendBoxCloneService").finish()
+    }
+}
+
+trait SendCloneService<R>: Service<R> {
+    fn clone_box(
+        &self,
+    ) -> Box<
+        dyn SendCloneService<
+                R,
+                Response = Self::Response,
+                Error = Self::Error,
+                Future = Self::Future,
+            > + Send
+            + Sync,
+    >;
+}
+
+impl<S, R> SendCloneService<R> for S
+where
+    S: Service<R> + Clone + Send + Sync + 'static,
+{
+    fn clone_box(
+        &self,
+    ) -> Box<
+        dyn SendCloneService<
+                R,
+                Response = Self::Response,
+                Error = Self::Error,
+                Future = Self::Future,
+            > + Send
+            + Sync,
+    > {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,184 @@
+use core::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::{ready, Stream};
+use futures_util::stream::{FuturesOrdered, FuturesUnordered, StreamExt};
+use pin_project_lite::pin_project;
+
+use crate::Service;
+
+pin_project! {
+    /// 依次使用流中的请求调用服务，并按请求的顺序返回响应的[`Stream`]，
+    /// 由[`ServiceExt::call_all`](super::ServiceExt::call_all)返回。
+    ///
+    /// 每次调用前都会等待服务就绪，已经发出的请求会并发执行。
+    pub struct CallAll<S, St>
+    where
+        St: Stream,
+        S: Service<St::Item>,
+    {
+        service: S,
+        #[pin]
+        stream: St,
+        queue: FuturesOrdered<S::Future>,
+        eof: bool,
+    }
+}
+
+impl<S, St> CallAll<S, St>
+where
+    St: Stream,
+    S: Service<St::Item>,
+{
+    pub(crate) fn new(service: S, stream: St) -> Self {
+        Self {
+            service,
+            stream,
+            queue: FuturesOrdered::new(),
+            eof: false,
+        }
+    }
+}
+
+impl<S, St> Stream for CallAll<S, St>
+where
+    St: Stream,
+    S: Service<St::Item>,
+{
+    type Item = Result<S::Response, S::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        loop {
+            if let Poll::Ready(Some(result)) = this.queue.poll_next_unpin(cx) {
+                return Poll::Ready(Some(result));
+            }
+
+            if *this.eof {
+                return if this.queue.is_empty() {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Pending
+                };
+            }
+
+            if let Err(err) = ready!(this.service.poll_ready(cx)) {
+                return Poll::Ready(Some(Err(err)));
+            }
+
+            match ready!(this.stream.as_mut().poll_next(cx)) {
+                Some(request) => this.queue.push_back(this.service.call(request)),
+                None => *this.eof = true,
+            }
+        }
+    }
+}
+
+impl<S, St> fmt::Debug for CallAll<S, St>
+where
+    St: Stream + fmt::Debug,
+    S: Service<St::Item> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallAll")
+            .field("service", &self.service)
+            .field("stream", &self.stream)
+            .field("in_flight", &self.queue.len())
+            .finish()
+    }
+}
+
+pin_project! {
+    /// 依次使用流中的请求调用服务，并按完成的顺序返回响应的[`Stream`]，
+    /// 由[`ServiceExt::call_all_unordered`](super::ServiceExt::call_all_unordered)返回。
+    ///
+    /// 每次调用前都会等待服务就绪，同时执行的请求数不会超过给定的限制。
+    pub struct CallAllUnordered<S, St>
+    where
+        St: Stream,
+        S: Service<St::Item>,
+    {
+        service: S,
+        #[pin]
+        stream: St,
+        queue: FuturesUnordered<S::Future>,
+        limit: usize,
+        eof: bool,
+    }
+}
+
+impl<S, St> CallAllUnordered<S, St>
+where
+    St: Stream,
+    S: Service<St::Item>,
+{
+    pub(crate) fn new(service: S, stream: St, limit: usize) -> Self {
+        assert!(limit > 0, "limit must be greater than 0");
+        Self {
+            service,
+            stream,
+            queue: FuturesUnordered::new(),
+            limit,
+            eof: false,
+        }
+    }
+}
+
+impl<S, St> Stream for CallAllUnordered<S, St>
+where
+    St: Stream,
+    S: Service<St::Item>,
+{
+    type Item = Result<S::Response, S::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        loop {
+            if let Poll::Ready(Some(result)) = this.queue.poll_next_unpin(cx) {
+                return Poll::Ready(Some(result));
+            }
+
+            if *this.eof {
+                return if this.queue.is_empty() {
+                    Poll::Ready(None)
+                } else {
+                    Poll::Pending
+                };
+            }
+
+            // 达到并发限制时，等待已经发出的请求完成。
+            if this.queue.len() >= *this.limit {
+                return Poll::Pending;
+            }
+
+            if let Err(err) = ready!(this.service.poll_ready(cx)) {
+                return Poll::Ready(Some(Err(err)));
+            }
+
+            match ready!(this.stream.as_mut().poll_next(cx)) {
+                Some(request) => this.queue.push(this.service.call(request)),
+                None => *this.eof = true,
+            }
+        }
+    }
+}
+
+impl<S, St> fmt::Debug for CallAllUnordered<S, St>
+where
+    St: Stream + fmt::Debug,
+    S: Service<St::Item> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallAllUnordered")
+            .field("service", &self.service)
+            .field("stream", &self.stream)
+            .field("in_flight", &self.queue.len())
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
@@ -0,0 +1,130 @@
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{Service, Wrap};
+
+/// 两个服务或包裹中的一个。
+///
+/// 作为服务时，两个服务的响应和错误类型必须相同；作为包裹时，包裹后的服务也是[`Either`]。
+///
+/// # 例子
+///
+/// ```
+/// use futures_util::FutureExt;
+/// use puzz_service::util::{service_fn, Either};
+/// use puzz_service::Service;
+///
+/// let service = if true {
+///     Either::Left(service_fn(|request: u32| async move { Ok::<_, ()>(request + 1) }))
+/// } else {
+///     Either::Right(service_fn(|request: u32| async move { Ok::<_, ()>(request - 1) }))
+/// };
+///
+/// assert_eq!(service.call(1).now_or_never(), Some(Ok(2)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+impl<A, B, Req> Service<Req> for Either<A, B>
+where
+    A: Service<Req>,
+    B: Service<Req, Response = A::Response, Error = A::Error>,
+{
+    type Response = A::Response;
+    type Error = A::Error;
+    type Future = EitherFuture<A::Future, B::Future>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self {
+            Either::Left(service) => service.poll_ready(cx),
+            Either::Right(service) => service.poll_ready(cx),
+        }
+    }
+
+    fn call(&self, request: Req) -> Self::Future {
+        match self {
+            Either::Left(service) => EitherFuture::Left {
+                fut: service.call(request),
+            },
+            Either::Right(service) => EitherFuture::Right {
+                fut: service.call(request),
+            },
+        }
+    }
+}
+
+impl<A, B, S> Wrap<S> for Either<A, B>
+where
+    A: Wrap<S>,
+    B: Wrap<S>,
+{
+    type Service = Either<A::Service, B::Service>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        match self {
+            Either::Left(wrap) => Either::Left(wrap.wrap(service)),
+            Either::Right(wrap) => Either::Right(wrap.wrap(service)),
+        }
+    }
+}
+
+/// 为[`None`]时不包裹服务。
+impl<W, S> Wrap<S> for Option<W>
+where
+    W: Wrap<S>,
+{
+    type Service = Either<W::Service, S>;
+
+    fn wrap(self, service: S) -> Self::Service {
+        match self {
+            Some(wrap) => Either::Left(wrap.wrap(service)),
+            None => Either::Right(service),
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// [`Either`]服务返回的[`Future`]。
+    #[project = EitherFutureProj]
+    pub enum EitherFuture<A, B> {
+        Left {
+            #[pin]
+            fut: A,
+        },
+        Right {
+            #[pin]
+            fut: B,
+        },
+    }
+}
+
+impl<A, B> Future for EitherFuture<A, B>
+where
+    A: Future,
+    B: Future<Output = A::Output>,
+{
+    type Output = A::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            EitherFutureProj::Left { fut } => fut.poll(cx),
+            EitherFutureProj::Right { fut } => fut.poll(cx),
+        }
+    }
+}
+
+impl<A, B> fmt::Debug for EitherFuture<A, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EitherFuture::Left { .. } => f.debug_tuple("EitherFuture::Left").finish(),
+            EitherFuture::Right { .. } => f.debug_tuple("EitherFuture::Right").finish(),
+        }
+    }
+}
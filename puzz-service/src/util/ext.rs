@@ -1,8 +1,11 @@
+use futures_core::Stream;
+
 use crate::{Service, Wrap};
 
 use super::{
-    AndThen, BoxCloneService, BoxService, MapErr, MapFuture, MapRequest, MapResponse, MapResult,
-    RcService, Then,
+    AndThen, AsyncFilter, BoxCloneService, BoxService, CallAll, CallAllUnordered, Filter, MapErr,
+    MapFuture, MapRequest, MapResponse, MapResult, Oneshot, RcService, Ready, Retry,
+    SendBoxCloneService, SendBoxService, Then,
 };
 
 pub trait ServiceExt<Req>: Service<Req> {
@@ -14,6 +17,99 @@ pub trait ServiceExt<Req>: Service<Req> {
         wrap.wrap(self)
     }
 
+    /// 等待服务就绪，返回服务的引用。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use futures_util::FutureExt;
+    /// use puzz_service::util::service_fn;
+    /// use puzz_service::{Service, ServiceExt};
+    ///
+    /// let service = service_fn(|request: u32| async move { Ok::<_, ()>(request + 1) });
+    ///
+    /// let response = async { service.ready().await?.call(1).await };
+    /// assert_eq!(response.now_or_never(), Some(Ok(2)));
+    /// ```
+    fn ready(&self) -> Ready<'_, Self, Req> {
+        Ready::new(self)
+    }
+
+    /// 等待服务就绪后使用给定的请求调用服务。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use futures_util::FutureExt;
+    /// use puzz_service::util::service_fn;
+    /// use puzz_service::ServiceExt;
+    ///
+    /// let service = service_fn(|request: u32| async move { Ok::<_, ()>(request + 1) });
+    ///
+    /// assert_eq!(service.oneshot(1).now_or_never(), Some(Ok(2)));
+    /// ```
+    fn oneshot(self, request: Req) -> Oneshot<Self, Req>
+    where
+        Self: Sized,
+    {
+        Oneshot::new(self, request)
+    }
+
+    /// 依次使用流中的请求调用服务，返回按请求顺序产生响应的[`Stream`]。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use futures_util::{stream, FutureExt, StreamExt};
+    /// use puzz_service::util::service_fn;
+    /// use puzz_service::ServiceExt;
+    ///
+    /// let service = service_fn(|request: u32| async move { Ok::<_, ()>(request * 2) });
+    ///
+    /// let responses = service.call_all(stream::iter([1, 2, 3])).collect::<Vec<_>>();
+    /// assert_eq!(responses.now_or_never(), Some(vec![Ok(2), Ok(4), Ok(6)]));
+    /// ```
+    fn call_all<St>(self, requests: St) -> CallAll<Self, St>
+    where
+        Self: Sized,
+        St: Stream<Item = Req>,
+    {
+        CallAll::new(self, requests)
+    }
+
+    /// 依次使用流中的请求调用服务，返回按完成顺序产生响应的[`Stream`]，
+    /// 同时执行的请求数不超过`limit`。
+    ///
+    /// # 恐慌
+    ///
+    /// 如果`limit`为`0`，将会发生恐慌。
+    ///
+    /// # 例子
+    ///
+    /// ```
+    /// use futures_util::{stream, FutureExt, StreamExt};
+    /// use puzz_service::util::service_fn;
+    /// use puzz_service::ServiceExt;
+    ///
+    /// let service = service_fn(|request: u32| async move { Ok::<_, ()>(request * 2) });
+    ///
+    /// let mut responses = service
+    ///     .call_all_unordered(stream::iter([1, 2, 3]), 2)
+    ///     .map(Result::unwrap)
+    ///     .collect::<Vec<_>>()
+    ///     .now_or_never()
+    ///     .unwrap();
+    /// responses.sort();
+    /// assert_eq!(responses, [2, 4, 6]);
+    /// ```
+    fn call_all_unordered<St>(self, requests: St, limit: usize) -> CallAllUnordered<Self, St>
+    where
+        Self: Sized,
+        St: Stream<Item = Req>,
+    {
+        CallAllUnordered::new(self, requests, limit)
+    }
+
     fn and_then<F>(self, f: F) -> AndThen<Self, F>
     where
         Self: Sized,
@@ -63,6 +159,27 @@ pub trait ServiceExt<Req>: Service<Req> {
         MapResult::new(self, f)
     }
 
+    fn filter<P>(self, predicate: P) -> Filter<Self, P>
+    where
+        Self: Sized,
+    {
+        Filter::new(self, predicate)
+    }
+
+    fn filter_async<P>(self, predicate: P) -> AsyncFilter<Self, P>
+    where
+        Self: Sized,
+    {
+        AsyncFilter::new(self, predicate)
+    }
+
+    fn retry<P>(self, policy: P) -> Retry<Self, P>
+    where
+        Self: Sized,
+    {
+        Retry::new(self, policy)
+    }
+
     fn boxed(self) -> BoxService<Req, Self::Response, Self::Error>
     where
         Self: Sized + 'static,
@@ -86,6 +203,22 @@ pub trait ServiceExt<Req>: Service<Req> {
     {
         RcService::new(self)
     }
+
+    fn send_boxed(self) -> SendBoxService<Req, Self::Response, Self::Error>
+    where
+        Self: Sized + Send + Sync + 'static,
+        Self::Future: Send + 'static,
+    {
+        SendBoxService::new(self)
+    }
+
+    fn send_boxed_clone(self) -> SendBoxCloneService<Req, Self::Response, Self::Error>
+    where
+        Self: Sized + Clone + Send + Sync + 'static,
+        Self::Future: Send + 'static,
+    {
+        SendBoxCloneService::new(self)
+    }
 }
 
 impl<S, Req> ServiceExt<Req> for S where S: Service<Req> {}
@@ -0,0 +1,341 @@
+use alloc::sync::Arc;
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+use crate::Service;
+
+/// 检查或转换请求的谓词。
+///
+/// 对于闭包`Fn(Req) -> Result<R, E>`，已经实现了该特征。
+pub trait Predicate<Req> {
+    /// 转换后的请求。
+    type Request;
+
+    /// 拒绝请求时返回的错误。
+    type Error;
+
+    /// 检查请求，返回转换后的请求或者拒绝请求的错误。
+    fn check(&self, request: Req) -> Result<Self::Request, Self::Error>;
+}
+
+impl<F, Req, R, E> Predicate<Req> for F
+where
+    F: Fn(Req) -> Result<R, E>,
+{
+    type Request = R;
+    type Error = E;
+
+    fn check(&self, request: Req) -> Result<R, E> {
+        self(request)
+    }
+}
+
+/// 异步检查或转换请求的谓词。
+///
+/// 对于闭包`Fn(Req) -> impl Future<Output = Result<R, E>>`，已经实现了该特征。
+pub trait AsyncPredicate<Req> {
+    /// 转换后的请求。
+    type Request;
+
+    /// 拒绝请求时返回的错误。
+    type Error;
+
+    /// 异步返回的检查结果。
+    type Future: Future<Output = Result<Self::Request, Self::Error>>;
+
+    /// 检查请求，返回转换后的请求或者拒绝请求的错误。
+    fn check(&self, request: Req) -> Self::Future;
+}
+
+impl<F, Fut, Req, R, E> AsyncPredicate<Req> for F
+where
+    F: Fn(Req) -> Fut,
+    Fut: Future<Output = Result<R, E>>,
+{
+    type Request = R;
+    type Error = E;
+    type Future = Fut;
+
+    fn check(&self, request: Req) -> Fut {
+        self(request)
+    }
+}
+
+/// [`Filter`]和[`AsyncFilter`]返回的错误。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterError<R, E> {
+    /// 谓词拒绝了请求。
+    Rejected(R),
+    /// 内部服务返回的错误。
+    Inner(E),
+}
+
+impl<R, E> fmt::Display for FilterError<R, E>
+where
+    R: fmt::Display,
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterError::Rejected(e) => write!(f, "request rejected: {}", e),
+            FilterError::Inner(e) => e.fmt(f),
+        }
+    }
+}
+
+/// 在调用内部服务前使用[`Predicate`]检查或转换请求的服务。
+///
+/// # 例子
+///
+/// ```
+/// use futures_util::FutureExt;
+/// use puzz_service::util::{service_fn, FilterError};
+/// use puzz_service::{Service, ServiceExt};
+///
+/// let service = service_fn(|request: u32| async move { Ok::<_, ()>(request * 2) })
+///     .filter(|request: &str| request.parse::<u32>().map_err(|_| "not a number"));
+///
+/// assert_eq!(service.call("21").now_or_never(), Some(Ok(42)));
+/// assert_eq!(
+///     service.call("hi!").now_or_never(),
+///     Some(Err(FilterError::Rejected("not a number")))
+/// );
+/// ```
+#[derive(Clone, Copy)]
+pub struct Filter<S, P> {
+    inner: S,
+    predicate: P,
+}
+
+impl<S, P> Filter<S, P> {
+    pub fn new(inner: S, predicate: P) -> Self {
+        Self { inner, predicate }
+    }
+}
+
+impl<S, P, Req> Service<Req> for Filter<S, P>
+where
+    P: Predicate<Req>,
+    S: Service<P::Request>,
+{
+    type Response = S::Response;
+    type Error = FilterError<P::Error, S::Error>;
+    type Future = FilterFuture<S::Future, P::Error>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(FilterError::Inner)
+    }
+
+    fn call(&self, request: Req) -> Self::Future {
+        match self.predicate.check(request) {
+            Ok(request) => FilterFuture::Calling {
+                fut: self.inner.call(request),
+            },
+            Err(err) => FilterFuture::Rejected { error: Some(err) },
+        }
+    }
+}
+
+impl<S, P> fmt::Debug for Filter<S, P>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Filter")
+            .field("inner", &self.inner)
+            .field("predicate", &core::any::type_name::<P>())
+            .finish()
+    }
+}
+
+pin_project! {
+    /// [`Filter`]返回的[`Future`]。
+    #[project = FilterFutureProj]
+    pub enum FilterFuture<F, R> {
+        Calling {
+            #[pin]
+            fut: F,
+        },
+        Rejected {
+            error: Option<R>,
+        },
+    }
+}
+
+impl<F, R, Res, E> Future for FilterFuture<F, R>
+where
+    F: Future<Output = Result<Res, E>>,
+{
+    type Output = Result<Res, FilterError<R, E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            FilterFutureProj::Calling { fut } => fut.poll(cx).map_err(FilterError::Inner),
+            FilterFutureProj::Rejected { error } => Poll::Ready(Err(FilterError::Rejected(
+                error.take().expect("polled after completion"),
+            ))),
+        }
+    }
+}
+
+impl<F, R> fmt::Debug for FilterFuture<F, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Calling { .. } => f.debug_struct("Calling").finish(),
+            Self::Rejected { .. } => f.debug_struct("Rejected").finish(),
+        }
+    }
+}
+
+/// 在调用内部服务前使用[`AsyncPredicate`]异步检查或转换请求的服务。
+///
+/// # 例子
+///
+/// ```
+/// use futures_util::FutureExt;
+/// use puzz_service::util::{service_fn, FilterError};
+/// use puzz_service::{Service, ServiceExt};
+///
+/// let service = service_fn(|request: u32| async move { Ok::<_, ()>(request) })
+///     .filter_async(|request: u32| async move {
+///         if request < 10 {
+///             Ok(request)
+///         } else {
+///             Err("too large")
+///         }
+///     });
+///
+/// assert_eq!(service.call(1).now_or_never(), Some(Ok(1)));
+/// assert_eq!(
+///     service.call(42).now_or_never(),
+///     Some(Err(FilterError::Rejected("too large")))
+/// );
+///
+/// // 服务和谓词实现`Send`和`Sync`时，`AsyncFilter`及其返回的`Future`也实现。
+/// fn assert_send_sync<T: Send + Sync>(_: &T) {}
+/// assert_send_sync(&service);
+/// assert_send_sync(&service.call(1));
+/// ```
+pub struct AsyncFilter<S, P> {
+    // 检查完成后需要在调用返回后继续持有服务。
+    inner: Arc<S>,
+    predicate: P,
+}
+
+impl<S, P> AsyncFilter<S, P> {
+    pub fn new(inner: S, predicate: P) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            predicate,
+        }
+    }
+}
+
+impl<S, P> Clone for AsyncFilter<S, P>
+where
+    P: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            predicate: self.predicate.clone(),
+        }
+    }
+}
+
+impl<S, P, Req> Service<Req> for AsyncFilter<S, P>
+where
+    P: AsyncPredicate<Req>,
+    S: Service<P::Request>,
+{
+    type Response = S::Response;
+    type Error = FilterError<P::Error, S::Error>;
+    type Future = AsyncFilterFuture<S, P::Future, P::Request>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(FilterError::Inner)
+    }
+
+    fn call(&self, request: Req) -> Self::Future {
+        AsyncFilterFuture::Checking {
+            fut: self.predicate.check(request),
+            inner: Some(self.inner.clone()),
+        }
+    }
+}
+
+impl<S, P> fmt::Debug for AsyncFilter<S, P>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncFilter")
+            .field("inner", &self.inner)
+            .field("predicate", &core::any::type_name::<P>())
+            .finish()
+    }
+}
+
+pin_project! {
+    /// [`AsyncFilter`]返回的[`Future`]。
+    #[project = AsyncFilterFutureProj]
+    pub enum AsyncFilterFuture<S, F, Req>
+    where
+        S: Service<Req>,
+    {
+        Checking {
+            #[pin]
+            fut: F,
+            inner: Option<Arc<S>>,
+        },
+        Calling {
+            #[pin]
+            fut: S::Future,
+        },
+    }
+}
+
+impl<S, F, Req, R> Future for AsyncFilterFuture<S, F, Req>
+where
+    S: Service<Req>,
+    F: Future<Output = Result<Req, R>>,
+{
+    type Output = Result<S::Response, FilterError<R, S::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                AsyncFilterFutureProj::Checking { fut, inner } => match ready!(fut.poll(cx)) {
+                    Ok(request) => {
+                        let inner = inner.take().expect("polled after completion");
+                        let fut = inner.call(request);
+                        self.set(AsyncFilterFuture::Calling { fut });
+                    }
+                    Err(err) => return Poll::Ready(Err(FilterError::Rejected(err))),
+                },
+                AsyncFilterFutureProj::Calling { fut } => {
+                    return fut.poll(cx).map_err(FilterError::Inner)
+                }
+            }
+        }
+    }
+}
+
+impl<S, F, Req> fmt::Debug for AsyncFilterFuture<S, F, Req>
+where
+    S: Service<Req>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Checking { .. } => f.debug_struct("Checking").finish(),
+            Self::Calling { .. } => f.debug_struct("Calling").finish(),
+        }
+    }
+}
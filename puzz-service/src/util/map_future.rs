@@ -1,4 +1,8 @@
-use core::{fmt, future::Future};
+use core::{
+    fmt,
+    future::Future,
+    task::{Context, Poll},
+};
 
 use crate::Service;
 
@@ -19,11 +23,16 @@ where
     S: Service<Req>,
     F: Fn(S::Future) -> Fut,
     Fut: Future<Output = Result<Res, Err>>,
+    Err: From<S::Error>,
 {
     type Response = Res;
     type Error = Err;
     type Future = Fut;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
     fn call(&self, request: Req) -> Self::Future {
         (self.f)(self.inner.call(request))
     }
@@ -1,4 +1,7 @@
-use core::fmt;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
 
 use crate::Service;
 
@@ -23,6 +26,10 @@ where
     type Error = S::Error;
     type Future = S::Future;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
     fn call(&self, request: R1) -> Self::Future {
         self.inner.call((self.f)(request))
     }
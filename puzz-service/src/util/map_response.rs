@@ -1,4 +1,7 @@
-use core::fmt;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
 
 use futures_util::TryFutureExt;
 
@@ -29,6 +32,10 @@ where
     type Error = S::Error;
     type Future = MapResponseFuture<S::Future, F>;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
     fn call(&self, request: Req) -> Self::Future {
         MapResponseFuture::new(self.inner.call(request).map_ok(self.f.clone()))
     }
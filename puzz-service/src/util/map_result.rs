@@ -1,4 +1,7 @@
-use core::fmt;
+use core::{
+    fmt,
+    task::{Context, Poll},
+};
 
 use futures_util::FutureExt;
 
@@ -24,11 +27,16 @@ impl<S, F, Req, Res, Err> Service<Req> for MapResult<S, F>
 where
     S: Service<Req>,
     F: FnOnce(Result<S::Response, S::Error>) -> Result<Res, Err> + Clone,
+    Err: From<S::Error>,
 {
     type Response = Res;
     type Error = Err;
     type Future = MapResultFuture<S::Future, F>;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
     fn call(&self, request: Req) -> Self::Future {
         MapResultFuture::new(self.inner.call(request).map(self.f.clone()))
     }
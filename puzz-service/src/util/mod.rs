@@ -1,23 +1,40 @@
 mod and_then;
 mod boxed;
+mod call_all;
+mod either;
 mod ext;
+mod filter;
 mod map_err;
 mod map_future;
 mod map_request;
 mod map_response;
 mod map_result;
+mod oneshot;
+mod ready;
+mod retry;
 mod service_fn;
 mod then;
 mod wrap_fn;
 
 pub use and_then::{AndThen, AndThenFuture};
-pub use boxed::{BoxCloneService, BoxFuture, BoxService, RcService};
+pub use boxed::{
+    BoxCloneService, BoxFuture, BoxService, RcService, SendBoxCloneService, SendBoxFuture,
+    SendBoxService,
+};
+pub use call_all::{CallAll, CallAllUnordered};
+pub use either::{Either, EitherFuture};
 pub use ext::ServiceExt;
+pub use filter::{
+    AsyncFilter, AsyncFilterFuture, AsyncPredicate, Filter, FilterError, FilterFuture, Predicate,
+};
 pub use map_err::{MapErr, MapErrFuture};
 pub use map_future::MapFuture;
 pub use map_request::MapRequest;
 pub use map_response::{MapResponse, MapResponseFuture};
 pub use map_result::{MapResult, MapResultFuture};
+pub use oneshot::Oneshot;
+pub use ready::Ready;
+pub use retry::{Policy, Retry, RetryFuture};
 pub use service_fn::{service_fn, ServiceFn};
 pub use then::{Then, ThenFuture};
 pub use wrap_fn::{wrap_fn, WrapFn};
@@ -0,0 +1,77 @@
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::ready;
+use pin_project_lite::pin_project;
+
+use crate::Service;
+
+pin_project! {
+    /// 等待服务就绪后调用服务的[`Future`]，由[`ServiceExt::oneshot`](super::ServiceExt::oneshot)返回。
+    #[project = OneshotProj]
+    pub enum Oneshot<S, Req>
+    where
+        S: Service<Req>,
+    {
+        NotReady {
+            service: S,
+            request: Option<Req>,
+        },
+        Called {
+            #[pin]
+            fut: S::Future,
+        },
+    }
+}
+
+impl<S, Req> Oneshot<S, Req>
+where
+    S: Service<Req>,
+{
+    pub(crate) fn new(service: S, request: Req) -> Self {
+        Oneshot::NotReady {
+            service,
+            request: Some(request),
+        }
+    }
+}
+
+impl<S, Req> Future for Oneshot<S, Req>
+where
+    S: Service<Req>,
+{
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                OneshotProj::NotReady { service, request } => {
+                    ready!(service.poll_ready(cx))?;
+                    let request = request.take().expect("polled after completion");
+                    let fut = service.call(request);
+                    self.set(Oneshot::Called { fut });
+                }
+                OneshotProj::Called { fut } => return fut.poll(cx),
+            }
+        }
+    }
+}
+
+impl<S, Req> fmt::Debug for Oneshot<S, Req>
+where
+    S: Service<Req> + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotReady { service, .. } => f
+                .debug_struct("NotReady")
+                .field("service", service)
+                .finish(),
+            Self::Called { .. } => f.debug_struct("Called").finish(),
+        }
+    }
+}
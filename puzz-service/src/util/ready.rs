@@ -0,0 +1,53 @@
+use core::{
+    fmt,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::Service;
+
+/// 等待服务就绪的[`Future`]，由[`ServiceExt::ready`](super::ServiceExt::ready)返回。
+pub struct Ready<'a, S, Req>
+where
+    S: ?Sized,
+{
+    service: &'a S,
+    _req: PhantomData<fn(Req)>,
+}
+
+impl<'a, S, Req> Ready<'a, S, Req>
+where
+    S: ?Sized,
+{
+    pub(crate) fn new(service: &'a S) -> Self {
+        Self {
+            service,
+            _req: PhantomData,
+        }
+    }
+}
+
+impl<'a, S, Req> Future for Ready<'a, S, Req>
+where
+    S: Service<Req> + ?Sized,
+{
+    type Output = Result<&'a S, S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let service = self.service;
+        service.poll_ready(cx).map_ok(|()| service)
+    }
+}
+
+impl<'a, S, Req> fmt::Debug for Ready<'a, S, Req>
+where
+    S: fmt::Debug + ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ready")
+            .field("service", &self.service)
+            .finish()
+    }
+}
@@ -0,0 +1,224 @@
+use alloc::sync::Arc;
+use core::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::Service;
+
+/// 决定是否重试请求的策略。
+///
+/// 每个请求都会克隆一份策略，因此策略可以在返回的[`Future`]中保存每个请求的状态（例如已经重试的次数）。
+pub trait Policy<Req, Res, E>: Sized {
+    /// 等待重试的[`Future`]，完成时返回下一次尝试使用的策略。
+    type Future: Future<Output = Self>;
+
+    /// 检查请求的结果，返回[`Some`]表示需要重试，返回[`None`]表示直接返回结果。
+    fn retry(&self, request: &Req, result: Result<&Res, &E>) -> Option<Self::Future>;
+
+    /// 克隆请求以便重试，返回[`None`]表示请求无法重试。
+    fn clone_request(&self, request: &Req) -> Option<Req>;
+}
+
+/// 根据[`Policy`]重试请求的服务。
+///
+/// # 例子
+///
+/// ```
+/// use core::cell::Cell;
+/// use core::future::{ready, Ready};
+///
+/// use futures_util::FutureExt;
+/// use puzz_service::util::{service_fn, Policy};
+/// use puzz_service::{Service, ServiceExt};
+///
+/// // 最多重试给定的次数。
+/// #[derive(Clone)]
+/// struct Attempts(usize);
+///
+/// impl<E> Policy<u32, u32, E> for Attempts {
+///     type Future = Ready<Self>;
+///
+///     fn retry(&self, _: &u32, result: Result<&u32, &E>) -> Option<Self::Future> {
+///         match result {
+///             Err(_) if self.0 > 0 => Some(ready(Attempts(self.0 - 1))),
+///             _ => None,
+///         }
+///     }
+///
+///     fn clone_request(&self, request: &u32) -> Option<u32> {
+///         Some(*request)
+///     }
+/// }
+///
+/// let calls = Cell::new(0);
+/// let service = service_fn(|request: u32| {
+///     calls.set(calls.get() + 1);
+///     let failed = calls.get() < 3;
+///     async move { if failed { Err("unavailable") } else { Ok(request) } }
+/// })
+/// .retry(Attempts(2));
+///
+/// assert_eq!(service.call(1).now_or_never(), Some(Ok(1)));
+/// assert_eq!(calls.get(), 3);
+///
+/// // 服务和策略实现`Send`和`Sync`时，`Retry`及其返回的`Future`也实现。
+/// fn assert_send_sync<T: Send + Sync>(_: &T) {}
+/// let service = service_fn(|request: u32| async move { Ok::<_, ()>(request) }).retry(Attempts(2));
+/// assert_send_sync(&service);
+/// assert_send_sync(&service.call(1));
+/// ```
+pub struct Retry<S, P> {
+    inner: Arc<S>,
+    policy: P,
+}
+
+impl<S, P> Retry<S, P> {
+    pub fn new(inner: S, policy: P) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            policy,
+        }
+    }
+}
+
+impl<S, P> Clone for Retry<S, P>
+where
+    P: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+impl<S, P, Req> Service<Req> for Retry<S, P>
+where
+    S: Service<Req>,
+    P: Policy<Req, S::Response, S::Error> + Clone,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = RetryFuture<S, P, Req>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&self, request: Req) -> Self::Future {
+        let cloned = self.policy.clone_request(&request);
+        RetryFuture::Calling {
+            fut: self.inner.call(request),
+            state: Some(State {
+                inner: self.inner.clone(),
+                policy: self.policy.clone(),
+                request: cloned,
+            }),
+        }
+    }
+}
+
+impl<S, P> fmt::Debug for Retry<S, P>
+where
+    S: fmt::Debug,
+    P: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Retry")
+            .field("inner", &self.inner)
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+struct State<S, P, Req> {
+    inner: Arc<S>,
+    policy: P,
+    request: Option<Req>,
+}
+
+pin_project_lite::pin_project! {
+    /// [`Retry`]返回的[`Future`]。
+    #[project = RetryFutureProj]
+    pub enum RetryFuture<S, P, Req>
+    where
+        S: Service<Req>,
+        P: Policy<Req, S::Response, S::Error>,
+    {
+        Calling {
+            #[pin]
+            fut: S::Future,
+            state: Option<State<S, P, Req>>,
+        },
+        Waiting {
+            #[pin]
+            fut: P::Future,
+            state: Option<State<S, P, Req>>,
+        },
+    }
+}
+
+impl<S, P, Req> Future for RetryFuture<S, P, Req>
+where
+    S: Service<Req>,
+    P: Policy<Req, S::Response, S::Error>,
+{
+    type Output = Result<S::Response, S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                RetryFutureProj::Calling { fut, state } => {
+                    let result = futures_core::ready!(fut.poll(cx));
+                    let mut state = state.take().expect("polled after completion");
+
+                    let fut = match &state.request {
+                        Some(request) => state.policy.retry(request, result.as_ref()),
+                        None => None,
+                    };
+
+                    match fut {
+                        Some(fut) => self.set(RetryFuture::Waiting {
+                            fut,
+                            state: Some(state),
+                        }),
+                        None => {
+                            state.request.take();
+                            return Poll::Ready(result);
+                        }
+                    }
+                }
+                RetryFutureProj::Waiting { fut, state } => {
+                    let policy = futures_core::ready!(fut.poll(cx));
+                    let state = state.take().expect("polled after completion");
+
+                    let request = state.request.expect("request is cloned");
+                    let cloned = policy.clone_request(&request);
+                    let fut = state.inner.call(request);
+                    self.set(RetryFuture::Calling {
+                        fut,
+                        state: Some(State {
+                            inner: state.inner,
+                            policy,
+                            request: cloned,
+                        }),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl<S, P, Req> fmt::Debug for RetryFuture<S, P, Req>
+where
+    S: Service<Req>,
+    P: Policy<Req, S::Response, S::Error>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryFuture").finish()
+    }
+}
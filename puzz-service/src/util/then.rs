@@ -1,4 +1,8 @@
-use core::{fmt, future::Future};
+use core::{
+    fmt,
+    future::Future,
+    task::{Context, Poll},
+};
 
 use futures_util::FutureExt;
 
@@ -25,11 +29,16 @@ where
     S: Service<Req>,
     F: FnOnce(Result<S::Response, S::Error>) -> Fut + Clone,
     Fut: Future<Output = Result<Res, Err>>,
+    Err: From<S::Error>,
 {
     type Response = Res;
     type Error = Err;
     type Future = ThenFuture<S::Future, Fut, F>;
 
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
     fn call(&self, request: Req) -> Self::Future {
         ThenFuture::new(self.inner.call(request).then(self.f.clone()))
     }
@@ -0,0 +1,350 @@
+//! Cookie。
+//!
+//! 从请求的`Cookie`标头中提取[`CookieJar`]，修改后的Cookie罐可以作为响应返回（或通过`into_headers`与响应正文组合），
+//! 其中新增和删除的Cookie会转换为`Set-Cookie`标头。
+//!
+//! [`SignedCookieJar`]和[`PrivateCookieJar`]分别对Cookie进行签名和加密，并支持通过[`CookieKeys`]轮换密钥。
+
+use std::fmt;
+
+use puzz_core::http::header::{COOKIE, SET_COOKIE};
+use puzz_core::http::{HeaderMap, HeaderValue};
+use puzz_core::response::{IntoResponse, Response};
+use puzz_core::Request;
+
+pub use ::cookie::time;
+pub use ::cookie::{Cookie, CookieBuilder, Expiration, Key, SameSite};
+
+/// 提取请求中的Cookie。
+///
+/// # 例子
+///
+/// ```
+/// use puzz::extract::cookie::{cookies, Cookie, SameSite};
+/// use puzz::response::IntoResponse;
+/// use puzz::Request;
+///
+/// let request = Request::builder()
+///     .header("cookie", "theme=dark; lang=zh")
+///     .body(Default::default())
+///     .unwrap();
+///
+/// let mut jar = cookies(&request);
+/// assert_eq!(jar.get("theme").unwrap().value(), "dark");
+///
+/// jar.remove("lang");
+/// jar.add(
+///     Cookie::build(("session", "abc"))
+///         .path("/")
+///         .http_only(true)
+///         .same_site(SameSite::Lax),
+/// );
+///
+/// let response = (jar.into_headers(), "ok").into_response();
+/// let set_cookie = response
+///     .headers()
+///     .get_all("set-cookie")
+///     .iter()
+///     .map(|value| value.to_str().unwrap())
+///     .collect::<Vec<_>>();
+/// assert_eq!(set_cookie.len(), 2);
+/// assert!(set_cookie.contains(&"session=abc; HttpOnly; SameSite=Lax; Path=/"));
+/// ```
+pub fn cookies(request: &Request) -> CookieJar {
+    CookieJar {
+        jar: parse(request),
+    }
+}
+
+/// 提取请求中的Cookie，并使用`keys`验证签名。
+///
+/// # 例子
+///
+/// ```
+/// use puzz::extract::cookie::{signed_cookies, CookieKeys, Key};
+/// use puzz::response::IntoResponse;
+/// use puzz::Request;
+///
+/// let old = Key::generate();
+/// let new = Key::generate();
+///
+/// // 使用旧密钥签名Cookie。
+/// let mut jar = signed_cookies(&Request::default(), &CookieKeys::new(old.clone()));
+/// jar.add(("user", "alice"));
+/// let response = jar.into_response();
+/// let cookie = response.headers()["set-cookie"].to_str().unwrap().to_owned();
+///
+/// // 轮换密钥后，旧密钥签名的Cookie仍然有效。
+/// let request = Request::builder()
+///     .header("cookie", cookie)
+///     .body(Default::default())
+///     .unwrap();
+/// let keys = CookieKeys::new(new).previous(old);
+/// let jar = signed_cookies(&request, &keys);
+/// assert_eq!(jar.get("user").unwrap().value(), "alice");
+/// ```
+pub fn signed_cookies(request: &Request, keys: &CookieKeys) -> SignedCookieJar {
+    SignedCookieJar {
+        jar: parse(request),
+        keys: keys.clone(),
+    }
+}
+
+/// 提取请求中的Cookie，并使用`keys`解密。
+pub fn private_cookies(request: &Request, keys: &CookieKeys) -> PrivateCookieJar {
+    PrivateCookieJar {
+        jar: parse(request),
+        keys: keys.clone(),
+    }
+}
+
+fn parse(request: &Request) -> ::cookie::CookieJar {
+    let mut jar = ::cookie::CookieJar::new();
+
+    request
+        .headers()
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| Cookie::split_parse(value.to_owned()))
+        .filter_map(Result::ok)
+        .for_each(|cookie| jar.add_original(cookie));
+
+    jar
+}
+
+fn append_set_cookie(jar: &::cookie::CookieJar, headers: &mut HeaderMap) {
+    for cookie in jar.delta() {
+        if let Ok(value) = HeaderValue::try_from(cookie.to_string()) {
+            headers.append(SET_COOKIE, value);
+        }
+    }
+}
+
+/// 签名和加密Cookie的密钥。
+///
+/// 新的Cookie总是使用当前密钥签名或加密，读取时依次尝试当前密钥和旧密钥，以便平滑地轮换密钥。
+#[derive(Clone)]
+pub struct CookieKeys {
+    current: Key,
+    previous: Vec<Key>,
+}
+
+impl CookieKeys {
+    /// 使用当前密钥创建。
+    pub fn new(current: Key) -> Self {
+        Self {
+            current,
+            previous: Vec::new(),
+        }
+    }
+
+    /// 添加一个旧密钥，使用旧密钥签名或加密的Cookie仍然可以读取。
+    pub fn previous(mut self, key: Key) -> Self {
+        self.previous.push(key);
+        self
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Key> {
+        std::iter::once(&self.current).chain(&self.previous)
+    }
+}
+
+impl From<Key> for CookieKeys {
+    fn from(key: Key) -> Self {
+        Self::new(key)
+    }
+}
+
+impl fmt::Debug for CookieKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CookieKeys")
+            .field("previous", &self.previous.len())
+            .finish()
+    }
+}
+
+/// Cookie罐。
+///
+/// 作为响应返回时，新增和删除的Cookie会转换为`Set-Cookie`标头。
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    jar: ::cookie::CookieJar,
+}
+
+impl CookieJar {
+    /// 创建一个空的Cookie罐。
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 获取给定名称的Cookie。
+    pub fn get(&self, name: &str) -> Option<&Cookie<'static>> {
+        self.jar.get(name)
+    }
+
+    /// 添加Cookie。
+    pub fn add<C>(&mut self, cookie: C)
+    where
+        C: Into<Cookie<'static>>,
+    {
+        self.jar.add(cookie);
+    }
+
+    /// 删除Cookie。
+    ///
+    /// 如果Cookie设置了路径或域，删除时需要指定相同的路径和域。
+    pub fn remove<C>(&mut self, cookie: C)
+    where
+        C: Into<Cookie<'static>>,
+    {
+        self.jar.remove(cookie);
+    }
+
+    /// 遍历所有Cookie。
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie<'static>> {
+        self.jar.iter()
+    }
+}
+
+/// 签名的Cookie罐。
+///
+/// Cookie的值附带签名，客户端可以读取但无法篡改，签名无效的Cookie将被忽略。
+#[derive(Clone)]
+pub struct SignedCookieJar {
+    jar: ::cookie::CookieJar,
+    keys: CookieKeys,
+}
+
+impl SignedCookieJar {
+    /// 使用给定的密钥创建一个空的Cookie罐。
+    pub fn new(keys: CookieKeys) -> Self {
+        Self {
+            jar: ::cookie::CookieJar::new(),
+            keys,
+        }
+    }
+
+    /// 获取给定名称并且签名有效的Cookie。
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        let cookie = self.jar.get(name)?;
+        self.keys
+            .iter()
+            .find_map(|key| self.jar.signed(key).verify(cookie.clone()))
+    }
+
+    /// 使用当前密钥签名并添加Cookie。
+    pub fn add<C>(&mut self, cookie: C)
+    where
+        C: Into<Cookie<'static>>,
+    {
+        self.jar.signed_mut(&self.keys.current).add(cookie);
+    }
+
+    /// 删除Cookie。
+    ///
+    /// 如果Cookie设置了路径或域，删除时需要指定相同的路径和域。
+    pub fn remove<C>(&mut self, cookie: C)
+    where
+        C: Into<Cookie<'static>>,
+    {
+        self.jar.remove(cookie);
+    }
+
+    /// 遍历所有签名有效的Cookie。
+    pub fn iter(&self) -> impl Iterator<Item = Cookie<'static>> + '_ {
+        self.jar.iter().filter_map(|cookie| self.get(cookie.name()))
+    }
+}
+
+impl fmt::Debug for SignedCookieJar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignedCookieJar")
+            .field("jar", &self.jar)
+            .field("keys", &self.keys)
+            .finish()
+    }
+}
+
+/// 加密的Cookie罐。
+///
+/// Cookie的值经过认证加密，客户端既无法读取也无法篡改，无法解密的Cookie将被忽略。
+#[derive(Clone)]
+pub struct PrivateCookieJar {
+    jar: ::cookie::CookieJar,
+    keys: CookieKeys,
+}
+
+impl PrivateCookieJar {
+    /// 使用给定的密钥创建一个空的Cookie罐。
+    pub fn new(keys: CookieKeys) -> Self {
+        Self {
+            jar: ::cookie::CookieJar::new(),
+            keys,
+        }
+    }
+
+    /// 获取给定名称并且可以解密的Cookie。
+    pub fn get(&self, name: &str) -> Option<Cookie<'static>> {
+        let cookie = self.jar.get(name)?;
+        self.keys
+            .iter()
+            .find_map(|key| self.jar.private(key).decrypt(cookie.clone()))
+    }
+
+    /// 使用当前密钥加密并添加Cookie。
+    pub fn add<C>(&mut self, cookie: C)
+    where
+        C: Into<Cookie<'static>>,
+    {
+        self.jar.private_mut(&self.keys.current).add(cookie);
+    }
+
+    /// 删除Cookie。
+    ///
+    /// 如果Cookie设置了路径或域，删除时需要指定相同的路径和域。
+    pub fn remove<C>(&mut self, cookie: C)
+    where
+        C: Into<Cookie<'static>>,
+    {
+        self.jar.remove(cookie);
+    }
+
+    /// 遍历所有可以解密的Cookie。
+    pub fn iter(&self) -> impl Iterator<Item = Cookie<'static>> + '_ {
+        self.jar.iter().filter_map(|cookie| self.get(cookie.name()))
+    }
+}
+
+impl fmt::Debug for PrivateCookieJar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrivateCookieJar")
+            .field("keys", &self.keys)
+            .finish()
+    }
+}
+
+macro_rules! impl_into_response {
+    ($($ty:ty),*) => {
+        $(
+            impl $ty {
+                /// 将新增和删除的Cookie转换为`Set-Cookie`标头。
+                ///
+                /// 返回的标头可以与响应正文组合为`(HeaderMap, T)`作为响应返回。
+                pub fn into_headers(self) -> HeaderMap {
+                    let mut headers = HeaderMap::new();
+                    append_set_cookie(&self.jar, &mut headers);
+                    headers
+                }
+            }
+
+            impl IntoResponse for $ty {
+                fn into_response(self) -> Response {
+                    self.into_headers().into_response()
+                }
+            }
+        )*
+    };
+}
+
+impl_into_response!(CookieJar, SignedCookieJar, PrivateCookieJar);
@@ -13,6 +13,11 @@ pub use extension::{extension, extension_mut};
 pub mod param;
 pub use param::{param, param_raw, params};
 
+#[cfg(feature = "cookie")]
+pub mod cookie;
+#[cfg(feature = "cookie")]
+pub use cookie::{cookies, private_cookies, signed_cookies};
+
 pub mod query;
 pub use query::query;
 
@@ -7,6 +7,59 @@ pub use puzz_core::*;
 
 pub mod middleware {
     pub use puzz_middleware::core::{add_extension, handle_error};
+
+    #[cfg(feature = "auth")]
+    pub use puzz_middleware::auth;
+
+    #[cfg(feature = "buffer")]
+    pub use puzz_middleware::buffer::{self, buffer};
+
+    #[cfg(feature = "cache")]
+    pub use puzz_middleware::cache::{self, cache};
+
+    #[cfg(feature = "catch-panic")]
+    pub use puzz_middleware::catch_panic::{self, catch_panic};
+
+    #[cfg(feature = "circuit-breaker")]
+    pub use puzz_middleware::circuit_breaker::{self, circuit_breaker};
+
+    #[cfg(feature = "conditional")]
+    pub use puzz_middleware::conditional::{self, conditional};
+
+    #[cfg(feature = "csrf")]
+    pub use puzz_middleware::csrf::{self, csrf};
+
+    #[cfg(feature = "forwarded")]
+    pub use puzz_middleware::forwarded::{self, forwarded};
+
+    #[cfg(feature = "jwt")]
+    pub use puzz_middleware::jwt::{self, jwt};
+
+    #[cfg(feature = "limit")]
+    pub use puzz_middleware::limit::{
+        self, adaptive_concurrency_limit, concurrency_limit, load_shed,
+    };
+
+    #[cfg(feature = "metrics")]
+    pub use puzz_middleware::metrics::{self, metrics};
+
+    #[cfg(feature = "rate-limit")]
+    pub use puzz_middleware::rate_limit::{self, rate_limit};
+
+    #[cfg(feature = "request-id")]
+    pub use puzz_middleware::request_id::{self, request_id};
+
+    #[cfg(feature = "retry")]
+    pub use puzz_middleware::retry;
+
+    #[cfg(feature = "security-headers")]
+    pub use puzz_middleware::security_headers::{self, security_headers};
+
+    #[cfg(feature = "session")]
+    pub use puzz_middleware::session::{self, session};
+
+    #[cfg(feature = "trace")]
+    pub use puzz_middleware::trace::{self, access_log, trace};
 }
 
 pub mod route {
@@ -1,4 +1,5 @@
 use puzz_core::body::{Body, BodyExt};
+use puzz_core::http::marker::MaybeSend;
 use puzz_core::http::{header, HeaderValue};
 use puzz_core::response::{IntoResponse, Response};
 use puzz_core::BoxError;
@@ -12,7 +13,7 @@ pub struct Html<B>(pub B);
 
 impl<B> IntoResponse for Html<B>
 where
-    B: Body + 'static,
+    B: Body + MaybeSend + 'static,
     B::Error: Into<BoxError>,
 {
     fn into_response(self) -> Response {